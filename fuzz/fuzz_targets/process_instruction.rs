@@ -0,0 +1,420 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program, sysvar,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Once, OnceLock,
+};
+use your_staking::{
+    metadata::mpl_token_metadata,
+    processor::{
+        create_user::get_user_storage_address,
+        initialize_pool::{get_pool_storage_address, get_vault_address},
+        Processor,
+    },
+    state::{
+        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES, USER_V1_STORAGE_TOTAL_BYTES,
+        YOUR_POOL_STORAGE_TOTAL_BYTES, YOUR_POOL_V1_STORAGE_TOTAL_BYTES,
+    },
+    token::{self, token_2022},
+    utils::constants::{RECEIPT_MINT_SEED, REWARDS_VAULT_SEED, STAKING_VAULT_SEED},
+};
+
+const PROGRAM_ID: Pubkey = Pubkey::new_from_array([255; 32]);
+const MAX_ACCOUNTS: usize = 16;
+
+#[derive(Debug, Arbitrary)]
+struct FuzzInput {
+    instruction_data: Vec<u8>,
+    accounts: Vec<FuzzAccount>,
+    unix_timestamp: u32,
+}
+
+/// Account passed to the program; pubkeys are indexes into `keys()` so that the fuzzer hits the
+/// program ids and the PDAs the processors check for
+#[derive(Debug, Arbitrary)]
+struct FuzzAccount {
+    key: u8,
+    owner: u8,
+    is_signer: bool,
+    is_writable: bool,
+    lamports: u64,
+    data: FuzzData,
+}
+
+#[derive(Debug, Arbitrary)]
+enum FuzzData {
+    Raw(Vec<u8>),
+    Pool(FuzzPool),
+    User(FuzzUser),
+    TokenAccount { mint: u8, owner: u8, amount: u64 },
+    Mint { supply: u64, decimals: u8 },
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzPool {
+    v1: bool,
+    owner_wallet: u8,
+    staking_vault: u8,
+    staking_mint: u8,
+    rewards_vault: u8,
+    rewards_mint: u8,
+    receipt_mint: Option<u8>,
+    nft_collection: Option<u8>,
+    migrated_from: Option<u8>,
+    fee_recipient: u8,
+    reward_rate: u64,
+    epoch_duration: u64,
+    reward_duration_end: u64,
+    last_update_time: u64,
+    total_staked: u64,
+    user_stake_count: u32,
+    reward_per_token_stored: u128,
+    referral_bps: u16,
+    referral_budget: u64,
+    reward_fee_bps: u16,
+    vesting_duration: u64,
+    rewards_owed: u64,
+    paused: bool,
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzUser {
+    v1: bool,
+    user_wallet: u8,
+    your_pool: u8,
+    referrer: Option<u8>,
+    balance_staked: u64,
+    unstake_pending: u64,
+    unstake_pending_date: i64,
+    claim_timeout_date: i64,
+    reward_per_token_complete: u128,
+    reward_per_token_pending: u64,
+    referral_rewards_owed: u64,
+    vesting_total: u64,
+    vesting_withdrawn: u64,
+    vesting_start: i64,
+    vesting_end: i64,
+    vesting_unlocked: u64,
+}
+
+fn keys() -> &'static [Pubkey] {
+    static KEYS: OnceLock<Vec<Pubkey>> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let wallets: Vec<Pubkey> = (1..=3).map(|i| Pubkey::new_from_array([i; 32])).collect();
+        let mints: Vec<Pubkey> = (4..=5).map(|i| Pubkey::new_from_array([i; 32])).collect();
+        let mut keys = vec![
+            PROGRAM_ID,
+            spl_token::id(),
+            token_2022::id(),
+            system_program::id(),
+            sysvar::rent::id(),
+            mpl_token_metadata::id(),
+        ];
+        keys.extend(&wallets);
+        keys.extend(&mints);
+        // Token accounts, NFT records and any other account without a derived address
+        keys.extend((6..=9).map(|i| Pubkey::new_from_array([i; 32])));
+        for pool_index in 0..2 {
+            let pool = get_pool_storage_address(&wallets[0], &mints[0], pool_index, &PROGRAM_ID);
+            keys.push(pool);
+            keys.push(get_vault_address(&pool, STAKING_VAULT_SEED, &PROGRAM_ID));
+            keys.push(get_vault_address(&pool, REWARDS_VAULT_SEED, &PROGRAM_ID));
+            keys.push(get_vault_address(&pool, RECEIPT_MINT_SEED, &PROGRAM_ID));
+            keys.push(Pubkey::find_program_address(&[&pool.to_bytes()], &PROGRAM_ID).0);
+            keys.extend(
+                wallets
+                    .iter()
+                    .map(|wallet| get_user_storage_address(wallet, &pool, &PROGRAM_ID)),
+            );
+        }
+        keys
+    })
+}
+
+fn key(index: u8) -> Pubkey {
+    let keys = keys();
+    keys[index as usize % keys.len()]
+}
+
+fn owner(index: u8) -> Pubkey {
+    let owners = [
+        PROGRAM_ID,
+        spl_token::id(),
+        token_2022::id(),
+        system_program::id(),
+        mpl_token_metadata::id(),
+    ];
+    owners[index as usize % owners.len()]
+}
+
+impl FuzzData {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            FuzzData::Raw(data) => data,
+            FuzzData::Pool(pool) => pool.into_bytes(),
+            FuzzData::User(user) => user.into_bytes(),
+            FuzzData::TokenAccount {
+                mint,
+                owner,
+                amount,
+            } => {
+                let mut data = vec![0u8; TokenAccount::LEN];
+                TokenAccount {
+                    mint: key(mint),
+                    owner: key(owner),
+                    amount,
+                    state: AccountState::Initialized,
+                    ..TokenAccount::default()
+                }
+                .pack_into_slice(&mut data);
+                data
+            }
+            FuzzData::Mint { supply, decimals } => {
+                let mut data = vec![0u8; Mint::LEN];
+                Mint {
+                    mint_authority: COption::None,
+                    supply,
+                    decimals,
+                    is_initialized: true,
+                    freeze_authority: COption::None,
+                }
+                .pack_into_slice(&mut data);
+                data
+            }
+        }
+    }
+}
+
+impl FuzzPool {
+    fn into_bytes(self) -> Vec<u8> {
+        let (acc_type, size) = if self.v1 {
+            (
+                AccTypesWithVersion::YourPoolDataV1,
+                YOUR_POOL_V1_STORAGE_TOTAL_BYTES,
+            )
+        } else {
+            (
+                AccTypesWithVersion::YourPoolDataV2,
+                YOUR_POOL_STORAGE_TOTAL_BYTES,
+            )
+        };
+        let pool = YourPool {
+            acc_type: acc_type as u8,
+            owner_wallet: key(self.owner_wallet),
+            your_staking_vault: key(self.staking_vault),
+            your_reward_rate: self.reward_rate,
+            your_epoch_duration: self.epoch_duration,
+            user_stake_count: self.user_stake_count,
+            pda_nonce: 0,
+            reward_duration_end: self.reward_duration_end,
+            your_staking_mint: key(self.staking_mint),
+            your_rewards_vault: key(self.rewards_vault),
+            your_rewards_mint: key(self.rewards_mint),
+            total_your_staked: self.total_staked,
+            migrated_from: self.migrated_from.map_or(Pubkey::default(), key),
+            pool_index: 0,
+            reward_per_token_stored: self.reward_per_token_stored,
+            last_update_time: self.last_update_time,
+            receipt_mint: self.receipt_mint.map_or(Pubkey::default(), key),
+            nft_collection: self.nft_collection.map_or(Pubkey::default(), key),
+            referral_bps: self.referral_bps,
+            referral_budget: self.referral_budget,
+            reward_fee_bps: self.reward_fee_bps,
+            fee_recipient: key(self.fee_recipient),
+            vesting_duration: self.vesting_duration,
+            rewards_owed: self.rewards_owed,
+            paused: self.paused as u8,
+        };
+        let mut data = vec![0u8; size];
+        pool.pack_versioned(&mut data).unwrap();
+        data
+    }
+}
+
+impl FuzzUser {
+    fn into_bytes(self) -> Vec<u8> {
+        let (acc_type, size) = if self.v1 {
+            (AccTypesWithVersion::UserDataV1, USER_V1_STORAGE_TOTAL_BYTES)
+        } else {
+            (AccTypesWithVersion::UserDataV2, USER_STORAGE_TOTAL_BYTES)
+        };
+        let user = User {
+            acc_type: acc_type as u8,
+            user_wallet: key(self.user_wallet),
+            your_pool: key(self.your_pool),
+            balance_your_staked: self.balance_staked,
+            unstake_pending: self.unstake_pending,
+            unstake_pending_date: self.unstake_pending_date,
+            nonce: 0,
+            claim_timeout_date: self.claim_timeout_date,
+            reward_per_token_complete: self.reward_per_token_complete,
+            reward_per_token_pending: self.reward_per_token_pending,
+            referrer: self.referrer.map_or(Pubkey::default(), key),
+            referral_rewards_owed: self.referral_rewards_owed,
+            vesting_total: self.vesting_total,
+            vesting_withdrawn: self.vesting_withdrawn,
+            vesting_start: self.vesting_start,
+            vesting_end: self.vesting_end,
+            vesting_unlocked: self.vesting_unlocked,
+        };
+        let mut data = vec![0u8; size];
+        user.pack_versioned(&mut data).unwrap();
+        data
+    }
+}
+
+static NOW: AtomicI64 = AtomicI64::new(0);
+
+/// Serves the clock and rent sysvars and runs SPL Token CPIs in process. Other programs are
+/// not available, so CPIs to them fail like they would on a cluster without the program.
+struct FuzzSyscallStubs;
+
+impl SyscallStubs for FuzzSyscallStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: NOW.load(Ordering::Relaxed),
+            ..Clock::default()
+        };
+        unsafe { std::ptr::write(var_addr as *mut Clock, clock) };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { std::ptr::write(var_addr as *mut Rent, Rent::default()) };
+        SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if instruction.program_id != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &PROGRAM_ID))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for account_meta in &instruction.accounts {
+            let mut account_info = account_infos
+                .iter()
+                .find(|account_info| *account_info.key == account_meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .clone();
+            // Like the runtime, refuse to escalate the privileges of the caller
+            if account_meta.is_signer
+                && !account_info.is_signer
+                && !signers.contains(&account_meta.pubkey)
+            {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if account_meta.is_writable && !account_info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            account_info.is_signer = account_meta.is_signer;
+            account_info.is_writable = account_meta.is_writable;
+            accounts.push(account_info);
+        }
+        spl_token::processor::Processor::process(
+            &instruction.program_id,
+            &accounts,
+            &instruction.data,
+        )
+    }
+}
+
+/// Lamports and, for token accounts, token amount of every account
+fn balances(account_infos: &[AccountInfo]) -> Vec<(u64, Option<u64>)> {
+    account_infos
+        .iter()
+        .map(|account_info| {
+            let amount = if token::is_token_program(account_info.owner) {
+                token::unpack_token_account(account_info)
+                    .ok()
+                    .map(|token_account| token_account.amount)
+            } else {
+                None
+            };
+            (account_info.lamports(), amount)
+        })
+        .collect()
+}
+
+fuzz_target!(|input: FuzzInput| {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(FuzzSyscallStubs));
+    });
+    NOW.store(input.unix_timestamp as i64, Ordering::Relaxed);
+
+    let mut accounts: Vec<(Pubkey, bool, bool, u64, Vec<u8>, Pubkey)> = input
+        .accounts
+        .into_iter()
+        .take(MAX_ACCOUNTS)
+        .map(|account| {
+            (
+                key(account.key),
+                account.is_signer,
+                account.is_writable,
+                account.lamports,
+                account.data.into_bytes(),
+                owner(account.owner),
+            )
+        })
+        .collect();
+
+    // The runtime passes an account listed twice as the same AccountInfo
+    let mut account_infos: Vec<AccountInfo> = Vec::with_capacity(accounts.len());
+    for (key, is_signer, is_writable, lamports, data, owner) in accounts.iter_mut() {
+        let account_info = match account_infos
+            .iter()
+            .find(|account_info| account_info.key == key)
+        {
+            Some(account_info) => account_info.clone(),
+            None => AccountInfo::new(
+                key,
+                *is_signer,
+                *is_writable,
+                lamports,
+                data,
+                owner,
+                false,
+                0,
+            ),
+        };
+        account_infos.push(account_info);
+    }
+
+    let balances_before = balances(&account_infos);
+    let result = Processor::process(&PROGRAM_ID, &account_infos, &input.instruction_data);
+    if result.is_err() {
+        assert_eq!(
+            balances_before,
+            balances(&account_infos),
+            "balances changed by a failed instruction"
+        );
+    }
+});
@@ -7,6 +7,12 @@ import { getUserStorageAccount } from "../utils";
 
 
 
+/**
+ * @deprecated Reimplements the program's reward math and drifts from it: simulate the
+ * QueryPendingRewards instruction instead, which returns the amount as a little-endian u64 in
+ * the transaction return data. `test_query_pending_rewards` in tests/staking.rs checks that
+ * amount against the program's own `utils::pending_rewards`.
+ */
 export async function getUserPendingRewards(userWallet: PublicKey): Promise<number> {
     const U64_MAX = new BN("18446744073709551615", 10);
     let yourPoolData = await YourPoolData.fromAccount(Pubkeys.yourPoolStoragePubkey);
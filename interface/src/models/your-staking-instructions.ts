@@ -6,5 +6,22 @@ export enum YourStakingInstructions {
   ClaimRewards = 4,
   ClosePool = 5,
   CloseUser = 6,
-  FinalUnstake = 7
+  FinalUnstake = 7,
+  MigratePool = 8,
+  MigrateUser = 9,
+  TransferPosition = 10,
+  MigrateStake = 11,
+  StakeNft = 12,
+  UnstakeNft = 13,
+  SetNftWeight = 14,
+  ConfigureReferrals = 15,
+  ClaimReferralRewards = 16,
+  SetFee = 17,
+  SetVestingDuration = 18,
+  WithdrawVested = 19,
+  VerifyPool = 20,
+  QueryPendingRewards = 21,
+  QueryRewardProjection = 22,
+  SetPaused = 23,
+  FundPool = 24
 }
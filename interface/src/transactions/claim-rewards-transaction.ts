@@ -9,7 +9,8 @@ import { ConnectionService } from '../config';
 import { YourStakingInstructions } from '../models';
 import { ASSOCIATED_TOKEN_PROGRAM_ID, Token, TOKEN_PROGRAM_ID } from '@solana/spl-token';
 export async function claimRewardsTransaction(
-    userWallet: PublicKey
+    userWallet: PublicKey,
+    feeRecipient?: PublicKey
 ): Promise<Transaction> {
     const connection = ConnectionService.getConnection();
 
@@ -86,7 +87,15 @@ export async function claimRewardsTransaction(
                 isSigner: false,
                 isWritable: false,
             },
+            {
+                pubkey: Pubkeys.rewardsMintPubkey,
+                isSigner: false,
+                isWritable: false,
+            },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+            ...(feeRecipient
+                ? [{ pubkey: feeRecipient, isSigner: false, isWritable: true }]
+                : []),
         ],
         data: Buffer.from([
             YourStakingInstructions.ClaimRewards
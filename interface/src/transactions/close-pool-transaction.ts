@@ -110,6 +110,16 @@ export async function closePoolTransaction(
                 isWritable: false,
             },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+            {
+                pubkey: Pubkeys.stakingMintPubkey,
+                isSigner: false,
+                isWritable: true,
+            },
+            {
+                pubkey: Pubkeys.rewardsMintPubkey,
+                isSigner: false,
+                isWritable: true,
+            },
         ],
         data: Buffer.from([
             YourStakingInstructions.ClosePool
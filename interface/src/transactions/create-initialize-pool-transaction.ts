@@ -1,9 +1,8 @@
-import { AccountLayout, Token, TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import {
-    Keypair,
-    LAMPORTS_PER_SOL,
     PublicKey,
     SystemProgram,
+    SYSVAR_RENT_PUBKEY,
     Transaction,
     TransactionInstruction,
 } from '@solana/web3.js';
@@ -11,77 +10,32 @@ import BN from 'bn.js';
 import { ConnectionService } from '../config';
 import { Pubkeys } from '../constants';
 import { YourStakingInstructions } from '../models';
-import {findAssociatedTokenAddress, getPoolSignerPdaNonce} from '../utils';
+import {
+    findAssociatedTokenAddress,
+    getPoolSignerPdaNonce,
+    getPoolStorageAccount,
+    getVaultAccount,
+} from '../utils';
 
 export async function createInitializePoolTransaction(
     poolOwnerWallet: PublicKey,
-    yourPoolStorageAccount: Keypair,
-    yourStakingVault: Keypair,
-    yourRewardsVault: Keypair,
+    poolIndex: number,
     rewardDurationInDays: number,
     fundPoolAmount: number
 ): Promise<Transaction> {
     const connection = ConnectionService.getConnection();
-    const poolStorageBytes = 94;
     const rewardDuration = rewardDurationInDays * 86400;
-    console.log('Pool Storage Pubkey: ', yourPoolStorageAccount.publicKey.toString());
-    console.log('Staking Vault Pubkey: ', yourStakingVault.publicKey.toString());
-    console.log('Rewards Vault Pubkey: ', yourRewardsVault.publicKey.toString());
-    const newAccountKeypair = Keypair.generate();
-    const createStakingVaultIx = SystemProgram.createAccount({
-        space: AccountLayout.span,
-        lamports: await connection.getMinimumBalanceForRentExemption(
-            AccountLayout.span,
-            'confirmed'
-        ),
-        fromPubkey: poolOwnerWallet,
-        newAccountPubkey: yourStakingVault.publicKey,
-        programId: TOKEN_PROGRAM_ID,
-    });
-
-    const initStakingVaultIx = Token.createInitAccountInstruction(
-        TOKEN_PROGRAM_ID,
+    const yourPoolStoragePubkey = await getPoolStorageAccount(
+        poolOwnerWallet,
         Pubkeys.stakingMintPubkey,
-        yourStakingVault.publicKey,
-        poolOwnerWallet
-    );
-    const createRewardsVaultIx = SystemProgram.createAccount({
-        space: AccountLayout.span,
-        lamports: await connection.getMinimumBalanceForRentExemption(
-            AccountLayout.span,
-            'confirmed'
-        ),
-        fromPubkey: poolOwnerWallet,
-        newAccountPubkey: yourRewardsVault.publicKey,
-        programId: TOKEN_PROGRAM_ID,
-    });
-
-    const initRewardsVaultIx = Token.createInitAccountInstruction(
-        TOKEN_PROGRAM_ID,
-        Pubkeys.rewardsMintPubkey,
-        yourRewardsVault.publicKey,
-        poolOwnerWallet
+        poolIndex
     );
+    console.log('Pool Storage Pubkey: ', yourPoolStoragePubkey.toString());
+    const yourStakingVaultPubkey = await getVaultAccount(yourPoolStoragePubkey, 'staking');
+    const yourRewardsVaultPubkey = await getVaultAccount(yourPoolStoragePubkey, 'rewards');
+    console.log('Staking Vault Pubkey: ', yourStakingVaultPubkey.toString());
+    console.log('Rewards Vault Pubkey: ', yourRewardsVaultPubkey.toString());
     const pool_nonce = await getPoolSignerPdaNonce();
-    const rentPrice = await connection.getMinimumBalanceForRentExemption(
-        poolStorageBytes,
-        'confirmed'
-    );
-    const createPoolStorageAccountIx = SystemProgram.createAccount({
-        space: poolStorageBytes,
-        lamports: rentPrice,
-        fromPubkey: poolOwnerWallet,
-        newAccountPubkey: yourPoolStorageAccount.publicKey,
-        programId: Pubkeys.yourStakingProgramId,
-    });
-
-    const balance = await connection.getBalance(poolOwnerWallet);
-    if (balance < rentPrice)
-        throw new Error(
-            `Need at least ${rentPrice / LAMPORTS_PER_SOL
-            } SOL for contest account rent`
-        );
-
     const funderWallet = poolOwnerWallet; // admin account
     const rewardsATAPubkey = await findAssociatedTokenAddress(
         funderWallet,
@@ -94,10 +48,10 @@ export async function createInitializePoolTransaction(
             {
                 pubkey: poolOwnerWallet,
                 isSigner: true,
-                isWritable: false,
+                isWritable: true,
             },
             {
-                pubkey: yourPoolStorageAccount.publicKey,
+                pubkey: yourPoolStoragePubkey,
                 isSigner: false,
                 isWritable: true,
             },
@@ -107,7 +61,7 @@ export async function createInitializePoolTransaction(
                 isWritable: false,
             },
             {
-                pubkey: yourStakingVault.publicKey,
+                pubkey: yourStakingVaultPubkey,
                 isSigner: false,
                 isWritable: true,
             },
@@ -117,7 +71,7 @@ export async function createInitializePoolTransaction(
                 isWritable: false,
             },
             {
-                pubkey: yourRewardsVault.publicKey,
+                pubkey: yourRewardsVaultPubkey,
                 isSigner: false,
                 isWritable: true,
             },
@@ -135,30 +89,31 @@ export async function createInitializePoolTransaction(
                 pubkey: TOKEN_PROGRAM_ID,
                 isSigner: false,
                 isWritable: false,
+            },
+            {
+                pubkey: SystemProgram.programId,
+                isSigner: false,
+                isWritable: false,
+            },
+            {
+                pubkey: SYSVAR_RENT_PUBKEY,
+                isSigner: false,
+                isWritable: false,
             }
         ],
         data: Buffer.from([
             YourStakingInstructions.InitializeYourPool,
             ...new BN(rewardDuration).toArray('le', 8), ...new BN(pool_nonce.valueOf()).toArray('le', 1), ... new BN
-            (fundPoolAmount).toArray('le', 8)
+            (fundPoolAmount).toArray('le', 8), ...new BN(poolIndex).toArray('le', 8)
         ])
     });
 
-    const transaction = new Transaction().add(
-        createStakingVaultIx,
-        initStakingVaultIx,
-        createRewardsVaultIx,
-        initRewardsVaultIx,
-        createPoolStorageAccountIx,
-        initPoolStorageAccountIx
-    );
+    const transaction = new Transaction().add(initPoolStorageAccountIx);
 
     transaction.recentBlockhash = (
         await connection.getRecentBlockhash()
     ).blockhash;
     transaction.feePayer = poolOwnerWallet;
 
-    transaction.partialSign(yourStakingVault, yourRewardsVault, yourPoolStorageAccount);
-
     return transaction;
 }
@@ -10,7 +10,8 @@ import { ConnectionService } from '../config';
 import { YourStakingInstructions } from '../models';
 import BN from 'bn.js';
 export async function createUserTransaction(
-    userWallet: PublicKey
+    userWallet: PublicKey,
+    referrer?: PublicKey
 ): Promise<Transaction> {
     const connection = ConnectionService.getConnection();
 
@@ -46,7 +47,8 @@ export async function createUserTransaction(
             },
         ],
         data: Buffer.from([
-            YourStakingInstructions.CreateUser, ...new BN(nonce.valueOf()).toArray('le', 1)
+            YourStakingInstructions.CreateUser, ...new BN(nonce.valueOf()).toArray('le', 1),
+            ...(referrer ? referrer.toBytes() : []),
         ]),
     });
     const createUserTx = new Transaction().add(createUserIx);
@@ -61,6 +61,11 @@ export async function finalUnstakeYourTransaction(
                 isSigner: false,
                 isWritable: false,
             },
+            {
+                pubkey: Pubkeys.stakingMintPubkey,
+                isSigner: false,
+                isWritable: false,
+            },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         ],
         data: Buffer.from([
@@ -57,6 +57,11 @@ export async function stakeYourTransaction(
                 isSigner: false,
                 isWritable: true,
             },
+            {
+                pubkey: Pubkeys.stakingMintPubkey,
+                isSigner: false,
+                isWritable: false,
+            },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         ],
         data: Buffer.from([
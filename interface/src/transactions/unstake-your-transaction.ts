@@ -46,7 +46,7 @@ export async function unstakeYourTransaction(
             {
                 pubkey: Pubkeys.yourPoolStoragePubkey,
                 isSigner: false,
-                isWritable: true,
+                isWritable: false,
             },
 
             {
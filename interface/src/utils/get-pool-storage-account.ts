@@ -0,0 +1,26 @@
+import {PublicKey} from '@solana/web3.js';
+import BN from 'bn.js';
+import {Pubkeys} from '../constants';
+
+export async function getPoolStorageAccount(
+  poolOwnerWallet: PublicKey,
+  stakingMint: PublicKey,
+  poolIndex: number
+): Promise<PublicKey> {
+  return (await getPoolStorageAccountWithNonce(poolOwnerWallet, stakingMint, poolIndex))[0];
+}
+
+export async function getPoolStorageAccountWithNonce(
+  poolOwnerWallet: PublicKey,
+  stakingMint: PublicKey,
+  poolIndex: number
+): Promise<[PublicKey, Number]> {
+  return await PublicKey.findProgramAddress(
+    [
+      poolOwnerWallet.toBuffer(),
+      stakingMint.toBuffer(),
+      new BN(poolIndex).toArrayLike(Buffer, 'le', 8),
+    ],
+    Pubkeys.yourStakingProgramId
+  );
+}
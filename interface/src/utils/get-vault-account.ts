@@ -0,0 +1,14 @@
+import {PublicKey} from '@solana/web3.js';
+import {Pubkeys} from '../constants';
+
+export async function getVaultAccount(
+  yourPoolStoragePubkey: PublicKey,
+  vaultSeed: 'staking' | 'rewards'
+): Promise<PublicKey> {
+  return (
+    await PublicKey.findProgramAddress(
+      [yourPoolStoragePubkey.toBuffer(), Buffer.from(vaultSeed)],
+      Pubkeys.yourStakingProgramId
+    )
+  )[0];
+}
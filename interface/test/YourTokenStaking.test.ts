@@ -11,9 +11,7 @@ import {
     adminAccount,
     setupEnvironment,
     walletAccount,
-    yourPoolStorageAccount,
-    yourStakingVault,
-    yourRewardsVault,
+    poolIndex,
     rewardDurationInDays,
 
 } from "./prepereTestsEvironment";
@@ -32,17 +30,12 @@ describe('Your Token Staking Tests', () => {
         const connection = ConnectionService.getConnection();
         const initializePoolTx = await createInitializePoolTransaction(
             adminAccount.publicKey,
-            yourPoolStorageAccount,
-            yourStakingVault,
-            yourRewardsVault,
+            poolIndex,
             rewardDurationInDays,
             10000
         );
         await sendAndConfirmTransaction(connection, initializePoolTx, [
             adminAccount,
-            yourPoolStorageAccount,
-            yourStakingVault,
-            yourRewardsVault,
         ]);
     });
 
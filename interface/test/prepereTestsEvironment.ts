@@ -3,7 +3,7 @@ import {Keypair, sendAndConfirmTransaction, Transaction} from "@solana/web3.js";
 import {Constants, Pubkeys} from "../src/constants";
 import {ConnectionService} from "../src/config";
 import {ASSOCIATED_TOKEN_PROGRAM_ID, Token, TOKEN_PROGRAM_ID, u64} from '@solana/spl-token';
-import {findAssociatedTokenAddress} from "../src/utils";
+import {findAssociatedTokenAddress, getPoolStorageAccount, getVaultAccount} from "../src/utils";
 import BN from "bn.js";
 import {
     createInitializePoolTransaction, createUserTransaction
@@ -11,9 +11,7 @@ import {
 
 const adminAccount: Keypair = getAdminAccount();
 const walletAccount: Keypair = Keypair.generate();
-let yourPoolStorageAccount: Keypair;
-let yourStakingVault: Keypair;
-let yourRewardsVault: Keypair;
+const poolIndex: number = 0;
 const rewardDurationInDays: number = 1 / 86400;
 const yourDecimals = 9;
 const rewardTokenDecimals = 9;
@@ -49,9 +47,6 @@ async function setupEnvironment() {
     );
     Pubkeys.rewardsMintPubkey = rewardTokenMint.publicKey;
 
-    yourPoolStorageAccount = Keypair.generate();
-    yourStakingVault = Keypair.generate();
-    yourRewardsVault = Keypair.generate();
 
     const funderRewardTokenData = await findAssociatedTokenAddress(
         adminAccount.publicKey,
@@ -92,9 +87,19 @@ async function setupEnvironment() {
     );
 
     await requestAirdrop(adminAccount.publicKey);
-    Pubkeys.yourPoolStoragePubkey = yourPoolStorageAccount.publicKey;
-    Pubkeys.yourStakingVaultPubkey = yourStakingVault.publicKey;
-    Pubkeys.yourRewardsVaultPubkey = yourRewardsVault.publicKey;
+    Pubkeys.yourPoolStoragePubkey = await getPoolStorageAccount(
+        adminAccount.publicKey,
+        Pubkeys.stakingMintPubkey,
+        poolIndex
+    );
+    Pubkeys.yourStakingVaultPubkey = await getVaultAccount(
+        Pubkeys.yourPoolStoragePubkey,
+        'staking'
+    );
+    Pubkeys.yourRewardsVaultPubkey = await getVaultAccount(
+        Pubkeys.yourPoolStoragePubkey,
+        'rewards'
+    );
 
     await requestAirdrop(walletAccount.publicKey);
 
@@ -135,8 +140,6 @@ export {
     adminAccount,
     walletAccount,
     setupEnvironment,
-    yourPoolStorageAccount,
-    yourStakingVault,
-    yourRewardsVault,
+    poolIndex,
     rewardDurationInDays
 }
\ No newline at end of file
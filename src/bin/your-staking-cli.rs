@@ -0,0 +1,725 @@
+//! Command-line tool for pool operators. It initializes, funds, pauses, inspects and closes
+//! pools, lists their users, projects their APR and APY and decodes the program accounts into
+//! JSON, reading the RPC URL and the keypair from the Solana CLI config unless they are given.
+
+use clap::{
+    crate_version, value_t, value_t_or_exit, App, AppSettings, Arg, ArgMatches, SubCommand,
+};
+use serde_json::json;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+};
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+    system_program, sysvar,
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+use std::{error::Error, str::FromStr};
+use your_staking::{
+    decoder::{decode_account, PoolView, UserView},
+    instruction::Instruction as StakingInstruction,
+    processor::initialize_pool::{get_pool_storage_address, get_vault_address},
+    state::{User, YourPool, USER_STORAGE_TOTAL_BYTES, USER_V1_STORAGE_TOTAL_BYTES},
+    token,
+    utils::{
+        self,
+        constants::{RECEIPT_MINT_SEED, REWARDS_VAULT_SEED, STAKING_VAULT_SEED},
+        TokenPrices,
+    },
+};
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+mod associated_token {
+    solana_sdk::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+}
+
+// User accounts store their pool right after the account type byte and the user wallet
+const USER_POOL_OFFSET: usize = 1 + 32;
+
+struct Config {
+    rpc_client: RpcClient,
+    keypair_path: String,
+    program_id: Option<Pubkey>,
+}
+
+impl Config {
+    fn program_id(&self) -> CliResult<Pubkey> {
+        self.program_id
+            .ok_or_else(|| "--program-id or YOUR_STAKING_PROGRAM_ID is required".into())
+    }
+
+    fn payer(&self) -> CliResult<Keypair> {
+        read_keypair_file(&self.keypair_path)
+            .map_err(|err| format!("cannot read keypair {}: {}", self.keypair_path, err).into())
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> CliResult<()> {
+    let cli_config = solana_cli_config::CONFIG_FILE
+        .as_ref()
+        .and_then(|config_file| solana_cli_config::Config::load(config_file).ok())
+        .unwrap_or_default();
+
+    let pool_arg = Arg::with_name("pool")
+        .value_name("POOL")
+        .required(true)
+        .validator(is_pubkey)
+        .help("Pool storage account");
+    let funding_account_arg = Arg::with_name("funding_account")
+        .long("funding-account")
+        .value_name("ADDRESS")
+        .takes_value(true)
+        .validator(is_pubkey)
+        .help("Rewards token account to debit [default: the keypair's associated token account]");
+
+    let matches = App::new("your-staking-cli")
+        .version(crate_version!())
+        .about("Operates YOUR staking pools")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("url")
+                .long("url")
+                .short("u")
+                .global(true)
+                .value_name("URL")
+                .takes_value(true)
+                .help("RPC URL [default: the Solana CLI config]"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .long("keypair")
+                .short("k")
+                .global(true)
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Pool owner keypair file, also paying the fees [default: the Solana CLI config]"),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .global(true)
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .env("YOUR_STAKING_PROGRAM_ID")
+                .validator(is_pubkey)
+                .help("Address of the deployed staking program"),
+        )
+        .subcommand(
+            SubCommand::with_name("init-pool")
+                .about("Create a pool with its vaults and fund its rewards")
+                .arg(
+                    Arg::with_name("staking_mint")
+                        .long("staking-mint")
+                        .value_name("ADDRESS")
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Mint staked in the pool, the collection mint for NFT pools"),
+                )
+                .arg(
+                    Arg::with_name("rewards_mint")
+                        .long("rewards-mint")
+                        .value_name("ADDRESS")
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Mint the rewards are paid in"),
+                )
+                .arg(
+                    Arg::with_name("reward_duration")
+                        .long("reward-duration")
+                        .value_name("SECONDS")
+                        .required(true)
+                        .help("Duration of the reward period"),
+                )
+                .arg(
+                    Arg::with_name("fund_amount")
+                        .long("fund-amount")
+                        .value_name("AMOUNT")
+                        .default_value("0")
+                        .help("Rewards paid over the period, in base units"),
+                )
+                .arg(
+                    Arg::with_name("pool_index")
+                        .long("pool-index")
+                        .value_name("INDEX")
+                        .default_value("0")
+                        .help("Index of the pool among the owner's pools for the staking mint"),
+                )
+                .arg(funding_account_arg.clone())
+                .arg(
+                    Arg::with_name("with_receipt_mint")
+                        .long("with-receipt-mint")
+                        .help("Mint 1:1 receipt tokens for the staked tokens"),
+                )
+                .arg(
+                    Arg::with_name("nft_pool")
+                        .long("nft-pool")
+                        .help("Stake NFTs of the verified collection given as staking mint"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fund-pool")
+                .about("Add rewards to a pool and restart its reward period")
+                .arg(pool_arg.clone())
+                .arg(
+                    Arg::with_name("amount")
+                        .long("amount")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .help("Rewards added to the pool, in base units"),
+                )
+                .arg(funding_account_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("fund-referrals")
+                .about("Top up the referral budget of a pool")
+                .arg(pool_arg.clone())
+                .arg(
+                    Arg::with_name("amount")
+                        .long("amount")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .help("Rewards added to the referral budget, in base units"),
+                )
+                .arg(
+                    Arg::with_name("referral_bps")
+                        .long("referral-bps")
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .help("New referral share in basis points [default: the current share]"),
+                )
+                .arg(funding_account_arg),
+        )
+        .subcommand(
+            SubCommand::with_name("pause-pool")
+                .about("Stop a pool from taking new stakes, or resume it")
+                .arg(pool_arg.clone())
+                .arg(
+                    Arg::with_name("unpause")
+                        .long("unpause")
+                        .help("Resume taking new stakes"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect-pool")
+                .about("Print a pool, its vault balances and the VerifyPool result as JSON")
+                .arg(pool_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("projection")
+                .about("Print the current and projected APR and APY of a deposit as JSON")
+                .arg(pool_arg.clone())
+                .arg(
+                    Arg::with_name("deposit")
+                        .long("deposit")
+                        .value_name("AMOUNT")
+                        .default_value("0")
+                        .help("Hypothetical deposit added to the pool, in base units"),
+                )
+                .arg(
+                    Arg::with_name("staking_price")
+                        .long("staking-price")
+                        .value_name("PRICE")
+                        .requires("rewards_price")
+                        .help("Price of a whole staking token, in any quote unit"),
+                )
+                .arg(
+                    Arg::with_name("rewards_price")
+                        .long("rewards-price")
+                        .value_name("PRICE")
+                        .requires("staking_price")
+                        .help("Price of a whole rewards token, in the same quote unit"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("close-pool")
+                .about("Close an ended pool without users and refund its vaults")
+                .arg(pool_arg.clone())
+                .arg(
+                    Arg::with_name("staking_refund")
+                        .long("staking-refund")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .validator(is_pubkey)
+                        .help("Staking token account refunded [default: the keypair's associated token account]"),
+                )
+                .arg(
+                    Arg::with_name("rewards_refund")
+                        .long("rewards-refund")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .validator(is_pubkey)
+                        .help("Rewards token account refunded [default: the keypair's associated token account]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list-users")
+                .about("Print the user accounts of a pool as JSON")
+                .arg(pool_arg),
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("Print a pool, user or NFT record account as JSON")
+                .arg(
+                    Arg::with_name("account")
+                        .value_name("ADDRESS")
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Account owned by the staking program"),
+                ),
+        )
+        .get_matches();
+
+    let config = Config {
+        rpc_client: RpcClient::new_with_commitment(
+            matches
+                .value_of("url")
+                .unwrap_or(&cli_config.json_rpc_url)
+                .to_string(),
+            CommitmentConfig::confirmed(),
+        ),
+        keypair_path: matches
+            .value_of("keypair")
+            .unwrap_or(&cli_config.keypair_path)
+            .to_string(),
+        program_id: value_t!(matches, "program_id", Pubkey).ok(),
+    };
+
+    match matches.subcommand() {
+        ("init-pool", Some(arg_matches)) => command_init_pool(&config, arg_matches),
+        ("fund-pool", Some(arg_matches)) => command_fund_pool(&config, arg_matches),
+        ("fund-referrals", Some(arg_matches)) => command_fund_referrals(&config, arg_matches),
+        ("pause-pool", Some(arg_matches)) => command_pause_pool(&config, arg_matches),
+        ("inspect-pool", Some(arg_matches)) => command_inspect_pool(&config, arg_matches),
+        ("projection", Some(arg_matches)) => command_projection(&config, arg_matches),
+        ("close-pool", Some(arg_matches)) => command_close_pool(&config, arg_matches),
+        ("list-users", Some(arg_matches)) => command_list_users(&config, arg_matches),
+        ("decode", Some(arg_matches)) => command_decode(&config, arg_matches),
+        _ => unreachable!(),
+    }
+}
+
+fn command_init_pool(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let payer = config.payer()?;
+    let staking_mint = value_t_or_exit!(matches, "staking_mint", Pubkey);
+    let rewards_mint = value_t_or_exit!(matches, "rewards_mint", Pubkey);
+    let reward_duration = value_t_or_exit!(matches, "reward_duration", u64);
+    let fund_amount = value_t_or_exit!(matches, "fund_amount", u64);
+    let pool_index = value_t_or_exit!(matches, "pool_index", u64);
+    let with_receipt_mint = matches.is_present("with_receipt_mint");
+    let nft_pool = matches.is_present("nft_pool");
+
+    let staking_token_program = get_token_program(config, &staking_mint)?;
+    let rewards_token_program = get_token_program(config, &rewards_mint)?;
+    let funding_account = value_t!(matches, "funding_account", Pubkey).unwrap_or_else(|_| {
+        get_associated_token_address(&payer.pubkey(), &rewards_mint, &rewards_token_program)
+    });
+
+    let pool_storage =
+        get_pool_storage_address(&payer.pubkey(), &staking_mint, pool_index, &program_id);
+    let mut accounts = vec![
+        AccountMeta::new(payer.pubkey(), true),
+        AccountMeta::new(pool_storage, false),
+        AccountMeta::new_readonly(staking_mint, false),
+        AccountMeta::new(
+            get_vault_address(&pool_storage, STAKING_VAULT_SEED, &program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(rewards_mint, false),
+        AccountMeta::new(
+            get_vault_address(&pool_storage, REWARDS_VAULT_SEED, &program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(payer.pubkey(), true),
+        AccountMeta::new(funding_account, false),
+        AccountMeta::new_readonly(staking_token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    if with_receipt_mint {
+        accounts.push(AccountMeta::new(
+            get_vault_address(&pool_storage, RECEIPT_MINT_SEED, &program_id),
+            false,
+        ));
+    }
+    if rewards_token_program != staking_token_program {
+        accounts.push(AccountMeta::new_readonly(rewards_token_program, false));
+    }
+
+    let instruction = Instruction {
+        program_id,
+        accounts,
+        data: StakingInstruction::InitializePool {
+            reward_duration,
+            pool_nonce: 0,
+            fund_amount,
+            pool_index,
+            with_receipt_mint,
+            nft_pool,
+        }
+        .pack(),
+    };
+    let signature = send(config, &payer, &[instruction])?;
+    println!("Pool: {}", pool_storage);
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+fn command_fund_pool(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let payer = config.payer()?;
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+    let amount = value_t_or_exit!(matches, "amount", u64);
+    let your_pool_data = get_v2_pool(config, &pool_storage)?;
+
+    let rewards_token_program = get_token_program(config, &your_pool_data.your_rewards_mint)?;
+    let funding_account = value_t!(matches, "funding_account", Pubkey).unwrap_or_else(|_| {
+        get_associated_token_address(
+            &payer.pubkey(),
+            &your_pool_data.your_rewards_mint,
+            &rewards_token_program,
+        )
+    });
+
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(pool_storage, false),
+            AccountMeta::new(your_pool_data.your_rewards_vault, false),
+            AccountMeta::new(funding_account, false),
+            AccountMeta::new_readonly(your_pool_data.your_rewards_mint, false),
+            AccountMeta::new_readonly(rewards_token_program, false),
+        ],
+        data: StakingInstruction::FundPool { amount }.pack(),
+    };
+    let signature = send(config, &payer, &[instruction])?;
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+fn command_fund_referrals(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let payer = config.payer()?;
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+    let fund_amount = value_t_or_exit!(matches, "amount", u64);
+    let your_pool_data = get_v2_pool(config, &pool_storage)?;
+    let referral_bps = if matches.is_present("referral_bps") {
+        value_t_or_exit!(matches, "referral_bps", u16)
+    } else {
+        your_pool_data.referral_bps
+    };
+
+    let rewards_token_program = get_token_program(config, &your_pool_data.your_rewards_mint)?;
+    let funding_account = value_t!(matches, "funding_account", Pubkey).unwrap_or_else(|_| {
+        get_associated_token_address(
+            &payer.pubkey(),
+            &your_pool_data.your_rewards_mint,
+            &rewards_token_program,
+        )
+    });
+
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(pool_storage, false),
+            AccountMeta::new(your_pool_data.your_rewards_vault, false),
+            AccountMeta::new(funding_account, false),
+            AccountMeta::new_readonly(your_pool_data.your_rewards_mint, false),
+            AccountMeta::new_readonly(rewards_token_program, false),
+        ],
+        data: StakingInstruction::ConfigureReferrals {
+            referral_bps,
+            fund_amount,
+        }
+        .pack(),
+    };
+    let signature = send(config, &payer, &[instruction])?;
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+fn command_pause_pool(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let payer = config.payer()?;
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+    get_v2_pool(config, &pool_storage)?;
+
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(pool_storage, false),
+        ],
+        data: StakingInstruction::SetPaused {
+            paused: !matches.is_present("unpause"),
+        }
+        .pack(),
+    };
+    let signature = send(config, &payer, &[instruction])?;
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+fn command_inspect_pool(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+    let your_pool_data = get_pool(config, &pool_storage)?;
+
+    let mut output = json!({
+        "address": pool_storage.to_string(),
+        "pool": PoolView::from(&your_pool_data),
+        "stakingVaultAmount": get_token_amount(config, &your_pool_data.your_staking_vault)?,
+    });
+    if !your_pool_data.is_v1() {
+        output["rewardsVaultAmount"] = json!(get_token_amount(
+            config,
+            &your_pool_data.your_rewards_vault
+        )?);
+
+        // VerifyPool writes nothing, so a simulation gives its result without paying for it
+        let payer = config.payer()?;
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(pool_storage, false),
+                AccountMeta::new_readonly(your_pool_data.your_staking_vault, false),
+                AccountMeta::new_readonly(your_pool_data.your_rewards_vault, false),
+            ],
+            data: StakingInstruction::VerifyPool {}.pack(),
+        };
+        let (recent_blockhash, _fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let simulation = config.rpc_client.simulate_transaction(&transaction)?.value;
+        output["verifyPool"] = match simulation.err {
+            None => json!("ok"),
+            Some(err) => json!(err.to_string()),
+        };
+    }
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+fn command_projection(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+    let deposit = value_t_or_exit!(matches, "deposit", u64);
+    let your_pool_data = get_v2_pool(config, &pool_storage)?;
+
+    let prices = if matches.is_present("staking_price") {
+        Some(TokenPrices {
+            staking_price: value_t_or_exit!(matches, "staking_price", u64),
+            staking_decimals: get_mint_decimals(config, &your_pool_data.your_staking_mint)?,
+            rewards_price: value_t_or_exit!(matches, "rewards_price", u64),
+            rewards_decimals: get_mint_decimals(config, &your_pool_data.your_rewards_mint)?,
+        })
+    } else {
+        None
+    };
+    // Projected from the cluster clock rather than the local one, as the program would
+    let clock: Clock = from_account(&config.rpc_client.get_account(&sysvar::clock::id())?)
+        .ok_or("cannot read the clock sysvar")?;
+    let projection =
+        utils::reward_projection(&your_pool_data, deposit, prices, clock.unix_timestamp)?;
+
+    let output = json!({
+        "address": pool_storage.to_string(),
+        "deposit": deposit,
+        "currentAprBps": projection.current_apr_bps,
+        "currentApyBps": projection.current_apy_bps,
+        "projectedAprBps": projection.projected_apr_bps,
+        "projectedApyBps": projection.projected_apy_bps,
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+fn command_close_pool(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let payer = config.payer()?;
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+    let your_pool_data = get_v2_pool(config, &pool_storage)?;
+
+    let staking_token_program = get_token_program(config, &your_pool_data.your_staking_mint)?;
+    let rewards_token_program = get_token_program(config, &your_pool_data.your_rewards_mint)?;
+    let staking_refund = value_t!(matches, "staking_refund", Pubkey).unwrap_or_else(|_| {
+        get_associated_token_address(
+            &payer.pubkey(),
+            &your_pool_data.your_staking_mint,
+            &staking_token_program,
+        )
+    });
+    let rewards_refund = value_t!(matches, "rewards_refund", Pubkey).unwrap_or_else(|_| {
+        get_associated_token_address(
+            &payer.pubkey(),
+            &your_pool_data.your_rewards_mint,
+            &rewards_token_program,
+        )
+    });
+    let (pool_signer, _bump_seed) =
+        Pubkey::find_program_address(&[&pool_storage.to_bytes()], &program_id);
+
+    let mut accounts = vec![
+        AccountMeta::new(payer.pubkey(), true),
+        AccountMeta::new(your_pool_data.your_staking_vault, false),
+        AccountMeta::new(staking_refund, false),
+        AccountMeta::new(your_pool_data.your_rewards_vault, false),
+        AccountMeta::new(rewards_refund, false),
+        AccountMeta::new(pool_storage, false),
+        AccountMeta::new_readonly(pool_signer, false),
+        AccountMeta::new_readonly(staking_token_program, false),
+        AccountMeta::new(your_pool_data.your_staking_mint, false),
+        AccountMeta::new(your_pool_data.your_rewards_mint, false),
+    ];
+    if rewards_token_program != staking_token_program {
+        accounts.push(AccountMeta::new_readonly(rewards_token_program, false));
+    }
+
+    let instruction = Instruction {
+        program_id,
+        accounts,
+        data: StakingInstruction::ClosePool {}.pack(),
+    };
+    let signature = send(config, &payer, &[instruction])?;
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+fn command_list_users(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let program_id = config.program_id()?;
+    let pool_storage = value_t_or_exit!(matches, "pool", Pubkey);
+
+    let mut users = Vec::new();
+    for data_size in [USER_V1_STORAGE_TOTAL_BYTES, USER_STORAGE_TOTAL_BYTES] {
+        let accounts = config.rpc_client.get_program_accounts_with_config(
+            &program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(data_size as u64),
+                    RpcFilterType::Memcmp(Memcmp {
+                        offset: USER_POOL_OFFSET,
+                        bytes: MemcmpEncodedBytes::Base58(pool_storage.to_string()),
+                        encoding: None,
+                    }),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                with_context: None,
+            },
+        )?;
+        for (address, account) in accounts {
+            let mut user =
+                serde_json::to_value(UserView::from(&User::unpack_versioned(&account.data)?))?;
+            user["address"] = json!(address.to_string());
+            users.push(user);
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&users)?);
+    Ok(())
+}
+
+fn command_decode(config: &Config, matches: &ArgMatches) -> CliResult<()> {
+    let address = value_t_or_exit!(matches, "account", Pubkey);
+    let account = config.rpc_client.get_account(&address)?;
+    let decoded = decode_account(&account.data)
+        .map_err(|err| format!("cannot decode {}: {}", address, err))?;
+    println!("{}", serde_json::to_string_pretty(&decoded)?);
+    Ok(())
+}
+
+fn is_pubkey(value: String) -> Result<(), String> {
+    Pubkey::from_str(&value)
+        .map(|_| ())
+        .map_err(|err| format!("{}: {}", value, err))
+}
+
+fn send(config: &Config, payer: &Keypair, instructions: &[Instruction]) -> CliResult<Signature> {
+    let (recent_blockhash, _fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        recent_blockhash,
+    );
+    Ok(config
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)?)
+}
+
+fn get_pool(config: &Config, pool_storage: &Pubkey) -> CliResult<YourPool> {
+    let account = config.rpc_client.get_account(pool_storage)?;
+    if account.owner != config.program_id()? {
+        return Err(format!("{} is not owned by the staking program", pool_storage).into());
+    }
+    Ok(YourPool::unpack_versioned(&account.data)?)
+}
+
+/// V1 pools keep neither their mints nor their rewards vault, they have to be migrated first
+fn get_v2_pool(config: &Config, pool_storage: &Pubkey) -> CliResult<YourPool> {
+    let your_pool_data = get_pool(config, pool_storage)?;
+    if your_pool_data.is_v1() {
+        return Err(format!("{} is a V1 pool, migrate it first", pool_storage).into());
+    }
+    Ok(your_pool_data)
+}
+
+fn get_token_program(config: &Config, mint: &Pubkey) -> CliResult<Pubkey> {
+    let owner = config.rpc_client.get_account(mint)?.owner;
+    if !token::is_token_program(&owner) {
+        return Err(format!("{} is not owned by a token program", mint).into());
+    }
+    Ok(owner)
+}
+
+fn get_token_amount(config: &Config, token_account: &Pubkey) -> CliResult<u64> {
+    let data = config.rpc_client.get_account(token_account)?.data;
+    let base = data
+        .get(..TokenAccount::LEN)
+        .ok_or_else(|| format!("{} is not a token account", token_account))?;
+    Ok(TokenAccount::unpack(base)?.amount)
+}
+
+fn get_mint_decimals(config: &Config, mint: &Pubkey) -> CliResult<u8> {
+    let data = config.rpc_client.get_account(mint)?.data;
+    let base = data
+        .get(..Mint::LEN)
+        .ok_or_else(|| format!("{} is not a mint", mint))?;
+    Ok(Mint::unpack(base)?.decimals)
+}
+
+fn get_associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            &wallet.to_bytes(),
+            &token_program.to_bytes(),
+            &mint.to_bytes(),
+        ],
+        &associated_token::id(),
+    )
+    .0
+}
@@ -0,0 +1,207 @@
+//! Serde views of the program accounts for off-chain consumers. Pubkeys are rendered in
+//! base58, timestamps in RFC3339 and `u128` accumulators as decimal strings.
+
+use crate::{
+    error::CustomError,
+    state::{AccTypesWithVersion, NftStake, NftWeight, User, YourPool},
+};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DecodedAccount {
+    Pool(PoolView),
+    User(UserView),
+    NftStake(NftStakeView),
+    NftWeight(NftWeightView),
+}
+
+const POOL_V1: u8 = AccTypesWithVersion::YourPoolDataV1 as u8;
+const POOL_V2: u8 = AccTypesWithVersion::YourPoolDataV2 as u8;
+const USER_V1: u8 = AccTypesWithVersion::UserDataV1 as u8;
+const USER_V2: u8 = AccTypesWithVersion::UserDataV2 as u8;
+const NFT_STAKE_V1: u8 = AccTypesWithVersion::NftStakeDataV1 as u8;
+const NFT_WEIGHT_V1: u8 = AccTypesWithVersion::NftWeightDataV1 as u8;
+
+/// Decodes a program account, dispatching on its `acc_type` byte
+pub fn decode_account(data: &[u8]) -> Result<DecodedAccount, ProgramError> {
+    match data.first() {
+        Some(&POOL_V1) | Some(&POOL_V2) => Ok(DecodedAccount::Pool(PoolView::from(
+            &YourPool::unpack_versioned(data)?,
+        ))),
+        Some(&USER_V1) | Some(&USER_V2) => Ok(DecodedAccount::User(UserView::from(
+            &User::unpack_versioned(data)?,
+        ))),
+        Some(&NFT_STAKE_V1) => Ok(DecodedAccount::NftStake(NftStakeView::from(
+            &NftStake::unpack(data)?,
+        ))),
+        Some(&NFT_WEIGHT_V1) => Ok(DecodedAccount::NftWeight(NftWeightView::from(
+            &NftWeight::unpack(data)?,
+        ))),
+        _ => Err(CustomError::ExpectedAccountTypeMismatched.into()),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolView {
+    /// Layout version, 1 for pools created before the V2 migration
+    pub version: u8,
+    pub owner_wallet: String,
+    pub staking_vault: String,
+    pub reward_rate: u64,
+    pub epoch_duration: u64,
+    pub user_stake_count: u32,
+    pub pda_nonce: u8,
+    pub reward_duration_end: Option<String>,
+    pub staking_mint: String,
+    pub rewards_vault: String,
+    pub rewards_mint: String,
+    pub total_staked: u64,
+    pub migrated_from: String,
+    pub pool_index: u64,
+    pub reward_per_token_stored: String,
+    pub last_update_time: Option<String>,
+    pub receipt_mint: String,
+    pub nft_collection: String,
+    pub referral_bps: u16,
+    pub referral_budget: u64,
+    pub reward_fee_bps: u16,
+    pub fee_recipient: String,
+    pub vesting_duration: u64,
+    pub rewards_owed: u64,
+    pub paused: bool,
+}
+
+impl From<&YourPool> for PoolView {
+    fn from(your_pool_data: &YourPool) -> Self {
+        PoolView {
+            version: if your_pool_data.is_v1() { 1 } else { 2 },
+            owner_wallet: base58(&your_pool_data.owner_wallet),
+            staking_vault: base58(&your_pool_data.your_staking_vault),
+            reward_rate: your_pool_data.your_reward_rate,
+            epoch_duration: your_pool_data.your_epoch_duration,
+            user_stake_count: your_pool_data.user_stake_count,
+            pda_nonce: your_pool_data.pda_nonce,
+            reward_duration_end: rfc3339(your_pool_data.reward_duration_end as i64),
+            staking_mint: base58(&your_pool_data.your_staking_mint),
+            rewards_vault: base58(&your_pool_data.your_rewards_vault),
+            rewards_mint: base58(&your_pool_data.your_rewards_mint),
+            total_staked: your_pool_data.total_your_staked,
+            migrated_from: base58(&your_pool_data.migrated_from),
+            pool_index: your_pool_data.pool_index,
+            reward_per_token_stored: { your_pool_data.reward_per_token_stored }.to_string(),
+            last_update_time: rfc3339(your_pool_data.last_update_time as i64),
+            receipt_mint: base58(&your_pool_data.receipt_mint),
+            nft_collection: base58(&your_pool_data.nft_collection),
+            referral_bps: your_pool_data.referral_bps,
+            referral_budget: your_pool_data.referral_budget,
+            reward_fee_bps: your_pool_data.reward_fee_bps,
+            fee_recipient: base58(&your_pool_data.fee_recipient),
+            vesting_duration: your_pool_data.vesting_duration,
+            rewards_owed: your_pool_data.rewards_owed,
+            paused: your_pool_data.is_paused(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserView {
+    /// Layout version, 1 for users created before the V2 migration
+    pub version: u8,
+    pub user_wallet: String,
+    pub pool: String,
+    pub balance_staked: u64,
+    pub unstake_pending: u64,
+    pub unstake_pending_date: Option<String>,
+    pub nonce: u8,
+    pub claim_timeout_date: Option<String>,
+    pub reward_per_token_complete: String,
+    pub reward_per_token_pending: u64,
+    pub referrer: String,
+    pub referral_rewards_owed: u64,
+    pub vesting_total: u64,
+    pub vesting_withdrawn: u64,
+    pub vesting_start: Option<String>,
+    pub vesting_end: Option<String>,
+    pub vesting_unlocked: u64,
+}
+
+impl From<&User> for UserView {
+    fn from(user_storage_data: &User) -> Self {
+        UserView {
+            version: if user_storage_data.is_v1() { 1 } else { 2 },
+            user_wallet: base58(&user_storage_data.user_wallet),
+            pool: base58(&user_storage_data.your_pool),
+            balance_staked: user_storage_data.balance_your_staked,
+            unstake_pending: user_storage_data.unstake_pending,
+            unstake_pending_date: rfc3339(user_storage_data.unstake_pending_date),
+            nonce: user_storage_data.nonce,
+            claim_timeout_date: rfc3339(user_storage_data.claim_timeout_date),
+            reward_per_token_complete: { user_storage_data.reward_per_token_complete }.to_string(),
+            reward_per_token_pending: user_storage_data.reward_per_token_pending,
+            referrer: base58(&user_storage_data.referrer),
+            referral_rewards_owed: user_storage_data.referral_rewards_owed,
+            vesting_total: user_storage_data.vesting_total,
+            vesting_withdrawn: user_storage_data.vesting_withdrawn,
+            vesting_start: rfc3339(user_storage_data.vesting_start),
+            vesting_end: rfc3339(user_storage_data.vesting_end),
+            vesting_unlocked: user_storage_data.vesting_unlocked,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftStakeView {
+    pub pool: String,
+    pub user_wallet: String,
+    pub nft_mint: String,
+    pub weight: u64,
+}
+
+impl From<&NftStake> for NftStakeView {
+    fn from(nft_stake_data: &NftStake) -> Self {
+        NftStakeView {
+            pool: base58(&nft_stake_data.your_pool),
+            user_wallet: base58(&nft_stake_data.user_wallet),
+            nft_mint: base58(&nft_stake_data.nft_mint),
+            weight: nft_stake_data.weight,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftWeightView {
+    pub pool: String,
+    pub nft_mint: String,
+    pub weight: u64,
+}
+
+impl From<&NftWeight> for NftWeightView {
+    fn from(nft_weight_data: &NftWeight) -> Self {
+        NftWeightView {
+            pool: base58(&nft_weight_data.your_pool),
+            nft_mint: base58(&nft_weight_data.nft_mint),
+            weight: nft_weight_data.weight,
+        }
+    }
+}
+
+fn base58(pubkey: &Pubkey) -> String {
+    bs58::encode(pubkey).into_string()
+}
+
+/// Renders a unix timestamp in RFC3339, the program leaving unset dates at 0
+fn rfc3339(unix_timestamp: i64) -> Option<String> {
+    if unix_timestamp == 0 {
+        return None;
+    }
+    NaiveDateTime::from_timestamp_opt(unix_timestamp, 0).map(|date_time| {
+        DateTime::<Utc>::from_utc(date_time, Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+    })
+}
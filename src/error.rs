@@ -70,6 +70,76 @@ pub enum CustomError {
     // Pool Address Already Initialized
     #[error("User final unstake timeout didn't expired")]
     UserFinalUnstakeTimeout,
+    /// Invalid Rewards Vault
+    #[error("Invalid Rewards Vault")]
+    InvalidRewardsVault,
+    /// User Still Has Staked Tokens
+    #[error("User still has staked tokens")]
+    UserBalanceNotEmpty,
+    /// User Still Has A Pending Unstake
+    #[error("User still has a pending unstake")]
+    UserUnstakePendingNotEmpty,
+    /// User Still Has Unclaimed Rewards
+    #[error("User still has unclaimed rewards")]
+    UserRewardsNotClaimed,
+    /// Invalid Receipt Mint
+    #[error("Invalid Receipt Mint")]
+    InvalidReceiptMint,
+    /// Pool Mode Mismatched
+    #[error("Instruction is not supported by this pool mode")]
+    PoolModeMismatched,
+    /// Invalid NFT Metadata
+    #[error("Invalid NFT Metadata")]
+    InvalidNftMetadata,
+    /// NFT Collection Not Verified
+    #[error("NFT is not a verified member of the pool collection")]
+    NftCollectionNotVerified,
+    /// Invalid Referrer
+    #[error("Invalid Referrer")]
+    InvalidReferrer,
+    /// Referral Share Too High
+    #[error("Referral share cannot exceed 100%")]
+    ReferralShareTooHigh,
+    /// Reward Fee Too High
+    #[error("Reward fee exceeds the maximum")]
+    RewardFeeTooHigh,
+    /// Invalid Fee Recipient
+    #[error("Invalid Fee Recipient")]
+    InvalidFeeRecipient,
+    /// User Still Has Vesting Rewards
+    #[error("User still has vesting rewards")]
+    UserVestingNotEmpty,
+    /// Reward Duration Too Long
+    #[error("Reward duration exceeds the maximum")]
+    RewardDurationTooLong,
+    // 36 was RewardRateTooHigh, the reward rate is now derived from the funded amount
+    /// Staking Vault Insolvent
+    #[error("Staking vault holds less than the staked amount")]
+    StakingVaultInsolvent = 37,
+    /// Rewards Vault Insolvent
+    #[error("Rewards vault holds less than the outstanding rewards")]
+    RewardsVaultInsolvent,
+    /// Account Owner Should Be Program
+    #[error("Account Owner Should Be Program")]
+    AccountOwnerShouldBeProgram,
+    /// Referral Rewards Not Claimed
+    #[error("Referral rewards owed to the referrer were not claimed")]
+    ReferralRewardsNotClaimed,
+    /// Reward Duration Too Short
+    #[error("Reward duration is below the minimum")]
+    RewardDurationTooShort,
+    /// Same Source And Destination
+    #[error("Source and destination must differ")]
+    SameSourceAndDestination,
+    /// Receipt Pool Not Transferable
+    #[error("Stake of a receipt-mint pool cannot be moved")]
+    ReceiptPoolNotTransferable,
+    /// Insufficient Stake To Transfer
+    #[error("Insufficient active stake to transfer")]
+    InsufficientStakeToTransfer,
+    /// Pool Paused
+    #[error("Pool is paused and takes no new stake")]
+    PoolPaused,
 }
 
 impl From<CustomError> for ProgramError {
@@ -0,0 +1,182 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{entrypoint::ProgramResult, log::sol_log_data, pubkey::Pubkey};
+
+/// Machine-readable record of a state change, emitted as a single `sol_log_data` field.
+/// New variants are only ever appended so the Borsh discriminants stay stable for indexers.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum StakingEvent {
+    PoolInitialized {
+        pool: Pubkey,
+        owner_wallet: Pubkey,
+        staking_mint: Pubkey,
+        rewards_mint: Pubkey,
+        pool_index: u64,
+        epoch_duration: u64,
+        timestamp: i64,
+    },
+    Staked {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        balance_staked: u64,
+        total_staked: u64,
+        timestamp: i64,
+    },
+    UnstakeRequested {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        unstake_pending_date: i64,
+        timestamp: i64,
+    },
+    Unstaked {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        balance_staked: u64,
+        total_staked: u64,
+        timestamp: i64,
+    },
+    RewardsClaimed {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        claim_timeout_date: i64,
+        timestamp: i64,
+    },
+    PoolFunded {
+        pool: Pubkey,
+        funder_wallet: Pubkey,
+        amount: u64,
+        reward_rate: u64,
+        reward_duration_end: u64,
+        timestamp: i64,
+    },
+    PoolClosed {
+        pool: Pubkey,
+        staking_refund_amount: u64,
+        rewards_refund_amount: u64,
+        timestamp: i64,
+    },
+    UserClosed {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        user_stake_count: u32,
+        timestamp: i64,
+    },
+    UserCreated {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        user_stake_count: u32,
+        timestamp: i64,
+    },
+    PoolMigrated {
+        pool_v1: Pubkey,
+        pool: Pubkey,
+        total_staked: u64,
+        timestamp: i64,
+    },
+    UserMigrated {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        balance_staked: u64,
+        timestamp: i64,
+    },
+    PositionTransferred {
+        pool: Pubkey,
+        from_wallet: Pubkey,
+        to_wallet: Pubkey,
+        amount: u64,
+        rewards_amount: u64,
+        timestamp: i64,
+    },
+    StakeMigrated {
+        pool: Pubkey,
+        destination_pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        timestamp: i64,
+    },
+    NftStaked {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        nft_mint: Pubkey,
+        weight: u64,
+        balance_staked: u64,
+        total_staked: u64,
+        timestamp: i64,
+    },
+    NftUnstaked {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        nft_mint: Pubkey,
+        weight: u64,
+        balance_staked: u64,
+        total_staked: u64,
+        timestamp: i64,
+    },
+    ReferralsConfigured {
+        pool: Pubkey,
+        referral_bps: u16,
+        funded_amount: u64,
+        referral_budget: u64,
+        timestamp: i64,
+    },
+    ReferralRewardsClaimed {
+        pool: Pubkey,
+        referrer_wallet: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        timestamp: i64,
+    },
+    RewardFeeSet {
+        pool: Pubkey,
+        reward_fee_bps: u16,
+        fee_recipient: Pubkey,
+        timestamp: i64,
+    },
+    RewardFeeCollected {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        fee_recipient: Pubkey,
+        amount: u64,
+        timestamp: i64,
+    },
+    VestingDurationSet {
+        pool: Pubkey,
+        vesting_duration: u64,
+        timestamp: i64,
+    },
+    RewardsVested {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        vesting_total: u64,
+        vesting_end: i64,
+        timestamp: i64,
+    },
+    VestedWithdrawn {
+        pool: Pubkey,
+        user_wallet: Pubkey,
+        amount: u64,
+        timestamp: i64,
+    },
+    NftWeightSet {
+        pool: Pubkey,
+        mint: Pubkey,
+        weight: u64,
+        timestamp: i64,
+    },
+    PausedSet {
+        pool: Pubkey,
+        paused: bool,
+        timestamp: i64,
+    },
+}
+
+impl StakingEvent {
+    pub fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&self.try_to_vec()?]);
+        Ok(())
+    }
+}
@@ -1,14 +1,18 @@
 use crate::error::CustomError::InvalidInstruction;
-use solana_program::program_error::ProgramError;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
 use std::convert::TryInto;
 pub enum Instruction {
     InitializePool {
         reward_duration: u64,
         pool_nonce: u8,
         fund_amount: u64,
+        pool_index: u64,
+        with_receipt_mint: bool,
+        nft_pool: bool,
     },
     CreateUser {
         nonce: u8,
+        referrer: Option<Pubkey>,
     },
     Stake {
         amount_to_deposit: u64,
@@ -20,22 +24,75 @@ pub enum Instruction {
     ClosePool {},
     CloseUser {},
     FinalUnstake {},
+    MigratePool {
+        pool_index: u64,
+    },
+    MigrateUser {},
+    TransferPosition {
+        amount: u64,
+    },
+    MigrateStake {},
+    StakeNft {},
+    UnstakeNft {},
+    SetNftWeight {
+        weight: u64,
+    },
+    ConfigureReferrals {
+        referral_bps: u16,
+        fund_amount: u64,
+    },
+    ClaimReferralRewards {},
+    SetFee {
+        reward_fee_bps: u16,
+    },
+    SetVestingDuration {
+        vesting_duration: u64,
+    },
+    WithdrawVested {},
+    VerifyPool {},
+    QueryPendingRewards {},
+    QueryRewardProjection {
+        deposit: u64,
+        staking_price: u64,
+        rewards_price: u64,
+    },
+    SetPaused {
+        paused: bool,
+    },
+    FundPool {
+        amount: u64,
+    },
 }
 
 impl Instruction {
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        Ok(match input[0] {
+        Ok(match *input.first().ok_or(InvalidInstruction)? {
             0 => Self::InitializePool {
-                reward_duration: Self::unpack_to_u64(&input[1..9])?,
-                pool_nonce: input[9],
-                fund_amount: Self::unpack_to_u64(&input[10..18])?,
+                reward_duration: Self::unpack_to_u64(input, 1)?,
+                pool_nonce: Self::unpack_to_u8(input, 9)?,
+                fund_amount: Self::unpack_to_u64(input, 10)?,
+                // Clients from before pool indexes send 18 bytes and keep creating pool 0
+                pool_index: match input.len() {
+                    18 => 0,
+                    _ => Self::unpack_to_u64(input, 18)?,
+                },
+                with_receipt_mint: input.get(26).map_or(false, |flag| *flag != 0),
+                nft_pool: input.get(27).map_or(false, |flag| *flag != 0),
+            },
+            1 => Self::CreateUser {
+                nonce: Self::unpack_to_u8(input, 1)?,
+                // The referrer is optional, any other length is malformed
+                referrer: match input.len() {
+                    2 => None,
+                    34 => Some(Pubkey::new(&input[2..34])),
+                    _ => return Err(InvalidInstruction.into()),
+                },
             },
-            1 => Self::CreateUser { nonce: input[1] },
             2 => Self::Stake {
-                amount_to_deposit: Self::unpack_to_u64(&input[1..9])?,
+                amount_to_deposit: Self::unpack_to_u64(input, 1)?,
             },
             3 => Self::Unstake {
-                amount_to_withdraw: Self::unpack_to_u64(&input[1..9])?,
+                amount_to_withdraw: Self::unpack_to_u64(input, 1)?,
             },
 
             4 => Self::ClaimRewards {},
@@ -46,16 +103,182 @@ impl Instruction {
 
             7 => Self::FinalUnstake {},
 
+            8 => Self::MigratePool {
+                pool_index: Self::unpack_to_u64(input, 1)?,
+            },
+
+            9 => Self::MigrateUser {},
+
+            10 => Self::TransferPosition {
+                amount: Self::unpack_to_u64(input, 1)?,
+            },
+
+            11 => Self::MigrateStake {},
+
+            12 => Self::StakeNft {},
+
+            13 => Self::UnstakeNft {},
+
+            14 => Self::SetNftWeight {
+                weight: Self::unpack_to_u64(input, 1)?,
+            },
+
+            15 => Self::ConfigureReferrals {
+                referral_bps: Self::unpack_to_u16(input, 1)?,
+                fund_amount: Self::unpack_to_u64(input, 3)?,
+            },
+
+            16 => Self::ClaimReferralRewards {},
+
+            17 => Self::SetFee {
+                reward_fee_bps: Self::unpack_to_u16(input, 1)?,
+            },
+
+            18 => Self::SetVestingDuration {
+                vesting_duration: Self::unpack_to_u64(input, 1)?,
+            },
+
+            19 => Self::WithdrawVested {},
+
+            20 => Self::VerifyPool {},
+
+            21 => Self::QueryPendingRewards {},
+
+            22 => Self::QueryRewardProjection {
+                deposit: Self::unpack_to_u64(input, 1)?,
+                staking_price: Self::unpack_to_u64(input, 9)?,
+                rewards_price: Self::unpack_to_u64(input, 17)?,
+            },
+
+            23 => Self::SetPaused {
+                paused: Self::unpack_to_u8(input, 1)? != 0,
+            },
+
+            24 => Self::FundPool {
+                amount: Self::unpack_to_u64(input, 1)?,
+            },
+
             _ => return Err(InvalidInstruction.into()),
         })
     }
 
-    fn unpack_to_u64(input: &[u8]) -> Result<u64, ProgramError> {
+    /// Serializes the instruction into the data `unpack` reads, for clients building transactions
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Self::InitializePool {
+                reward_duration,
+                pool_nonce,
+                fund_amount,
+                pool_index,
+                with_receipt_mint,
+                nft_pool,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&reward_duration.to_le_bytes());
+                buf.push(*pool_nonce);
+                buf.extend_from_slice(&fund_amount.to_le_bytes());
+                buf.extend_from_slice(&pool_index.to_le_bytes());
+                buf.push(*with_receipt_mint as u8);
+                buf.push(*nft_pool as u8);
+            }
+            Self::CreateUser { nonce, referrer } => {
+                buf.push(1);
+                buf.push(*nonce);
+                if let Some(referrer) = referrer {
+                    buf.extend_from_slice(referrer.as_ref());
+                }
+            }
+            Self::Stake { amount_to_deposit } => {
+                buf.push(2);
+                buf.extend_from_slice(&amount_to_deposit.to_le_bytes());
+            }
+            Self::Unstake { amount_to_withdraw } => {
+                buf.push(3);
+                buf.extend_from_slice(&amount_to_withdraw.to_le_bytes());
+            }
+            Self::ClaimRewards {} => buf.push(4),
+            Self::ClosePool {} => buf.push(5),
+            Self::CloseUser {} => buf.push(6),
+            Self::FinalUnstake {} => buf.push(7),
+            Self::MigratePool { pool_index } => {
+                buf.push(8);
+                buf.extend_from_slice(&pool_index.to_le_bytes());
+            }
+            Self::MigrateUser {} => buf.push(9),
+            Self::TransferPosition { amount } => {
+                buf.push(10);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::MigrateStake {} => buf.push(11),
+            Self::StakeNft {} => buf.push(12),
+            Self::UnstakeNft {} => buf.push(13),
+            Self::SetNftWeight { weight } => {
+                buf.push(14);
+                buf.extend_from_slice(&weight.to_le_bytes());
+            }
+            Self::ConfigureReferrals {
+                referral_bps,
+                fund_amount,
+            } => {
+                buf.push(15);
+                buf.extend_from_slice(&referral_bps.to_le_bytes());
+                buf.extend_from_slice(&fund_amount.to_le_bytes());
+            }
+            Self::ClaimReferralRewards {} => buf.push(16),
+            Self::SetFee { reward_fee_bps } => {
+                buf.push(17);
+                buf.extend_from_slice(&reward_fee_bps.to_le_bytes());
+            }
+            Self::SetVestingDuration { vesting_duration } => {
+                buf.push(18);
+                buf.extend_from_slice(&vesting_duration.to_le_bytes());
+            }
+            Self::WithdrawVested {} => buf.push(19),
+            Self::VerifyPool {} => buf.push(20),
+            Self::QueryPendingRewards {} => buf.push(21),
+            Self::QueryRewardProjection {
+                deposit,
+                staking_price,
+                rewards_price,
+            } => {
+                buf.push(22);
+                buf.extend_from_slice(&deposit.to_le_bytes());
+                buf.extend_from_slice(&staking_price.to_le_bytes());
+                buf.extend_from_slice(&rewards_price.to_le_bytes());
+            }
+            Self::SetPaused { paused } => {
+                buf.push(23);
+                buf.push(*paused as u8);
+            }
+            Self::FundPool { amount } => {
+                buf.push(24);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    fn unpack_to_u8(input: &[u8], offset: usize) -> Result<u8, ProgramError> {
+        let out_value = *input.get(offset).ok_or(InvalidInstruction)?;
+        Ok(out_value)
+    }
+
+    fn unpack_to_u16(input: &[u8], offset: usize) -> Result<u16, ProgramError> {
+        let out_value = input
+            .get(offset..offset + 2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(InvalidInstruction)?;
+        Ok(out_value)
+    }
+
+    fn unpack_to_u64(input: &[u8], offset: usize) -> Result<u64, ProgramError> {
         let out_value = input
-            .get(..8)
+            .get(offset..offset + 8)
             .and_then(|slice| slice.try_into().ok())
             .map(u64::from_le_bytes)
             .ok_or(InvalidInstruction)?;
         Ok(out_value)
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,21 @@
+/// `msg!` for diagnostic logs, compiled in only with the `debug-logs` feature as every log
+/// line costs compute units. Errors keep logging through `msg!`.
+macro_rules! debug_msg {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "debug-logs")]
+        solana_program::msg!($($arg)*);
+    };
+}
+
+#[cfg(feature = "decoder")]
+pub mod decoder;
 pub mod error;
+pub mod events;
 pub mod instruction;
+pub mod metadata;
 pub mod processor;
 pub mod utils;
 pub mod state;
+pub mod token;
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
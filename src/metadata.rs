@@ -0,0 +1,114 @@
+use crate::error::CustomError;
+use solana_program::{account_info::AccountInfo, msg, program_error::ProgramError, pubkey::Pubkey};
+use std::convert::TryInto;
+
+pub mod mpl_token_metadata {
+    solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+const METADATA_PREFIX: &[u8] = b"metadata";
+const KEY_METADATA_V1: u8 = 4;
+const CREATOR_LEN: usize = 32 + 1 + 1;
+
+/// Derives the Metaplex metadata account address of a mint
+pub fn get_metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            METADATA_PREFIX,
+            &mpl_token_metadata::id().to_bytes(),
+            &mint.to_bytes(),
+        ],
+        &mpl_token_metadata::id(),
+    )
+    .0
+}
+
+/// Returns the collection of the NFT when the metadata marks it as verified.
+/// The Borsh layout of the Metaplex `Metadata` account is read up to the `collection`
+/// field so the program does not depend on the metadata crate.
+pub fn get_verified_collection(
+    metadata_info: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<Option<Pubkey>, ProgramError> {
+    if *metadata_info.owner != mpl_token_metadata::id()
+        || *metadata_info.key != get_metadata_address(mint)
+    {
+        msg!("CustomError::InvalidNftMetadata");
+        return Err(CustomError::InvalidNftMetadata.into());
+    }
+
+    let data = metadata_info.try_borrow_data()?;
+    let mut reader = MetadataReader { data: &data };
+    if reader.read_u8()? != KEY_METADATA_V1 {
+        msg!("CustomError::InvalidNftMetadata");
+        return Err(CustomError::InvalidNftMetadata.into());
+    }
+    reader.skip(32)?; // update_authority
+    if reader.read_pubkey()? != *mint {
+        msg!("CustomError::InvalidNftMetadata");
+        return Err(CustomError::InvalidNftMetadata.into());
+    }
+    for _ in 0..3 {
+        let length = reader.read_u32()? as usize; // name, symbol, uri
+        reader.skip(length)?;
+    }
+    reader.skip(2)?; // seller_fee_basis_points
+    if reader.read_u8()? == 1 {
+        let creators = reader.read_u32()? as usize;
+        reader.skip(
+            creators
+                .checked_mul(CREATOR_LEN)
+                .ok_or(CustomError::InvalidNftMetadata)?,
+        )?;
+    }
+    reader.skip(2)?; // primary_sale_happened, is_mutable
+    for _ in 0..2 {
+        if reader.read_u8()? == 1 {
+            reader.skip(1)?; // edition_nonce, token_standard
+        }
+    }
+    if reader.read_u8()? == 1 {
+        let verified = reader.read_u8()? == 1;
+        let collection = reader.read_pubkey()?;
+        if verified {
+            return Ok(Some(collection));
+        }
+    }
+    Ok(None)
+}
+
+struct MetadataReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> MetadataReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ProgramError> {
+        if self.data.len() < length {
+            msg!("CustomError::InvalidNftMetadata");
+            return Err(CustomError::InvalidNftMetadata.into());
+        }
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), ProgramError> {
+        self.take(length).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ProgramError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ProgramError> {
+        let bytes = self
+            .take(4)?
+            .try_into()
+            .map_err(|_| CustomError::InvalidNftMetadata)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_pubkey(&mut self) -> Result<Pubkey, ProgramError> {
+        Ok(Pubkey::new(self.take(32)?))
+    }
+}
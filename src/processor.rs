@@ -1,22 +1,48 @@
 use crate::instruction::Instruction;
 
 use {
-    claim_rewards::process_claim_rewards, close_pool::process_close_pool,
-    close_user::process_close_user, create_user::process_create_user,
-    final_unstake::process_final_unstake, initialize_pool::process_initialize_your_pool,
-    stake::process_stake, unstake::process_unstake,
+    claim_referral_rewards::process_claim_referral_rewards, claim_rewards::process_claim_rewards,
+    close_pool::process_close_pool, close_user::process_close_user,
+    configure_referrals::process_configure_referrals, create_user::process_create_user,
+    final_unstake::process_final_unstake, fund_pool::process_fund_pool, initialize_pool::process_initialize_your_pool,
+    migrate_pool::process_migrate_pool, migrate_stake::process_migrate_stake,
+    migrate_user::process_migrate_user, query_pending_rewards::process_query_pending_rewards,
+    query_reward_projection::process_query_reward_projection, set_fee::process_set_fee,
+    set_nft_weight::process_set_nft_weight, set_paused::process_set_paused,
+    set_vesting_duration::process_set_vesting_duration,
+    stake::process_stake, stake_nft::process_stake_nft,
+    transfer_position::process_transfer_position, unstake::process_unstake,
+    unstake_nft::process_unstake_nft, verify_pool::process_verify_pool,
+    withdraw_vested::process_withdraw_vested,
 };
 
+pub mod claim_referral_rewards;
 pub mod claim_rewards;
 pub mod close_pool;
 pub mod close_user;
+pub mod configure_referrals;
 pub mod create_user;
 pub mod final_unstake;
+pub mod fund_pool;
 pub mod initialize_pool;
+pub mod migrate_pool;
+pub mod migrate_stake;
+pub mod migrate_user;
+pub mod query_pending_rewards;
+pub mod query_reward_projection;
+pub mod set_fee;
+pub mod set_nft_weight;
+pub mod set_paused;
+pub mod set_vesting_duration;
 pub mod stake;
+pub mod stake_nft;
+pub mod transfer_position;
 pub mod unstake;
+pub mod unstake_nft;
+pub mod verify_pool;
+pub mod withdraw_vested;
 
-use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, msg, pubkey::Pubkey};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
 
 pub struct Processor;
 impl Processor {
@@ -31,50 +57,154 @@ impl Processor {
                 reward_duration,
                 pool_nonce,
                 fund_amount,
+                pool_index,
+                with_receipt_mint,
+                nft_pool,
             } => {
-                msg!("Instruction::InitializePool");
+                debug_msg!("Instruction::InitializePool");
                 process_initialize_your_pool(
                     accounts,
                     reward_duration,
                     pool_nonce,
                     fund_amount,
+                    pool_index,
+                    with_receipt_mint,
+                    nft_pool,
                     program_id,
                 )
             }
-            Instruction::CreateUser { nonce } => {
-                msg!("Instruction::CreateUser");
-                process_create_user(accounts, nonce, program_id)
+            Instruction::CreateUser { nonce, referrer } => {
+                debug_msg!("Instruction::CreateUser");
+                process_create_user(accounts, nonce, referrer, program_id)
             }
 
             Instruction::Stake { amount_to_deposit } => {
-                msg!("Instruction::Stake");
+                debug_msg!("Instruction::Stake");
                 process_stake(accounts, amount_to_deposit, program_id)
             }
 
             Instruction::Unstake { amount_to_withdraw } => {
-                msg!("Instruction::Unstake");
+                debug_msg!("Instruction::Unstake");
                 process_unstake(accounts, amount_to_withdraw, program_id)
             }
 
             Instruction::ClaimRewards {} => {
-                msg!("Instruction::ClaimRewards");
+                debug_msg!("Instruction::ClaimRewards");
                 process_claim_rewards(accounts, program_id)
             }
 
             Instruction::ClosePool {} => {
-                msg!("Instruction::ClosePool");
+                debug_msg!("Instruction::ClosePool");
                 process_close_pool(accounts, program_id)
             }
 
             Instruction::CloseUser {} => {
-                msg!("Instruction::CloseUser");
+                debug_msg!("Instruction::CloseUser");
                 process_close_user(accounts, program_id)
             }
 
             Instruction::FinalUnstake {} => {
-                msg!("Instruction::FinalUnstake");
+                debug_msg!("Instruction::FinalUnstake");
                 process_final_unstake(accounts, program_id)
             }
+
+            Instruction::MigratePool { pool_index } => {
+                debug_msg!("Instruction::MigratePool");
+                process_migrate_pool(accounts, pool_index, program_id)
+            }
+
+            Instruction::MigrateUser {} => {
+                debug_msg!("Instruction::MigrateUser");
+                process_migrate_user(accounts, program_id)
+            }
+
+            Instruction::TransferPosition { amount } => {
+                debug_msg!("Instruction::TransferPosition");
+                process_transfer_position(accounts, amount, program_id)
+            }
+
+            Instruction::MigrateStake {} => {
+                debug_msg!("Instruction::MigrateStake");
+                process_migrate_stake(accounts, program_id)
+            }
+
+            Instruction::StakeNft {} => {
+                debug_msg!("Instruction::StakeNft");
+                process_stake_nft(accounts, program_id)
+            }
+
+            Instruction::UnstakeNft {} => {
+                debug_msg!("Instruction::UnstakeNft");
+                process_unstake_nft(accounts, program_id)
+            }
+
+            Instruction::SetNftWeight { weight } => {
+                debug_msg!("Instruction::SetNftWeight");
+                process_set_nft_weight(accounts, weight, program_id)
+            }
+
+            Instruction::ConfigureReferrals {
+                referral_bps,
+                fund_amount,
+            } => {
+                debug_msg!("Instruction::ConfigureReferrals");
+                process_configure_referrals(accounts, referral_bps, fund_amount, program_id)
+            }
+
+            Instruction::ClaimReferralRewards {} => {
+                debug_msg!("Instruction::ClaimReferralRewards");
+                process_claim_referral_rewards(accounts, program_id)
+            }
+
+            Instruction::SetFee { reward_fee_bps } => {
+                debug_msg!("Instruction::SetFee");
+                process_set_fee(accounts, reward_fee_bps, program_id)
+            }
+
+            Instruction::SetVestingDuration { vesting_duration } => {
+                debug_msg!("Instruction::SetVestingDuration");
+                process_set_vesting_duration(accounts, vesting_duration, program_id)
+            }
+
+            Instruction::WithdrawVested {} => {
+                debug_msg!("Instruction::WithdrawVested");
+                process_withdraw_vested(accounts, program_id)
+            }
+
+            Instruction::VerifyPool {} => {
+                debug_msg!("Instruction::VerifyPool");
+                process_verify_pool(accounts, program_id)
+            }
+
+            Instruction::QueryPendingRewards {} => {
+                debug_msg!("Instruction::QueryPendingRewards");
+                process_query_pending_rewards(accounts, program_id)
+            }
+
+            Instruction::QueryRewardProjection {
+                deposit,
+                staking_price,
+                rewards_price,
+            } => {
+                debug_msg!("Instruction::QueryRewardProjection");
+                process_query_reward_projection(
+                    accounts,
+                    deposit,
+                    staking_price,
+                    rewards_price,
+                    program_id,
+                )
+            }
+
+            Instruction::SetPaused { paused } => {
+                debug_msg!("Instruction::SetPaused");
+                process_set_paused(accounts, paused, program_id)
+            }
+
+            Instruction::FundPool { amount } => {
+                debug_msg!("Instruction::FundPool");
+                process_fund_pool(accounts, amount, program_id)
+            }
         }
     }
 }
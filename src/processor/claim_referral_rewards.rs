@@ -0,0 +1,166 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::create_user::get_user_storage_address,
+    state::{User, YourPool},
+    token, utils,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Pays the referrer the rewards set aside for them from one referee's position. The referee
+/// can trigger the payout too, or have CloseUser pay it.
+///
+/// 0. `[signer]` Referrer or Referee Wallet Account
+/// 1. `[writable]` Referee User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[writable]` YOUR Rewards Vault
+/// 4. `[writable]` Referrer Rewards Token Account
+/// 5. `[]` Pool Signer PDA
+/// 6. `[]` YOUR Rewards Mint
+/// 7. `[]` Token Program of the rewards mint
+pub fn process_claim_referral_rewards(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let signer_wallet_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_rewards_vault = next_account_info(account_info_iter)?;
+    let referrer_rewards_ata = next_account_info(account_info_iter)?;
+    let pool_signer_pda = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if !signer_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if your_pool_storage_account.owner != program_id || user_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+
+    if get_user_storage_address(
+        &user_storage_data.user_wallet,
+        your_pool_storage_account.key,
+        program_id,
+    ) != *user_storage_account.key
+    {
+        msg!("Error: User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if user_storage_data.your_pool != *your_pool_storage_account.key {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+    if !user_storage_data.has_referrer()
+        || (user_storage_data.referrer != *signer_wallet_account.key
+            && user_storage_data.user_wallet != *signer_wallet_account.key)
+    {
+        msg!("CustomError::InvalidReferrer");
+        return Err(CustomError::InvalidReferrer.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+
+    pay_referral_rewards(
+        your_pool_storage_account,
+        &mut your_pool_data,
+        &mut user_storage_data,
+        your_rewards_vault,
+        referrer_rewards_ata,
+        pool_signer_pda,
+        your_rewards_mint,
+        token_program,
+        program_id,
+        now,
+    )?;
+
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+
+    Ok(())
+}
+
+/// Transfers the referral rewards owed from the referee's position to the referrer and clears
+/// them. The pool and the user have to be updated to `now` first.
+#[allow(clippy::too_many_arguments)]
+pub fn pay_referral_rewards<'a>(
+    your_pool_storage_account: &AccountInfo<'a>,
+    your_pool_data: &mut YourPool,
+    user_storage_data: &mut User,
+    your_rewards_vault: &AccountInfo<'a>,
+    referrer_rewards_ata: &AccountInfo<'a>,
+    pool_signer_pda: &AccountInfo<'a>,
+    your_rewards_mint: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+    now: i64,
+) -> ProgramResult {
+    if !token::is_token_program(token_program.key) {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+    let (pool_signer_address, bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    if your_pool_data.your_rewards_vault != *your_rewards_vault.key {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+    let referrer_rewards_ata_data = token::unpack_token_account(referrer_rewards_ata)?;
+    if referrer_rewards_ata_data.owner != user_storage_data.referrer {
+        msg!("CustomError::InvalidReferrer");
+        return Err(CustomError::InvalidReferrer.into());
+    }
+    if referrer_rewards_ata_data.mint != your_pool_data.your_rewards_mint
+        || *your_rewards_mint.key != your_pool_data.your_rewards_mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let amount_to_claim = user_storage_data.referral_rewards_owed;
+    if amount_to_claim > 0 {
+        debug_msg!("Calling the token program to transfer YOUR to Referrer from Rewards Vault...");
+        token::transfer_checked(
+            token_program,
+            your_rewards_vault,
+            your_rewards_mint,
+            referrer_rewards_ata,
+            pool_signer_pda,
+            amount_to_claim,
+            &[&[&your_pool_storage_account.key.to_bytes(), &[bump_seed]]],
+        )?;
+        utils::pay_out_rewards(your_pool_data, amount_to_claim);
+    }
+    user_storage_data.referral_rewards_owed = 0u64;
+
+    StakingEvent::ReferralRewardsClaimed {
+        pool: *your_pool_storage_account.key,
+        referrer_wallet: user_storage_data.referrer,
+        user_wallet: user_storage_data.user_wallet,
+        amount: amount_to_claim,
+        timestamp: now,
+    }
+    .emit()
+}
@@ -1,25 +1,31 @@
 use crate::{
     error::CustomError,
+    events::StakingEvent,
     processor::create_user::get_user_storage_address_and_bump_seed,
-    state::{
-        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES,
-        YOUR_POOL_STORAGE_TOTAL_BYTES,
-    },
+    state::{User, YourPool},
+    token, utils,
+    utils::constants::BPS_DENOMINATOR,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
     program_error::ProgramError,
-    program_pack::Pack,
     pubkey::Pubkey,
     sysvar::clock::Clock,
     sysvar::Sysvar,
 };
-use spl_token::state::Account as TokenAccount;
 
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[]` YOUR Staking Vault
+/// 4. `[writable]` YOUR Rewards Vault
+/// 5. `[writable]` User Rewards Token Account
+/// 6. `[]` Pool Signer PDA
+/// 7. `[]` YOUR Rewards Mint
+/// 8. `[]` Token Program of the rewards mint
+/// 9. `[writable]` Fee Recipient Rewards Token Account, only when the pool takes a reward fee
 pub fn process_claim_rewards(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let user_wallet_account = next_account_info(account_info_iter)?;
@@ -29,13 +35,15 @@ pub fn process_claim_rewards(accounts: &[AccountInfo], program_id: &Pubkey) -> P
     let your_rewards_vault = next_account_info(account_info_iter)?;
     let user_rewards_ata = next_account_info(account_info_iter)?;
     let pool_signer_pda = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
+    let fee_recipient_account = account_info_iter.next();
 
     if !user_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
     }
-    if token_program.key != &spl_token::id() {
+    if !token::is_token_program(token_program.key) {
         msg!("CustomError::InvalidTokenProgram");
         return Err(CustomError::InvalidTokenProgram.into());
     }
@@ -50,31 +58,15 @@ pub fn process_claim_rewards(accounts: &[AccountInfo], program_id: &Pubkey) -> P
         return Err(ProgramError::InvalidSeeds);
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
     }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
 
-    if user_storage_account.data_len() != USER_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-
-    let mut user_data_byte_array = user_storage_account.data.try_borrow_mut().unwrap();
-    let mut user_storage_data: User =
-        User::try_from_slice(&user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]).unwrap();
-    if user_storage_data.acc_type != AccTypesWithVersion::UserDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
 
     if user_storage_data.user_wallet != *user_wallet_account.key {
         msg!("CustomError::UserStorageAuthorityMismatched");
@@ -85,50 +77,147 @@ pub fn process_claim_rewards(accounts: &[AccountInfo], program_id: &Pubkey) -> P
         return Err(CustomError::UserPoolMismatched.into());
     }
 
-    if your_staking_vault.owner != token_program.key {
+    if !token::is_token_program(your_staking_vault.owner) {
         msg!("CustomError::AccountOwnerShouldBeTokenProgram");
         return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
     }
 
-    let your_staking_vault_data = TokenAccount::unpack(&your_staking_vault.data.borrow())?;
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
     let (pool_signer_address, bump_seed) =
         Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
 
-    if your_staking_vault_data.owner != pool_signer_address {
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    if your_staking_vault_data.owner != pool_signer_address
+        || your_pool_data.your_staking_vault != *your_staking_vault.key
+    {
         msg!("CustomError::InvalidStakingVault");
         return Err(CustomError::InvalidStakingVault.into());
     }
 
-    let now = Clock::get()?.unix_timestamp as i64;
+    if your_rewards_vault.owner != token_program.key {
+        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+    }
+    let your_rewards_vault_data = token::unpack_token_account(your_rewards_vault)?;
+    if your_rewards_vault_data.owner != pool_signer_address
+        || (!your_pool_data.is_v1() && your_pool_data.your_rewards_vault != *your_rewards_vault.key)
+    {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+
+    let user_rewards_ata_data = token::unpack_token_account(user_rewards_ata)?;
+    if user_rewards_ata_data.mint != your_rewards_vault_data.mint
+        || *your_rewards_mint.key != your_rewards_vault_data.mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
     if user_storage_data.claim_timeout_date <= now || user_storage_data.claim_timeout_date == 0 {
-        msg!("Calling the token program to transfer YOUR to User from Rewards Vault...");
-        invoke_signed(
-            &spl_token::instruction::transfer(
-                token_program.key,
-                your_rewards_vault.key,
-                user_rewards_ata.key,
-                &pool_signer_address,
-                &[&pool_signer_address],
-                2,
-            )?,
-            &[
-                your_rewards_vault.clone(),
-                user_rewards_ata.clone(),
-                pool_signer_pda.clone(),
-                token_program.clone(),
-            ],
-            &[&[&your_pool_storage_account.key.to_bytes(), &[bump_seed]]],
-        )?;
+        // V1 pools do not track rewards and keep paying the fixed legacy amount
+        let amount_to_claim = if your_pool_data.is_v1() {
+            2
+        } else {
+            utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+            user_storage_data.reward_per_token_pending
+        };
+
+        let pool_signer_seeds: &[&[u8]] =
+            &[&your_pool_storage_account.key.to_bytes(), &[bump_seed]];
+        let fee_amount = ((amount_to_claim as u128)
+            .checked_mul(your_pool_data.reward_fee_bps as u128)
+            .ok_or(CustomError::AmountOverflow)?
+            / BPS_DENOMINATOR as u128) as u64;
+        if fee_amount > 0 {
+            let fee_recipient_account = match fee_recipient_account {
+                Some(account) if *account.key == your_pool_data.fee_recipient => account,
+                _ => {
+                    msg!("CustomError::InvalidFeeRecipient");
+                    return Err(CustomError::InvalidFeeRecipient.into());
+                }
+            };
+            debug_msg!(
+                "Calling the token program to transfer the reward fee from Rewards Vault..."
+            );
+            token::transfer_checked(
+                token_program,
+                your_rewards_vault,
+                your_rewards_mint,
+                fee_recipient_account,
+                pool_signer_pda,
+                fee_amount,
+                &[pool_signer_seeds],
+            )?;
+            utils::pay_out_rewards(&mut your_pool_data, fee_amount);
+
+            StakingEvent::RewardFeeCollected {
+                pool: *your_pool_storage_account.key,
+                user_wallet: *user_wallet_account.key,
+                fee_recipient: your_pool_data.fee_recipient,
+                amount: fee_amount,
+                timestamp: now,
+            }
+            .emit()?;
+        }
 
+        let user_amount = amount_to_claim - fee_amount;
+        if user_amount > 0 && your_pool_data.has_vesting() {
+            // Vesting rewards stay in the Rewards Vault until they are withdrawn
+            utils::add_vesting(
+                &mut user_storage_data,
+                user_amount,
+                your_pool_data.vesting_duration,
+                now,
+            )?;
+
+            StakingEvent::RewardsVested {
+                pool: *your_pool_storage_account.key,
+                user_wallet: *user_wallet_account.key,
+                amount: user_amount,
+                vesting_total: user_storage_data.vesting_total,
+                vesting_end: user_storage_data.vesting_end,
+                timestamp: now,
+            }
+            .emit()?;
+        } else if user_amount > 0 {
+            debug_msg!("Calling the token program to transfer YOUR to User from Rewards Vault...");
+            token::transfer_checked(
+                token_program,
+                your_rewards_vault,
+                your_rewards_mint,
+                user_rewards_ata,
+                pool_signer_pda,
+                user_amount,
+                &[pool_signer_seeds],
+            )?;
+            utils::pay_out_rewards(&mut your_pool_data, user_amount);
+        }
+
+        user_storage_data.reward_per_token_pending = 0u64;
         user_storage_data.claim_timeout_date = now + 86400; // in seconds
+
+        StakingEvent::RewardsClaimed {
+            pool: *your_pool_storage_account.key,
+            user_wallet: *user_wallet_account.key,
+            amount: amount_to_claim,
+            claim_timeout_date: user_storage_data.claim_timeout_date,
+            timestamp: now,
+        }
+        .emit()?;
     } else {
         msg!("CustomError::UserClaimRewardTimeout");
         return Err(CustomError::UserClaimRewardTimeout.into());
     }
 
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
-    user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&user_storage_data.try_to_vec().unwrap());
+    // V1 pools do not track rewards, so claiming leaves them unchanged
+    if !your_pool_data.is_v1() {
+        your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    }
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
     Ok(())
 }
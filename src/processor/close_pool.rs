@@ -1,22 +1,27 @@
-use crate::{
-    error::CustomError,
-    state::{AccTypesWithVersion, YourPool, YOUR_POOL_STORAGE_TOTAL_BYTES},
-};
+use crate::{error::CustomError, events::StakingEvent, state::YourPool, token};
 use solana_program::sysvar::clock::Clock;
 use solana_program::sysvar::Sysvar;
 
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
     program::invoke_signed,
     program_error::ProgramError,
-    program_pack::Pack,
     pubkey::Pubkey,
 };
-use spl_token::state::Account as TokenAccount;
 
+/// 0. `[signer, writable]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Staking Vault
+/// 2. `[writable]` YOUR Staking Refund Token Account
+/// 3. `[writable]` YOUR Rewards Vault
+/// 4. `[writable]` YOUR Rewards Refund Token Account
+/// 5. `[writable]` YOUR Pool Storage Account
+/// 6. `[]` Pool Signer PDA
+/// 7. `[]` Token Program of the staking mint
+/// 8. `[writable]` YOUR Staking Mint
+/// 9. `[writable]` YOUR Rewards Mint
+/// 10. `[]` Token Program of the rewards mint, only when it differs from the staking one
 pub fn process_close_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let pool_owner_wallet_account = next_account_info(account_info_iter)?;
@@ -27,29 +32,28 @@ pub fn process_close_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> Prog
     let your_pool_storage_account = next_account_info(account_info_iter)?;
     let pool_signer_pda = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
+    let your_staking_mint = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
+    let rewards_token_program = account_info_iter.next().unwrap_or(token_program);
 
     if !pool_owner_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    if token_program.key != &spl_token::id() {
+    if !token::is_token_program(token_program.key)
+        || !token::is_token_program(rewards_token_program.key)
+    {
         msg!("CustomError::InvalidTokenProgram");
         return Err(CustomError::InvalidTokenProgram.into());
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let mut your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
     }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
 
     if your_pool_data.owner_wallet != *pool_owner_wallet_account.key {
         msg!("CustomError::PoolOwnerMismatched");
@@ -61,10 +65,14 @@ pub fn process_close_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> Prog
         return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
     }
 
-    let your_staking_vault_data = TokenAccount::unpack(&your_staking_vault.data.borrow())?;
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
     let (pool_signer_address, bump_seed) =
         Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
 
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
     if your_staking_vault_data.owner != pool_signer_address
         || your_pool_data.your_staking_vault != *your_staking_vault.key
     {
@@ -72,11 +80,34 @@ pub fn process_close_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> Prog
         return Err(CustomError::InvalidStakingVault.into());
     }
 
+    if your_rewards_vault.owner != rewards_token_program.key {
+        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+    }
+    let your_rewards_vault_data = token::unpack_token_account(your_rewards_vault)?;
+    if your_rewards_vault_data.owner != pool_signer_address
+        || (!your_pool_data.is_v1() && your_pool_data.your_rewards_vault != *your_rewards_vault.key)
+    {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+
+    let your_staking_refund_ata_data = token::unpack_token_account(your_staking_refund_ata)?;
+    let your_rewards_refund_ata_data = token::unpack_token_account(your_rewards_refund_ata)?;
+    if your_staking_refund_ata_data.mint != your_staking_vault_data.mint
+        || your_rewards_refund_ata_data.mint != your_rewards_vault_data.mint
+        || *your_staking_mint.key != your_staking_vault_data.mint
+        || *your_rewards_mint.key != your_rewards_vault_data.mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
     let total_your_staked = your_staking_vault_data.amount;
 
     let now = Clock::get()?.unix_timestamp;
 
-    if your_pool_data.reward_duration_end <= 0u64
+    if your_pool_data.reward_duration_end == 0u64
         || your_pool_data.reward_duration_end >= (now as u64)
         || your_pool_data.user_stake_count != 0u32
         || total_your_staked != 0u64
@@ -85,86 +116,73 @@ pub fn process_close_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> Prog
         return Err(CustomError::PoolStillActive.into());
     }
 
-    msg!("Calling the token program to transfer YOUR to Staking Refund from Staking Vault...");
-    invoke_signed(
-        &spl_token::instruction::transfer(
-            token_program.key,
-            your_staking_vault.key,
-            your_staking_refund_ata.key,
-            &pool_signer_address,
-            &[&pool_signer_address],
+    let pool_signer_seeds: &[&[u8]] = &[&your_pool_storage_account.key.to_bytes(), &[bump_seed]];
+    for (vault, vault_amount, refund_ata, mint, vault_token_program) in [
+        (
+            your_staking_vault,
             your_staking_vault_data.amount,
-        )?,
-        &[
-            your_staking_vault.clone(),
-            your_staking_refund_ata.clone(),
-            pool_signer_pda.clone(),
-            token_program.clone(),
-        ],
-        &[&[&your_pool_storage_account.key.to_bytes()[..], &[bump_seed]]],
-    )?;
-
-    let your_rewards_vault_data = TokenAccount::unpack(&your_rewards_vault.data.borrow())?;
-    let (pool_signer_address, bump_seed) =
-        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
-
-    msg!("Calling the token program to transfer YOUR to Rewards Refund from Rewards Vault...");
-    invoke_signed(
-        &spl_token::instruction::transfer(
-            token_program.key,
-            your_rewards_vault.key,
-            your_rewards_refund_ata.key,
-            &pool_signer_address,
-            &[&pool_signer_address],
+            your_staking_refund_ata,
+            your_staking_mint,
+            token_program,
+        ),
+        (
+            your_rewards_vault,
             your_rewards_vault_data.amount,
-        )?,
-        &[
-            your_rewards_vault.clone(),
-            your_rewards_refund_ata.clone(),
-            pool_signer_pda.clone(),
-            token_program.clone(),
-        ],
-        &[&[&your_pool_storage_account.key.to_bytes()[..], &[bump_seed]]],
-    )?;
-
-    msg!("Calling the token program to close YOUR Staking Vault...");
-    invoke_signed(
-        &spl_token::instruction::close_account(
-            token_program.key,
-            your_staking_vault.key,
-            pool_owner_wallet_account.key,
-            &pool_signer_address,
-            &[&pool_signer_address],
-        )?,
-        &[
-            your_staking_vault.clone(),
-            pool_owner_wallet_account.clone(),
-            pool_signer_pda.clone(),
-            token_program.clone(),
-        ],
-        &[&[&your_pool_storage_account.key.to_bytes()[..], &[bump_seed]]],
-    )?;
-
-    msg!("Calling the token program to close YOUR Rewards Vault...");
-    invoke_signed(
-        &spl_token::instruction::close_account(
-            token_program.key,
-            your_rewards_vault.key,
-            pool_owner_wallet_account.key,
-            &pool_signer_address,
-            &[&pool_signer_address],
-        )?,
-        &[
-            your_rewards_vault.clone(),
-            pool_owner_wallet_account.clone(),
-            pool_signer_pda.clone(),
-            token_program.clone(),
-        ],
-        &[&[&your_pool_storage_account.key.to_bytes()[..], &[bump_seed]]],
-    )?;
+            your_rewards_refund_ata,
+            your_rewards_mint,
+            rewards_token_program,
+        ),
+    ] {
+        debug_msg!(
+            "Calling the token program to transfer the Vault balance to the Refund account..."
+        );
+        token::transfer_checked(
+            vault_token_program,
+            vault,
+            mint,
+            refund_ata,
+            pool_signer_pda,
+            vault_amount,
+            &[pool_signer_seeds],
+        )?;
+
+        if token::has_transfer_fee(mint)? {
+            debug_msg!("Calling the token program to harvest the fees withheld in the Vault...");
+            token::harvest_withheld_tokens_to_mint(vault_token_program, mint, &[vault])?;
+        }
+
+        debug_msg!("Calling the token program to close the Vault...");
+        invoke_signed(
+            &token::instruction_for(
+                vault_token_program.key,
+                spl_token::instruction::close_account(
+                    &spl_token::id(),
+                    vault.key,
+                    pool_owner_wallet_account.key,
+                    &pool_signer_address,
+                    &[&pool_signer_address],
+                ),
+            )?,
+            &[
+                vault.clone(),
+                pool_owner_wallet_account.clone(),
+                pool_signer_pda.clone(),
+                vault_token_program.clone(),
+            ],
+            &[pool_signer_seeds],
+        )?;
+    }
 
     your_pool_data.your_staking_vault = Pubkey::default();
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
+    your_pool_data.your_rewards_vault = Pubkey::default();
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+
+    StakingEvent::PoolClosed {
+        pool: *your_pool_storage_account.key,
+        staking_refund_amount: your_staking_vault_data.amount,
+        rewards_refund_amount: your_rewards_vault_data.amount,
+        timestamp: now,
+    }
+    .emit()?;
     Ok(())
 }
@@ -1,10 +1,11 @@
 use crate::{
     error::CustomError,
-    processor::create_user::get_user_storage_address_and_bump_seed,
-    state::{
-        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES,
-        YOUR_POOL_STORAGE_TOTAL_BYTES,
+    events::StakingEvent,
+    processor::{
+        claim_referral_rewards::pay_referral_rewards,
+        create_user::get_user_storage_address_and_bump_seed,
     },
+    state::{User, YourPool},
     utils,
 };
 use solana_program::{
@@ -13,14 +14,23 @@ use solana_program::{
     msg,
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
+/// 0. `[signer, writable]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[writable]` YOUR Rewards Vault, only to pay out referral rewards still owed
+/// 4. `[writable]` Referrer Rewards Token Account, idem
+/// 5. `[]` Pool Signer PDA, idem
+/// 6. `[]` YOUR Rewards Mint, idem
+/// 7. `[]` Token Program of the rewards mint, idem
 pub fn process_close_user(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let user_wallet_account = next_account_info(account_info_iter)?;
     let user_storage_account = next_account_info(account_info_iter)?;
     let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let referral_payout_accounts = account_info_iter.as_slice();
 
     if !user_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
@@ -37,36 +47,15 @@ pub fn process_close_user(accounts: &[AccountInfo], program_id: &Pubkey) -> Prog
         return Err(ProgramError::InvalidSeeds);
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let mut your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
-
-    your_pool_data.user_stake_count -= 1u32;
-
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
-
-    if user_storage_account.data_len() != USER_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
     }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
 
-    let mut user_data_byte_array = user_storage_account.data.try_borrow_mut().unwrap();
-    let user_storage_data: User =
-        User::try_from_slice(&user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]).unwrap();
-    if user_storage_data.acc_type != AccTypesWithVersion::UserDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
 
     if user_storage_data.user_wallet != *user_wallet_account.key {
         msg!("CustomError::UserStorageAuthorityMismatched");
@@ -77,12 +66,74 @@ pub fn process_close_user(accounts: &[AccountInfo], program_id: &Pubkey) -> Prog
         return Err(CustomError::UserPoolMismatched.into());
     }
 
-    msg!("Closing the User Data Storage account and transferring lamports to User wallet...");
+    if user_storage_data.balance_your_staked != 0u64 {
+        msg!("CustomError::UserBalanceNotEmpty");
+        return Err(CustomError::UserBalanceNotEmpty.into());
+    }
+    if user_storage_data.unstake_pending != 0u64 {
+        msg!("CustomError::UserUnstakePendingNotEmpty");
+        return Err(CustomError::UserUnstakePendingNotEmpty.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+    if user_storage_data.reward_per_token_pending != 0u64 {
+        msg!("CustomError::UserRewardsNotClaimed");
+        return Err(CustomError::UserRewardsNotClaimed.into());
+    }
+
+    if utils::vesting_balance(&user_storage_data)? != 0u64 {
+        msg!("CustomError::UserVestingNotEmpty");
+        return Err(CustomError::UserVestingNotEmpty.into());
+    }
+
+    // The referrer's share is paid out here when the payout accounts are passed, otherwise it
+    // has to be claimed with ClaimReferralRewards first
+    if user_storage_data.referral_rewards_owed != 0u64 {
+        let payout_account_iter = &mut referral_payout_accounts.iter();
+        if payout_account_iter.len() == 0 {
+            msg!("CustomError::ReferralRewardsNotClaimed");
+            return Err(CustomError::ReferralRewardsNotClaimed.into());
+        }
+        let your_rewards_vault = next_account_info(payout_account_iter)?;
+        let referrer_rewards_ata = next_account_info(payout_account_iter)?;
+        let pool_signer_pda = next_account_info(payout_account_iter)?;
+        let your_rewards_mint = next_account_info(payout_account_iter)?;
+        let token_program = next_account_info(payout_account_iter)?;
+        pay_referral_rewards(
+            your_pool_storage_account,
+            &mut your_pool_data,
+            &mut user_storage_data,
+            your_rewards_vault,
+            referrer_rewards_ata,
+            pool_signer_pda,
+            your_rewards_mint,
+            token_program,
+            program_id,
+            now,
+        )?;
+    }
+
+    your_pool_data.user_stake_count = your_pool_data
+        .user_stake_count
+        .checked_sub(1u32)
+        .ok_or(CustomError::AmountOverflow)?;
+
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+
+    StakingEvent::UserClosed {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        user_stake_count: your_pool_data.user_stake_count,
+        timestamp: now,
+    }
+    .emit()?;
+
+    debug_msg!("Closing the User Data Storage account and transferring lamports to User wallet...");
     utils::close_account(
         user_storage_account,
         user_wallet_account,
         &mut user_data_byte_array,
-    )
-    .unwrap();
+    )?;
     Ok(())
 }
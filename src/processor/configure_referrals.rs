@@ -0,0 +1,115 @@
+use crate::{
+    error::CustomError, events::StakingEvent, state::YourPool, token,
+    utils::constants::BPS_DENOMINATOR,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Sets the share of their referees' rewards paid to referrers, in basis points, and tops up
+/// the referral budget those shares are paid from. The share applies to rewards settled after
+/// the change, including rewards referees earned before it and did not settle yet. Settling
+/// every referee first would take all their accounts, so this is intended.
+///
+/// 0. `[signer]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Pool Storage Account
+/// 2. `[writable]` YOUR Rewards Vault
+/// 3. `[writable]` Pool Owner Rewards Token Account to Debit
+/// 4. `[]` YOUR Rewards Mint
+/// 5. `[]` Token Program of the rewards mint
+pub fn process_configure_referrals(
+    accounts: &[AccountInfo],
+    referral_bps: u16,
+    fund_amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_owner_wallet_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_rewards_vault = next_account_info(account_info_iter)?;
+    let your_rewards_ata_to_debit = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if !pool_owner_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+    if referral_bps as u64 > BPS_DENOMINATOR {
+        msg!("CustomError::ReferralShareTooHigh");
+        return Err(CustomError::ReferralShareTooHigh.into());
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    if your_pool_data.owner_wallet != *pool_owner_wallet_account.key {
+        msg!("CustomError::PoolOwnerMismatched");
+        return Err(CustomError::PoolOwnerMismatched.into());
+    }
+    // V1 pools do not track rewards, so there is nothing to share with referrers
+    if your_pool_data.is_v1() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    if your_pool_data.your_rewards_vault != *your_rewards_vault.key {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+    if your_pool_data.your_rewards_mint != *your_rewards_mint.key {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let mut funded_amount = 0u64;
+    if fund_amount > 0 {
+        if token::unpack_token_account(your_rewards_ata_to_debit)?.mint != *your_rewards_mint.key {
+            msg!("CustomError::MintMismatched");
+            return Err(CustomError::MintMismatched.into());
+        }
+        debug_msg!("Calling the token program to fund the referral budget...");
+        funded_amount = token::transfer_checked(
+            token_program,
+            your_rewards_ata_to_debit,
+            your_rewards_mint,
+            your_rewards_vault,
+            pool_owner_wallet_account,
+            fund_amount,
+            &[],
+        )?;
+    }
+
+    if { your_pool_data.referral_bps } != referral_bps || funded_amount != 0u64 {
+        your_pool_data.referral_bps = referral_bps;
+        your_pool_data.referral_budget = your_pool_data
+            .referral_budget
+            .checked_add(funded_amount)
+            .ok_or(CustomError::AmountOverflow)?;
+        your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    }
+
+    StakingEvent::ReferralsConfigured {
+        pool: *your_pool_storage_account.key,
+        referral_bps,
+        funded_amount,
+        referral_budget: your_pool_data.referral_budget,
+        timestamp: Clock::get()?.unix_timestamp,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -1,9 +1,8 @@
 use crate::{
     error::CustomError,
-    state,
+    events::StakingEvent,
     state::{
-        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES,
-        YOUR_POOL_STORAGE_TOTAL_BYTES,
+        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES, USER_V1_STORAGE_TOTAL_BYTES,
     },
 };
 
@@ -16,15 +15,18 @@ use solana_program::{
     program_error::ProgramError,
     pubkey::Pubkey,
     system_instruction, system_program,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 use std::convert::TryInto;
 
-use borsh::{BorshDeserialize, BorshSerialize};
-
+/// 0. `[signer, writable]` User Wallet Account
+/// 1. `[writable]` User Storage Account (PDA of user wallet and pool storage)
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[]` System Program
 pub fn process_create_user(
     accounts: &[AccountInfo],
-    nonce: u8,
+    _nonce: u8,
+    referrer: Option<Pubkey>,
     program_id: &Pubkey,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
@@ -32,7 +34,6 @@ pub fn process_create_user(
     let user_storage_account = next_account_info(account_info_iter)?;
     let your_pool_storage_account = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
-    msg!("nonce: {}", nonce);
     if !user_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
@@ -61,6 +62,32 @@ pub fn process_create_user(
         return Err(ProgramError::InvalidSeeds);
     }
 
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+
+    if let Some(referrer) = referrer {
+        if referrer == *user_wallet_account.key || referrer == Pubkey::default() {
+            msg!("CustomError::InvalidReferrer");
+            return Err(CustomError::InvalidReferrer.into());
+        }
+        // V1 pools do not track rewards, so there is nothing to share with a referrer
+        if your_pool_data.is_v1() {
+            msg!("CustomError::PoolModeMismatched");
+            return Err(CustomError::PoolModeMismatched.into());
+        }
+    }
+
+    // Users of a pool that was not migrated yet keep the pool's V1 layout
+    let (user_acc_type, user_storage_total_bytes) = if your_pool_data.is_v1() {
+        (AccTypesWithVersion::UserDataV1, USER_V1_STORAGE_TOTAL_BYTES)
+    } else {
+        (AccTypesWithVersion::UserDataV2, USER_STORAGE_TOTAL_BYTES)
+    };
+
     let user_storage_account_signer_seeds: &[&[_]] = &[
         &user_wallet_account.key.to_bytes(),
         &your_pool_storage_account.key.to_bytes(),
@@ -72,13 +99,12 @@ pub fn process_create_user(
         user_storage_account,
         system_program_info,
         user_wallet_account,
-        state::USER_STORAGE_TOTAL_BYTES,
+        user_storage_total_bytes,
         user_storage_account_signer_seeds,
-    )
-    .unwrap();
+    )?;
 
     let user_storage_data = User {
-        acc_type: state::AccTypesWithVersion::UserDataV1 as u8,
+        acc_type: user_acc_type as u8,
         user_wallet: *user_wallet_account.key,
         your_pool: *your_pool_storage_account.key,
         balance_your_staked: 0u64,
@@ -86,30 +112,32 @@ pub fn process_create_user(
         unstake_pending_date: 0i64,
         nonce: bump_seed,
         claim_timeout_date: 0i64,
+        reward_per_token_complete: your_pool_data.reward_per_token_stored,
+        reward_per_token_pending: 0u64,
+        referrer: referrer.unwrap_or_default(),
+        referral_rewards_owed: 0u64,
+        vesting_total: 0u64,
+        vesting_withdrawn: 0u64,
+        vesting_start: 0i64,
+        vesting_end: 0i64,
+        vesting_unlocked: 0u64,
     };
 
-    let mut user_data_byte_array = user_storage_account.data.try_borrow_mut().unwrap();
-
-    user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&user_storage_data.try_to_vec().unwrap());
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let mut your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
 
     your_pool_data.user_stake_count += 1u32;
 
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+
+    StakingEvent::UserCreated {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        user_stake_count: your_pool_data.user_stake_count,
+        timestamp: Clock::get()?.unix_timestamp,
+    }
+    .emit()?;
 
     Ok(())
 }
@@ -130,7 +158,7 @@ pub fn create_and_allocate_account_raw<'a>(
         .saturating_sub(new_account_info.lamports());
 
     if required_lamports > 0 {
-        msg!("Transfer {} lamports to the new account", required_lamports);
+        debug_msg!("Transfer {} lamports to the new account", required_lamports);
         invoke(
             &system_instruction::transfer(payer_info.key, new_account_info.key, required_lamports),
             &[
@@ -141,20 +169,21 @@ pub fn create_and_allocate_account_raw<'a>(
         )?;
     }
 
-    msg!("Allocate space for the account");
+    debug_msg!("Allocate space for the account");
+    let space = size.try_into().map_err(|_| CustomError::AmountOverflow)?;
     invoke_signed(
-        &system_instruction::allocate(new_account_info.key, size.try_into().unwrap()),
+        &system_instruction::allocate(new_account_info.key, space),
         &[new_account_info.clone(), system_program_info.clone()],
         &[signer_seeds],
     )?;
 
-    msg!("Assign the account to the owning program");
+    debug_msg!("Assign the account to the owning program");
     invoke_signed(
         &system_instruction::assign(new_account_info.key, &owner_program_id),
         &[new_account_info.clone(), system_program_info.clone()],
         &[signer_seeds],
     )?;
-    msg!("Completed assignation!");
+    debug_msg!("Completed assignation!");
 
     Ok(())
 }
@@ -164,7 +193,7 @@ pub fn assert_derivation(
     account: &AccountInfo,
     path: &[&[u8]],
 ) -> Result<u8, ProgramError> {
-    let (key, bump) = Pubkey::find_program_address(&path, program_id);
+    let (key, bump) = Pubkey::find_program_address(path, program_id);
     if key != *account.key {
         return Err(CustomError::DerivedKeyInvalid.into());
     }
@@ -1,24 +1,29 @@
 use crate::{
     error::CustomError,
+    events::StakingEvent,
     processor::create_user::get_user_storage_address_and_bump_seed,
-    state::{
-        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES,
-        YOUR_POOL_STORAGE_TOTAL_BYTES,
-    },
+    state::{User, YourPool},
+    token, utils,
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
     program_error::ProgramError,
     pubkey::Pubkey,
     sysvar::clock::Clock,
     sysvar::Sysvar,
 };
 
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[writable]` YOUR Staking Vault
+/// 4. `[writable]` User YOUR Token Account
+/// 5. `[]` Pool Signer PDA
+/// 6. `[]` YOUR Staking Mint
+/// 7. `[]` Token Program
 pub fn process_final_unstake(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let user_wallet_account = next_account_info(account_info_iter)?;
@@ -27,13 +32,14 @@ pub fn process_final_unstake(accounts: &[AccountInfo], program_id: &Pubkey) -> P
     let your_staking_vault = next_account_info(account_info_iter)?;
     let user_your_ata = next_account_info(account_info_iter)?;
     let pool_signer_pda = next_account_info(account_info_iter)?;
+    let your_staking_mint = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
 
     if !user_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
     }
-    if token_program.key != &spl_token::id() {
+    if !token::is_token_program(token_program.key) {
         msg!("CustomError::InvalidTokenProgram");
         return Err(CustomError::InvalidTokenProgram.into());
     }
@@ -48,31 +54,15 @@ pub fn process_final_unstake(accounts: &[AccountInfo], program_id: &Pubkey) -> P
         return Err(ProgramError::InvalidSeeds);
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
-
-    if user_storage_account.data_len() != USER_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
     }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
 
-    let mut user_data_byte_array = user_storage_account.data.try_borrow_mut().unwrap();
-    let mut user_storage_data: User =
-        User::try_from_slice(&user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]).unwrap();
-    if user_storage_data.acc_type != AccTypesWithVersion::UserDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
 
     if user_storage_data.user_wallet != *user_wallet_account.key {
         msg!("CustomError::UserStorageAuthorityMismatched");
@@ -83,43 +73,78 @@ pub fn process_final_unstake(accounts: &[AccountInfo], program_id: &Pubkey) -> P
         return Err(CustomError::UserPoolMismatched.into());
     }
 
+    if your_staking_vault.owner != token_program.key {
+        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+    }
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
     let (pool_signer_address, bump_seed) =
         Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    if your_staking_vault_data.owner != pool_signer_address
+        || your_pool_data.your_staking_vault != *your_staking_vault.key
+    {
+        msg!("CustomError::InvalidStakingVault");
+        return Err(CustomError::InvalidStakingVault.into());
+    }
 
-    let now = Clock::get()?.unix_timestamp as i64;
+    let user_your_ata_data = token::unpack_token_account(user_your_ata)?;
+    if user_your_ata_data.mint != your_staking_vault_data.mint
+        || *your_staking_mint.key != your_staking_vault_data.mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
     if now > user_storage_data.unstake_pending_date {
-        msg!("Calling the token program to transfer YOUR to User from Staking Vault...");
-        invoke_signed(
-            &spl_token::instruction::transfer(
-                token_program.key,
-                your_staking_vault.key,
-                user_your_ata.key,
-                &pool_signer_address,
-                &[&pool_signer_address],
-                user_storage_data.unstake_pending,
-            )?,
-            &[
-                your_staking_vault.clone(),
-                user_your_ata.clone(),
-                pool_signer_pda.clone(),
-                token_program.clone(),
-            ],
+        debug_msg!("Calling the token program to transfer YOUR to User from Staking Vault...");
+        token::transfer_checked(
+            token_program,
+            your_staking_vault,
+            your_staking_mint,
+            user_your_ata,
+            pool_signer_pda,
+            user_storage_data.unstake_pending,
             &[&[&your_pool_storage_account.key.to_bytes(), &[bump_seed]]],
         )?;
+        utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+
         user_storage_data.balance_your_staked = user_storage_data
             .balance_your_staked
             .checked_sub(user_storage_data.unstake_pending)
             .ok_or(CustomError::AmountOverflow)?;
+        if !your_pool_data.is_v1() {
+            your_pool_data.total_your_staked = your_pool_data
+                .total_your_staked
+                .checked_sub(user_storage_data.unstake_pending)
+                .ok_or(CustomError::AmountOverflow)?;
+        }
     } else {
         msg!("CustomError::UserFinalUnstakeTimeout");
         return Err(CustomError::UserFinalUnstakeTimeout.into());
     }
 
+    let amount_unstaked = user_storage_data.unstake_pending;
     user_storage_data.unstake_pending = 0u64;
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
-    user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&user_storage_data.try_to_vec().unwrap());
+    // V1 pools track neither the stake total nor the rewards, so unstaking leaves them unchanged
+    if !your_pool_data.is_v1() {
+        your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    }
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+
+    StakingEvent::Unstaked {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        amount: amount_unstaked,
+        balance_staked: user_storage_data.balance_your_staked,
+        total_staked: your_pool_data.total_your_staked,
+        timestamp: now,
+    }
+    .emit()?;
 
     Ok(())
 }
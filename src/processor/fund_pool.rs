@@ -0,0 +1,130 @@
+use crate::{error::CustomError, events::StakingEvent, state::YourPool, token, utils};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Adds rewards to a pool and restarts its reward period. Rewards not emitted yet are spread
+/// together with the new amount over a full epoch from now, so the rate is
+/// `(amount + rewards_left) / epoch_duration`.
+///
+/// 0. `[signer]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Pool Storage Account
+/// 2. `[writable]` YOUR Rewards Vault
+/// 3. `[writable]` Pool Owner Rewards Token Account to Debit
+/// 4. `[]` YOUR Rewards Mint
+/// 5. `[]` Token Program of the rewards mint
+pub fn process_fund_pool(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_owner_wallet_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_rewards_vault = next_account_info(account_info_iter)?;
+    let your_rewards_ata_to_debit = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if !pool_owner_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+    if amount == 0u64 {
+        msg!("CustomError::AmountMustBeGreaterThanZero");
+        return Err(CustomError::AmountMustBeGreaterThanZero.into());
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    // Only the owner funds, a restarted period spreads the rewards left over a longer time
+    if your_pool_data.owner_wallet != *pool_owner_wallet_account.key {
+        msg!("CustomError::PoolOwnerMismatched");
+        return Err(CustomError::PoolOwnerMismatched.into());
+    }
+    // V1 pools keep paying the fixed legacy amount and have no rewards vault
+    if your_pool_data.is_v1() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    if your_pool_data.your_rewards_vault != *your_rewards_vault.key {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+    if your_pool_data.your_rewards_mint != *your_rewards_mint.key
+        || token::unpack_token_account(your_rewards_ata_to_debit)?.mint != *your_rewards_mint.key
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    // Rewards emitted so far are settled at the old rate before it changes
+    utils::update_rewards(&mut your_pool_data, None, now)?;
+
+    // Mints with a transfer fee credit the vault with less than the funded amount
+    debug_msg!("Calling the token program to transfer YOUR rewards to Rewards Vault...");
+    let funded_amount = token::transfer_checked(
+        token_program,
+        your_rewards_ata_to_debit,
+        your_rewards_mint,
+        your_rewards_vault,
+        pool_owner_wallet_account,
+        amount,
+        &[],
+    )?;
+
+    let now = now as u64;
+    let reward_duration_end = your_pool_data.reward_duration_end;
+    let rewards_left_amount = if now < reward_duration_end {
+        reward_duration_end
+            .checked_sub(now)
+            .and_then(|remaining_duration| {
+                remaining_duration.checked_mul(your_pool_data.your_reward_rate)
+            })
+            .ok_or(CustomError::AmountOverflow)?
+    } else {
+        0u64
+    };
+    debug_msg!("rewards_left_amount: {}", rewards_left_amount);
+    your_pool_data.your_reward_rate = funded_amount
+        .checked_add(rewards_left_amount)
+        .ok_or(CustomError::AmountOverflow)?
+        .checked_div(your_pool_data.your_epoch_duration)
+        .ok_or(CustomError::AmountOverflow)?;
+    debug_msg!("your_pool_data.your_reward_rate: {}", {
+        your_pool_data.your_reward_rate
+    });
+    your_pool_data.last_update_time = now;
+    your_pool_data.reward_duration_end = now
+        .checked_add(your_pool_data.your_epoch_duration)
+        .ok_or(CustomError::AmountOverflow)?;
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+
+    StakingEvent::PoolFunded {
+        pool: *your_pool_storage_account.key,
+        funder_wallet: *pool_owner_wallet_account.key,
+        amount: funded_amount,
+        reward_rate: your_pool_data.your_reward_rate,
+        reward_duration_end: your_pool_data.reward_duration_end,
+        timestamp: now as i64,
+    }
+    .emit()?;
+
+    Ok(())
+}
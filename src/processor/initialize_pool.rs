@@ -1,9 +1,16 @@
 use crate::{
     error::CustomError,
+    events::StakingEvent,
+    processor::create_user::create_and_allocate_account_raw,
     state::{AccTypesWithVersion, YourPool, YOUR_POOL_STORAGE_TOTAL_BYTES},
+    token,
+    utils::constants::{
+        MAX_REWARD_DURATION, MIN_REWARD_DURATION, RECEIPT_MINT_SEED, REWARDS_VAULT_SEED,
+        STAKING_VAULT_SEED,
+    },
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::Zeroable;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     clock::Clock,
@@ -13,25 +20,33 @@ use solana_program::{
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
-    rent::Rent,
-    sysvar::Sysvar,
+    system_program,
+    sysvar::{self, Sysvar},
 };
-use spl_token::state::Account as TokenAccount;
+use spl_token::state::Mint;
 
-/// 0. `[signer]` Pool Owner Wallet Account
-/// 1. `[writable]` YOUR Pool Storage Account
-/// 2. `[]` YOUR Staking Mint
-/// 3. `[writable]` YOUR Staking Vault
+/// 0. `[signer, writable]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Pool Storage Account (PDA of owner, staking mint and pool index)
+/// 2. `[]` YOUR Staking Mint, the collection mint for NFT pools
+/// 3. `[writable]` YOUR Staking Vault (PDA of pool storage and "staking")
 /// 4. `[]` YOUR Rewards Mint
-/// 5. `[writable]` YOUR Rewards Vault
+/// 5. `[writable]` YOUR Rewards Vault (PDA of pool storage and "rewards")
 /// 6. `[]` Funder Wallet Account
 /// 7. `[writable]` YOUR ATA to Debit (Reward Token)
-/// 8. `[]` Token Program
+/// 8. `[]` Token Program of the staking mint
+/// 9. `[]` System Program
+/// 10. `[]` Rent Sysvar
+/// 11. `[writable]` Receipt Mint (PDA of pool storage and "receipt"), only with `with_receipt_mint`
+/// 12. `[]` Token Program of the rewards mint, only when it differs from the staking one
+#[allow(clippy::too_many_arguments)]
 pub fn process_initialize_your_pool(
     accounts: &[AccountInfo],
     reward_duration: u64,
-    pool_nonce: u8,
+    _pool_nonce: u8,
     fund_pool: u64,
+    pool_index: u64,
+    with_receipt_mint: bool,
+    nft_pool: bool,
     program_id: &Pubkey,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
@@ -44,213 +59,332 @@ pub fn process_initialize_your_pool(
     let funder_wallet_account = next_account_info(account_info_iter)?;
     let your_rewards_ata_to_debit = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_sysvar_info = next_account_info(account_info_iter)?;
+    let receipt_mint_info = if with_receipt_mint {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let rewards_token_program = account_info_iter.next().unwrap_or(token_program);
 
-    msg!("pool_nonce: {}", pool_nonce);
-    msg!(
-        "Pool Authority: {}",
-        pool_owner_wallet_account.key.to_string()
-    );
-    msg!(
-        "Pool Storage: {}",
-        your_pool_storage_account.key.to_string()
-    );
-    msg!("Staking Mint: {}", your_staking_mint.key.to_string());
-    msg!("Staking Vault: {}", your_staking_vault.key.to_string());
-    msg!("Rewards Mint: {}", your_rewards_mint.key.to_string());
-    msg!("Rewards Vault: {}", your_rewards_vault.key.to_string());
-    msg!(
-        "Reward ATA to Debit: {}",
-        your_rewards_ata_to_debit.key.to_string()
-    );
-    msg!("Fund pool amount: {}", fund_pool);
+    debug_msg!("pool_index: {}", pool_index);
+    debug_msg!("Pool Authority: {}", pool_owner_wallet_account.key);
+    debug_msg!("Pool Storage: {}", your_pool_storage_account.key);
+    debug_msg!("Staking Mint: {}", your_staking_mint.key);
+    debug_msg!("Staking Vault: {}", your_staking_vault.key);
+    debug_msg!("Rewards Mint: {}", your_rewards_mint.key);
+    debug_msg!("Rewards Vault: {}", your_rewards_vault.key);
+    debug_msg!("Reward ATA to Debit: {}", your_rewards_ata_to_debit.key);
+    debug_msg!("Fund pool amount: {}", fund_pool);
     if !pool_owner_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
     }
+    if reward_duration < MIN_REWARD_DURATION {
+        msg!("CustomError::RewardDurationTooShort");
+        return Err(CustomError::RewardDurationTooShort.into());
+    }
+    if reward_duration > MAX_REWARD_DURATION {
+        msg!("CustomError::RewardDurationTooLong");
+        return Err(CustomError::RewardDurationTooLong.into());
+    }
 
-    msg!("Token program: {}", token_program.key.to_string());
+    debug_msg!("Token program: {}", token_program.key);
 
-    if token_program.key != &spl_token::id() {
+    if !token::is_token_program(token_program.key)
+        || !token::is_token_program(rewards_token_program.key)
+    {
         msg!("CustomError::InvalidTokenProgram");
         return Err(CustomError::InvalidTokenProgram.into());
     }
 
-    let rent = Rent::get()?;
-
-    if !rent.is_exempt(your_staking_vault.lamports(), your_staking_vault.data_len()) {
-        msg!("CustomError::NotRentExempt");
-        return Err(CustomError::NotRentExempt.into());
+    if *system_program_info.key != system_program::id() {
+        msg!("CustomError::InvalidSystemProgram");
+        return Err(CustomError::InvalidSystemProgram.into());
     }
 
-    if !rent.is_exempt(your_rewards_vault.lamports(), your_rewards_vault.data_len()) {
-        msg!("CustomError::NotRentExempt");
-        return Err(CustomError::NotRentExempt.into());
+    // Receipts are minted 1:1 for fungible deposits only
+    if nft_pool && with_receipt_mint {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
     }
 
-    if !rent.is_exempt(
-        your_pool_storage_account.lamports(),
-        your_pool_storage_account.data_len(),
-    ) {
-        msg!("CustomError::NotRentExempt");
-        return Err(CustomError::NotRentExempt.into());
+    if fund_pool > 0
+        && token::unpack_token_account(your_rewards_ata_to_debit)?.mint != *your_rewards_mint.key
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
+    let (your_pool_storage_address, pool_storage_bump_seed) =
+        get_pool_storage_address_and_bump_seed(
+            pool_owner_wallet_account.key,
+            your_staking_mint.key,
+            pool_index,
+            program_id,
+        );
+    if your_pool_storage_address != *your_pool_storage_account.key {
+        msg!("Error: Pool Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
     }
 
-    let (pool_signer_address, bump_seed) =
-        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
-    msg!(
-        "Calling the token program to transfer Staking vault account ownership to Pool program..."
-    );
-    invoke(
-        &spl_token::instruction::set_authority(
-            token_program.clone().key,
-            your_staking_vault.clone().key,
-            Some(&pool_signer_address),
-            spl_token::instruction::AuthorityType::AccountOwner,
-            pool_owner_wallet_account.key,
-            &[&pool_owner_wallet_account.key],
-        )?,
-        &[
-            your_staking_vault.clone(),
-            pool_owner_wallet_account.clone(),
-            token_program.clone(),
-        ],
-    )?;
+    if !your_pool_storage_account.data_is_empty()
+        || *your_pool_storage_account.owner != system_program::id()
+    {
+        msg!("CustomError::PoolAddressAlreadyInitialized");
+        return Err(CustomError::PoolAddressAlreadyInitialized.into());
+    }
 
-    msg!(
-        "Calling the token program to transfer Rewards vault account ownership to Pool program..."
-    );
-    invoke(
-        &spl_token::instruction::set_authority(
-            token_program.clone().key,
-            your_rewards_vault.clone().key,
-            Some(&pool_signer_address),
-            spl_token::instruction::AuthorityType::AccountOwner,
-            pool_owner_wallet_account.key,
-            &[&pool_owner_wallet_account.key],
-        )?,
+    create_and_allocate_account_raw(
+        *program_id,
+        your_pool_storage_account,
+        system_program_info,
+        pool_owner_wallet_account,
+        YOUR_POOL_STORAGE_TOTAL_BYTES,
         &[
-            your_rewards_vault.clone(),
-            pool_owner_wallet_account.clone(),
-            token_program.clone(),
+            &pool_owner_wallet_account.key.to_bytes(),
+            &your_staking_mint.key.to_bytes(),
+            &pool_index.to_le_bytes(),
+            &[pool_storage_bump_seed],
         ],
     )?;
 
-    let your_staking_vault_data = TokenAccount::unpack(&your_staking_vault.data.borrow())?;
-    if your_staking_vault_data.mint != *your_staking_mint.key {
-        msg!("CustomError::MintMismatched");
-        return Err(CustomError::MintMismatched.into());
-    }
-
-    let your_rewards_vault_data = TokenAccount::unpack(&your_rewards_vault.clone().data.borrow())?;
-    if your_rewards_vault_data.mint != *your_rewards_mint.key {
-        msg!("CustomError::MintMismatched");
-        return Err(CustomError::MintMismatched.into());
+    if *rent_sysvar_info.key != sysvar::rent::id() {
+        msg!("ProgramError::InvalidArgument");
+        return Err(ProgramError::InvalidArgument);
     }
-    let your_pool_storage_account_clone = your_pool_storage_account.clone();
-    let mut your_pool_data_byte_array = your_pool_storage_account_clone
-        .data
-        .try_borrow_mut()
-        .unwrap();
-    let mut your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-
-    if your_pool_data.acc_type != 0 {
-        msg!("CustomError::PoolAddressAlreadyInitialized");
-        return Err(CustomError::PoolAddressAlreadyInitialized.into());
-    }
-    your_pool_data.acc_type = AccTypesWithVersion::YourPoolDataV1 as u8;
-    your_pool_data.owner_wallet = *pool_owner_wallet_account.key;
-    your_pool_data.your_staking_vault = *your_staking_vault.key;
-    your_pool_data.your_reward_rate = 0u64;
-    your_pool_data.your_epoch_duration = reward_duration;
-    your_pool_data.user_stake_count = 0u32;
-    your_pool_data.pda_nonce = bump_seed;
-    your_pool_data.reward_duration_end = 0u64;
 
-    //your_pool_data.user_stake_count += 1u32; // TODO: Think on it
+    let (pool_signer_address, bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
 
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
+    for (vault, vault_seed, vault_mint, vault_token_program) in [
+        (
+            your_staking_vault,
+            STAKING_VAULT_SEED,
+            your_staking_mint,
+            token_program,
+        ),
+        (
+            your_rewards_vault,
+            REWARDS_VAULT_SEED,
+            your_rewards_mint,
+            rewards_token_program,
+        ),
+    ] {
+        let (vault_address, vault_bump_seed) =
+            get_vault_address_and_bump_seed(your_pool_storage_account.key, vault_seed, program_id);
+        if vault_address != *vault.key {
+            msg!("Error: Vault address does not match seed derivation");
+            return Err(ProgramError::InvalidSeeds);
+        }
 
-    msg!("Fund pool started...");
+        if vault_mint.owner != vault_token_program.key {
+            msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+            return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+        }
 
-    // Fund pool
-    if token_program.key != &spl_token::id() {
-        msg!("CustomError::InvalidTokenProgram");
-        return Err(CustomError::InvalidTokenProgram.into());
-    }
+        debug_msg!("Creating the vault account owned by the token program...");
+        create_and_allocate_account_raw(
+            *vault_token_program.key,
+            vault,
+            system_program_info,
+            pool_owner_wallet_account,
+            token::get_account_data_size(vault_token_program, vault_mint)?,
+            &[
+                &your_pool_storage_account.key.to_bytes(),
+                vault_seed,
+                &[vault_bump_seed],
+            ],
+        )?;
 
-    if your_staking_vault.owner != token_program.key {
-        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
-        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+        debug_msg!("Calling the token program to initialize the vault for the Pool signer...");
+        invoke(
+            &token::instruction_for(
+                vault_token_program.key,
+                spl_token::instruction::initialize_account2(
+                    &spl_token::id(),
+                    vault.key,
+                    vault_mint.key,
+                    &pool_signer_address,
+                ),
+            )?,
+            &[
+                vault.clone(),
+                vault_mint.clone(),
+                rent_sysvar_info.clone(),
+                vault_token_program.clone(),
+            ],
+        )?;
     }
 
-    let your_staking_vault_data = TokenAccount::unpack(&your_staking_vault.data.borrow())?;
-    let (pool_signer_address, _bump_seed) =
-        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if let Some(receipt_mint_info) = receipt_mint_info {
+        let (receipt_mint_address, receipt_mint_bump_seed) = get_vault_address_and_bump_seed(
+            your_pool_storage_account.key,
+            RECEIPT_MINT_SEED,
+            program_id,
+        );
+        if receipt_mint_address != *receipt_mint_info.key {
+            msg!("Error: Receipt Mint address does not match seed derivation");
+            return Err(ProgramError::InvalidSeeds);
+        }
 
-    if your_staking_vault_data.owner != pool_signer_address {
-        msg!("CustomError::InvalidStakingVault");
-        return Err(CustomError::InvalidStakingVault.into());
-    }
+        let your_staking_mint_data = token::unpack_mint(your_staking_mint)?;
 
-    let now = Clock::get()?.unix_timestamp as u64;
-    let reward_duration_end = your_pool_data.reward_duration_end;
-    msg!("now: {}", now);
-    msg!("reward_duration_end: {}", reward_duration_end);
-    msg!("fund_pool: {}", fund_pool);
-    if now < reward_duration_end {
-        let remaining_duration = reward_duration_end
-            .checked_sub(now)
-            .ok_or(CustomError::AmountOverflow)?;
-        let rewards_left_amount = remaining_duration
-            .checked_mul(your_pool_data.your_reward_rate)
-            .ok_or(CustomError::AmountOverflow)?;
-        your_pool_data.your_reward_rate = fund_pool
-            .checked_add(rewards_left_amount)
-            .ok_or(CustomError::AmountOverflow)?
-            .checked_div(your_pool_data.your_epoch_duration)
-            .ok_or(CustomError::AmountOverflow)?;
-    } else {
-        your_pool_data.your_reward_rate = fund_pool
-            .checked_div(your_pool_data.your_epoch_duration)
-            .ok_or(CustomError::AmountOverflow)?;
-    }
+        debug_msg!("Creating the receipt mint account owned by the token program...");
+        create_and_allocate_account_raw(
+            *token_program.key,
+            receipt_mint_info,
+            system_program_info,
+            pool_owner_wallet_account,
+            Mint::LEN,
+            &[
+                &your_pool_storage_account.key.to_bytes(),
+                RECEIPT_MINT_SEED,
+                &[receipt_mint_bump_seed],
+            ],
+        )?;
 
-    if fund_pool > 0 {
-        msg!("Calling the token program to transfer YOUR rewards to Rewards Vault...");
+        debug_msg!(
+            "Calling the token program to initialize the receipt mint for the Pool signer..."
+        );
         invoke(
-            &spl_token::instruction::transfer(
-                token_program.clone().key,
-                your_rewards_ata_to_debit.key,
-                your_rewards_vault.key,
-                funder_wallet_account.key,
-                &[],
-                fund_pool,
+            &token::instruction_for(
+                token_program.key,
+                spl_token::instruction::initialize_mint(
+                    &spl_token::id(),
+                    receipt_mint_info.key,
+                    &pool_signer_address,
+                    None,
+                    your_staking_mint_data.decimals,
+                ),
             )?,
             &[
-                your_rewards_ata_to_debit.clone(),
-                your_rewards_vault.clone(),
-                funder_wallet_account.clone(),
+                receipt_mint_info.clone(),
+                rent_sysvar_info.clone(),
                 token_program.clone(),
             ],
         )?;
     }
-    msg!(
-        "your_pool_data.your_reward_rate: {}",
+
+    // The account was just created, so it holds no data to keep
+    let mut your_pool_data = YourPool::zeroed();
+
+    your_pool_data.acc_type = AccTypesWithVersion::YourPoolDataV2 as u8;
+    your_pool_data.owner_wallet = *pool_owner_wallet_account.key;
+    your_pool_data.your_staking_vault = *your_staking_vault.key;
+    your_pool_data.your_reward_rate = 0u64;
+    your_pool_data.your_epoch_duration = reward_duration;
+    your_pool_data.user_stake_count = 0u32;
+    your_pool_data.pda_nonce = bump_seed;
+    your_pool_data.reward_duration_end = 0u64;
+    your_pool_data.your_staking_mint = *your_staking_mint.key;
+    your_pool_data.your_rewards_vault = *your_rewards_vault.key;
+    your_pool_data.your_rewards_mint = *your_rewards_mint.key;
+    your_pool_data.total_your_staked = 0u64;
+    your_pool_data.migrated_from = Pubkey::default();
+    your_pool_data.pool_index = pool_index;
+    your_pool_data.reward_per_token_stored = 0u128;
+    your_pool_data.receipt_mint = receipt_mint_info.map_or(Pubkey::default(), |info| *info.key);
+    if nft_pool {
+        your_pool_data.nft_collection = *your_staking_mint.key;
+    }
+
+    debug_msg!("Fund pool started...");
+
+    // Fund pool
+    // Mints with a transfer fee credit the vault with less than the funded amount
+    let fund_pool = if fund_pool > 0 {
+        debug_msg!("Calling the token program to transfer YOUR rewards to Rewards Vault...");
+        token::transfer_checked(
+            rewards_token_program,
+            your_rewards_ata_to_debit,
+            your_rewards_mint,
+            your_rewards_vault,
+            funder_wallet_account,
+            fund_pool,
+            &[],
+        )?
+    } else {
+        0u64
+    };
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    debug_msg!("now: {}", now);
+    debug_msg!("fund_pool: {}", fund_pool);
+    your_pool_data.your_reward_rate = fund_pool
+        .checked_div(your_pool_data.your_epoch_duration)
+        .ok_or(CustomError::AmountOverflow)?;
+
+    debug_msg!("your_pool_data.your_reward_rate: {}", {
         your_pool_data.your_reward_rate
-    );
+    });
+    your_pool_data.last_update_time = now;
     your_pool_data.reward_duration_end = now
         .checked_add(your_pool_data.your_epoch_duration)
         .ok_or(CustomError::AmountOverflow)?;
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
+    your_pool_data.pack_versioned(&mut your_pool_storage_account.try_borrow_mut_data()?)?;
+
+    StakingEvent::PoolInitialized {
+        pool: *your_pool_storage_account.key,
+        owner_wallet: your_pool_data.owner_wallet,
+        staking_mint: your_pool_data.your_staking_mint,
+        rewards_mint: your_pool_data.your_rewards_mint,
+        pool_index,
+        epoch_duration: your_pool_data.your_epoch_duration,
+        timestamp: now as i64,
+    }
+    .emit()?;
+    if fund_pool > 0 {
+        StakingEvent::PoolFunded {
+            pool: *your_pool_storage_account.key,
+            funder_wallet: *funder_wallet_account.key,
+            amount: fund_pool,
+            reward_rate: your_pool_data.your_reward_rate,
+            reward_duration_end: your_pool_data.reward_duration_end,
+            timestamp: now as i64,
+        }
+        .emit()?;
+    }
 
     Ok(())
 }
+
+/// Derives the pool storage account address for the given owner, staking mint and pool index
+pub fn get_pool_storage_address(
+    owner_wallet: &Pubkey,
+    staking_mint: &Pubkey,
+    pool_index: u64,
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_pool_storage_address_and_bump_seed(owner_wallet, staking_mint, pool_index, program_id).0
+}
+
+pub fn get_pool_storage_address_and_bump_seed(
+    owner_wallet: &Pubkey,
+    staking_mint: &Pubkey,
+    pool_index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &owner_wallet.to_bytes(),
+            &staking_mint.to_bytes(),
+            &pool_index.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the address of a pool vault, `vault_seed` being `STAKING_VAULT_SEED` or `REWARDS_VAULT_SEED`
+pub fn get_vault_address(pool_storage: &Pubkey, vault_seed: &[u8], program_id: &Pubkey) -> Pubkey {
+    get_vault_address_and_bump_seed(pool_storage, vault_seed, program_id).0
+}
+
+pub fn get_vault_address_and_bump_seed(
+    pool_storage: &Pubkey,
+    vault_seed: &[u8],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&pool_storage.to_bytes(), vault_seed], program_id)
+}
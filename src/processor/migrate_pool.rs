@@ -0,0 +1,230 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::{
+        create_user::create_and_allocate_account_raw,
+        initialize_pool::get_pool_storage_address_and_bump_seed,
+    },
+    state::{AccTypesWithVersion, YourPool, YOUR_POOL_STORAGE_TOTAL_BYTES},
+    token, utils,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Moves a V1 pool into a V2 pool account, as accounts can not be resized in place. The V2 pool
+/// lives at a new address, the PDA of owner, staking mint and pool index, so clients have to
+/// switch to it. It counts no users until each runs MigrateUser, so V1 users that never migrate
+/// do not keep ClosePool blocked, unless they left stake in the vault.
+///
+/// 0. `[signer, writable]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Pool V1 Storage Account
+/// 2. `[writable]` YOUR Pool V2 Storage Account (PDA of owner, staking mint and pool index)
+/// 3. `[]` YOUR Staking Mint
+/// 4. `[writable]` YOUR Staking Vault
+/// 5. `[]` YOUR Rewards Mint
+/// 6. `[writable]` YOUR Rewards Vault
+/// 7. `[]` V1 Pool Signer PDA
+/// 8. `[]` Token Program of the staking mint
+/// 9. `[]` System Program
+/// 10. `[]` Token Program of the rewards mint, only when it differs from the staking one
+pub fn process_migrate_pool(
+    accounts: &[AccountInfo],
+    pool_index: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_owner_wallet_account = next_account_info(account_info_iter)?;
+    let your_pool_v1_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_staking_mint = next_account_info(account_info_iter)?;
+    let your_staking_vault = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
+    let your_rewards_vault = next_account_info(account_info_iter)?;
+    let pool_v1_signer_pda = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rewards_token_program = account_info_iter.next().unwrap_or(token_program);
+
+    if !pool_owner_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if !token::is_token_program(token_program.key)
+        || !token::is_token_program(rewards_token_program.key)
+    {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+
+    if *system_program_info.key != system_program::id() {
+        msg!("CustomError::InvalidSystemProgram");
+        return Err(CustomError::InvalidSystemProgram.into());
+    }
+
+    if your_pool_v1_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_v1_data_byte_array = your_pool_v1_storage_account.try_borrow_mut_data()?;
+    let your_pool_v1_data = YourPool::unpack_versioned(&your_pool_v1_data_byte_array)?;
+    if !your_pool_v1_data.is_v1() {
+        msg!("CustomError::ExpectedAccountTypeMismatched");
+        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    }
+
+    if your_pool_v1_data.owner_wallet != *pool_owner_wallet_account.key {
+        msg!("CustomError::PoolOwnerMismatched");
+        return Err(CustomError::PoolOwnerMismatched.into());
+    }
+
+    let (pool_v1_signer_address, pool_v1_bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_v1_storage_account.key.to_bytes()], program_id);
+    if *pool_v1_signer_pda.key != pool_v1_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+
+    if your_staking_vault.owner != token_program.key
+        || your_rewards_vault.owner != rewards_token_program.key
+    {
+        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+    }
+
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
+    if your_staking_vault_data.owner != pool_v1_signer_address
+        || your_pool_v1_data.your_staking_vault != *your_staking_vault.key
+    {
+        msg!("CustomError::InvalidStakingVault");
+        return Err(CustomError::InvalidStakingVault.into());
+    }
+
+    let your_rewards_vault_data = token::unpack_token_account(your_rewards_vault)?;
+    if your_rewards_vault_data.owner != pool_v1_signer_address {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+
+    if your_staking_vault_data.mint != *your_staking_mint.key
+        || your_rewards_vault_data.mint != *your_rewards_mint.key
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let (your_pool_storage_address, pool_storage_bump_seed) =
+        get_pool_storage_address_and_bump_seed(
+            pool_owner_wallet_account.key,
+            your_staking_mint.key,
+            pool_index,
+            program_id,
+        );
+    if your_pool_storage_address != *your_pool_storage_account.key {
+        msg!("Error: Pool Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if !your_pool_storage_account.data_is_empty()
+        || *your_pool_storage_account.owner != system_program::id()
+    {
+        msg!("CustomError::PoolAddressAlreadyInitialized");
+        return Err(CustomError::PoolAddressAlreadyInitialized.into());
+    }
+
+    create_and_allocate_account_raw(
+        *program_id,
+        your_pool_storage_account,
+        system_program_info,
+        pool_owner_wallet_account,
+        YOUR_POOL_STORAGE_TOTAL_BYTES,
+        &[
+            &pool_owner_wallet_account.key.to_bytes(),
+            &your_staking_mint.key.to_bytes(),
+            &pool_index.to_le_bytes(),
+            &[pool_storage_bump_seed],
+        ],
+    )?;
+
+    let (pool_signer_address, bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+
+    for (vault, vault_token_program) in [
+        (your_staking_vault, token_program),
+        (your_rewards_vault, rewards_token_program),
+    ] {
+        debug_msg!(
+            "Calling the token program to transfer vault ownership to the V2 Pool signer..."
+        );
+        invoke_signed(
+            &token::instruction_for(
+                vault_token_program.key,
+                spl_token::instruction::set_authority(
+                    &spl_token::id(),
+                    vault.key,
+                    Some(&pool_signer_address),
+                    spl_token::instruction::AuthorityType::AccountOwner,
+                    &pool_v1_signer_address,
+                    &[&pool_v1_signer_address],
+                ),
+            )?,
+            &[
+                vault.clone(),
+                pool_v1_signer_pda.clone(),
+                vault_token_program.clone(),
+            ],
+            &[&[
+                &your_pool_v1_storage_account.key.to_bytes(),
+                &[pool_v1_bump_seed],
+            ]],
+        )?;
+    }
+
+    let your_pool_data = YourPool {
+        acc_type: AccTypesWithVersion::YourPoolDataV2 as u8,
+        pda_nonce: bump_seed,
+        user_stake_count: 0u32,
+        your_staking_mint: *your_staking_mint.key,
+        your_rewards_vault: *your_rewards_vault.key,
+        your_rewards_mint: *your_rewards_mint.key,
+        total_your_staked: your_staking_vault_data.amount,
+        migrated_from: *your_pool_v1_storage_account.key,
+        pool_index,
+        reward_per_token_stored: 0u128,
+        // Rewards start accruing now, a V1 period that already ended accrues nothing
+        last_update_time: utils::last_time_reward_applicable(
+            your_pool_v1_data.reward_duration_end,
+            Clock::get()?.unix_timestamp,
+        ),
+        ..your_pool_v1_data
+    };
+    your_pool_data.pack_versioned(&mut your_pool_storage_account.try_borrow_mut_data()?)?;
+
+    StakingEvent::PoolMigrated {
+        pool_v1: *your_pool_v1_storage_account.key,
+        pool: *your_pool_storage_account.key,
+        total_staked: your_pool_data.total_your_staked,
+        timestamp: your_pool_data.last_update_time as i64,
+    }
+    .emit()?;
+
+    debug_msg!(
+        "Closing the V1 Pool Storage account and transferring lamports to Pool owner wallet..."
+    );
+    utils::close_account(
+        your_pool_v1_storage_account,
+        pool_owner_wallet_account,
+        &mut your_pool_v1_data_byte_array,
+    )?;
+
+    Ok(())
+}
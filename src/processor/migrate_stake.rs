@@ -0,0 +1,231 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::create_user::get_user_storage_address_and_bump_seed,
+    state::{User, YourPool},
+    token, utils,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` Source User Storage Account
+/// 2. `[writable]` Source YOUR Pool Storage Account
+/// 3. `[writable]` Source YOUR Staking Vault
+/// 4. `[]` Source Pool Signer PDA
+/// 5. `[writable]` Destination User Storage Account (created with CreateUser beforehand)
+/// 6. `[writable]` Destination YOUR Pool Storage Account
+/// 7. `[writable]` Destination YOUR Staking Vault
+/// 8. `[]` YOUR Staking Mint
+/// 9. `[]` Token Program
+pub fn process_migrate_stake(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_wallet_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_staking_vault = next_account_info(account_info_iter)?;
+    let pool_signer_pda = next_account_info(account_info_iter)?;
+    let destination_user_storage_account = next_account_info(account_info_iter)?;
+    let destination_pool_storage_account = next_account_info(account_info_iter)?;
+    let destination_staking_vault = next_account_info(account_info_iter)?;
+    let your_staking_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if !user_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+
+    if your_pool_storage_account.key == destination_pool_storage_account.key {
+        msg!("CustomError::SameSourceAndDestination");
+        return Err(CustomError::SameSourceAndDestination.into());
+    }
+
+    for (user_storage, pool_storage) in [
+        (user_storage_account, your_pool_storage_account),
+        (
+            destination_user_storage_account,
+            destination_pool_storage_account,
+        ),
+    ] {
+        let (user_storage_address, _bump_seed) = get_user_storage_address_and_bump_seed(
+            user_wallet_account.key,
+            pool_storage.key,
+            program_id,
+        );
+        if user_storage_address != *user_storage.key {
+            msg!("Error: User Storage address does not match seed derivation");
+            return Err(ProgramError::InvalidSeeds);
+        }
+    }
+
+    for storage_account in [
+        your_pool_storage_account,
+        destination_pool_storage_account,
+        user_storage_account,
+        destination_user_storage_account,
+    ] {
+        if storage_account.owner != program_id {
+            msg!("CustomError::AccountOwnerShouldBeProgram");
+            return Err(CustomError::AccountOwnerShouldBeProgram.into());
+        }
+    }
+
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    let mut destination_pool_data_byte_array =
+        destination_pool_storage_account.try_borrow_mut_data()?;
+    let mut destination_pool_data = YourPool::unpack_versioned(&destination_pool_data_byte_array)?;
+
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+    let mut destination_user_data_byte_array =
+        destination_user_storage_account.try_borrow_mut_data()?;
+    let mut destination_user_data = User::unpack_versioned(&destination_user_data_byte_array)?;
+
+    if user_storage_data.user_wallet != *user_wallet_account.key
+        || destination_user_data.user_wallet != *user_wallet_account.key
+    {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+    if user_storage_data.your_pool != *your_pool_storage_account.key
+        || destination_user_data.your_pool != *destination_pool_storage_account.key
+    {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    // Receipts are tied to a single pool, moving the stake would leave them unbacked
+    if your_pool_data.has_receipt_mint() || destination_pool_data.has_receipt_mint() {
+        msg!("CustomError::ReceiptPoolNotTransferable");
+        return Err(CustomError::ReceiptPoolNotTransferable.into());
+    }
+
+    // NFT stakes are held in per-NFT escrows, not in the staking vault
+    if your_pool_data.is_nft_pool() || destination_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+    // Moving stake in is a stake into the destination pool
+    if destination_pool_data.is_paused() {
+        msg!("CustomError::PoolPaused");
+        return Err(CustomError::PoolPaused.into());
+    }
+
+    if your_staking_vault.owner != token_program.key
+        || destination_staking_vault.owner != token_program.key
+    {
+        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+    }
+
+    let (pool_signer_address, bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    let (destination_pool_signer_address, _bump_seed) = Pubkey::find_program_address(
+        &[&destination_pool_storage_account.key.to_bytes()],
+        program_id,
+    );
+
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
+    let destination_staking_vault_data = token::unpack_token_account(destination_staking_vault)?;
+    if your_staking_vault_data.owner != pool_signer_address
+        || your_pool_data.your_staking_vault != *your_staking_vault.key
+        || destination_staking_vault_data.owner != destination_pool_signer_address
+        || destination_pool_data.your_staking_vault != *destination_staking_vault.key
+    {
+        msg!("CustomError::InvalidStakingVault");
+        return Err(CustomError::InvalidStakingVault.into());
+    }
+
+    if your_staking_vault_data.mint != destination_staking_vault_data.mint
+        || *your_staking_mint.key != your_staking_vault_data.mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    // Tokens already waiting for FinalUnstake keep their cooldown in the source pool
+    let amount_to_migrate = user_storage_data
+        .balance_your_staked
+        .checked_sub(user_storage_data.unstake_pending)
+        .ok_or(CustomError::AmountOverflow)?;
+    if amount_to_migrate == 0u64 {
+        msg!("CustomError::AmountMustBeGreaterThanZero");
+        return Err(CustomError::AmountMustBeGreaterThanZero.into());
+    }
+
+    debug_msg!("Calling the token program to transfer YOUR to the destination Staking Vault...");
+    // Mints with a transfer fee credit the destination vault with less than the migrated amount
+    let amount_received = token::transfer_checked(
+        token_program,
+        your_staking_vault,
+        your_staking_mint,
+        destination_staking_vault,
+        pool_signer_pda,
+        amount_to_migrate,
+        &[&[&your_pool_storage_account.key.to_bytes(), &[bump_seed]]],
+    )?;
+
+    let now = Clock::get()?.unix_timestamp;
+    // Rewards earned in the source pool stay claimable there
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+    utils::update_rewards(
+        &mut destination_pool_data,
+        Some(&mut destination_user_data),
+        now,
+    )?;
+
+    user_storage_data.balance_your_staked = user_storage_data
+        .balance_your_staked
+        .checked_sub(amount_to_migrate)
+        .ok_or(CustomError::AmountOverflow)?;
+    if !your_pool_data.is_v1() {
+        your_pool_data.total_your_staked = your_pool_data
+            .total_your_staked
+            .checked_sub(amount_to_migrate)
+            .ok_or(CustomError::AmountOverflow)?;
+    }
+
+    destination_user_data.balance_your_staked = destination_user_data
+        .balance_your_staked
+        .checked_add(amount_received)
+        .ok_or(CustomError::AmountOverflow)?;
+    if !destination_pool_data.is_v1() {
+        destination_pool_data.total_your_staked = destination_pool_data
+            .total_your_staked
+            .checked_add(amount_received)
+            .ok_or(CustomError::AmountOverflow)?;
+    }
+
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    destination_pool_data.pack_versioned(&mut destination_pool_data_byte_array)?;
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+    destination_user_data.pack_versioned(&mut destination_user_data_byte_array)?;
+
+    StakingEvent::StakeMigrated {
+        pool: *your_pool_storage_account.key,
+        destination_pool: *destination_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        amount: amount_to_migrate,
+        timestamp: now,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::create_user::{
+        create_and_allocate_account_raw, get_user_storage_address_and_bump_seed,
+    },
+    state::{AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES},
+    utils,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` User V1 Storage Account
+/// 2. `[writable]` YOUR Pool V2 Storage Account
+/// 3. `[writable]` User V2 Storage Account
+/// 4. `[]` System Program
+pub fn process_migrate_user(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_wallet_account = next_account_info(account_info_iter)?;
+    let user_v1_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !user_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if *system_program_info.key != system_program::id() {
+        msg!("CustomError::InvalidSystemProgram");
+        return Err(CustomError::InvalidSystemProgram.into());
+    }
+
+    if user_v1_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut user_v1_data_byte_array = user_v1_storage_account.try_borrow_mut_data()?;
+    let user_v1_data = User::unpack_versioned(&user_v1_data_byte_array)?;
+    if !user_v1_data.is_v1() {
+        msg!("CustomError::ExpectedAccountTypeMismatched");
+        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    }
+
+    if user_v1_data.user_wallet != *user_wallet_account.key {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+
+    let (user_v1_storage_address, _bump_seed) = get_user_storage_address_and_bump_seed(
+        user_wallet_account.key,
+        &user_v1_data.your_pool,
+        program_id,
+    );
+    if user_v1_storage_address != *user_v1_storage_account.key {
+        msg!("Error: User V1 Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    if your_pool_data.is_v1() || your_pool_data.migrated_from != user_v1_data.your_pool {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    if !user_storage_account.data_is_empty() || user_storage_account.lamports() != 0 {
+        msg!("CustomError::UserStorageAccountAlreadyInitialized");
+        return Err(CustomError::UserStorageAccountAlreadyInitialized.into());
+    }
+
+    let (user_storage_address, bump_seed) = get_user_storage_address_and_bump_seed(
+        user_wallet_account.key,
+        your_pool_storage_account.key,
+        program_id,
+    );
+    if user_storage_address != *user_storage_account.key {
+        msg!("Error: User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let user_storage_account_signer_seeds: &[&[_]] = &[
+        &user_wallet_account.key.to_bytes(),
+        &your_pool_storage_account.key.to_bytes(),
+        &[bump_seed],
+    ];
+
+    create_and_allocate_account_raw(
+        *program_id,
+        user_storage_account,
+        system_program_info,
+        user_wallet_account,
+        USER_STORAGE_TOTAL_BYTES,
+        user_storage_account_signer_seeds,
+    )?;
+
+    let user_storage_data = User {
+        acc_type: AccTypesWithVersion::UserDataV2 as u8,
+        your_pool: *your_pool_storage_account.key,
+        nonce: bump_seed,
+        ..user_v1_data
+    };
+    user_storage_data.pack_versioned(&mut user_storage_account.try_borrow_mut_data()?)?;
+
+    your_pool_data.user_stake_count += 1u32;
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+
+    StakingEvent::UserMigrated {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        balance_staked: user_storage_data.balance_your_staked,
+        timestamp: Clock::get()?.unix_timestamp,
+    }
+    .emit()?;
+
+    debug_msg!("Closing the User V1 Storage account and transferring lamports to User wallet...");
+    utils::close_account(
+        user_v1_storage_account,
+        user_wallet_account,
+        &mut user_v1_data_byte_array,
+    )?;
+
+    Ok(())
+}
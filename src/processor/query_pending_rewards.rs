@@ -0,0 +1,48 @@
+use crate::{
+    error::CustomError,
+    state::{User, YourPool},
+    utils,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Returns the rewards the user could claim now as a little-endian u64 in the return data,
+/// for clients to read from a simulated transaction. Nothing is written.
+///
+/// 0. `[]` YOUR Pool Storage Account
+/// 1. `[]` User Storage Account
+pub fn process_query_pending_rewards(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+
+    if your_pool_storage_account.owner != program_id || user_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let your_pool_data = YourPool::unpack_versioned(&your_pool_storage_account.try_borrow_data()?)?;
+    let user_storage_data = User::unpack_versioned(&user_storage_account.try_borrow_data()?)?;
+    if user_storage_data.your_pool != *your_pool_storage_account.key {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    let pending_rewards = utils::pending_rewards(
+        &your_pool_data,
+        &user_storage_data,
+        Clock::get()?.unix_timestamp,
+    )?;
+    debug_msg!("pending_rewards: {}", pending_rewards);
+    set_return_data(&pending_rewards.to_le_bytes());
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::{
+    error::CustomError,
+    state::YourPool,
+    token,
+    utils::{self, TokenPrices},
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Returns the APR and APY of a `deposit` in the return data, packed as a `RewardProjection`,
+/// for clients to read from a simulated transaction. Prices of 0 value reward and staking
+/// base units the same; otherwise the mints are read for their decimals. Nothing is written.
+///
+/// 0. `[]` YOUR Pool Storage Account
+/// 1. `[]` YOUR Staking Mint, only with prices
+/// 2. `[]` YOUR Rewards Mint, only with prices
+pub fn process_query_reward_projection(
+    accounts: &[AccountInfo],
+    deposit: u64,
+    staking_price: u64,
+    rewards_price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let your_pool_data = YourPool::unpack_versioned(&your_pool_storage_account.try_borrow_data()?)?;
+
+    let prices = if staking_price == 0 && rewards_price == 0 {
+        None
+    } else {
+        let your_staking_mint = next_account_info(account_info_iter)?;
+        let your_rewards_mint = next_account_info(account_info_iter)?;
+        if your_pool_data.your_staking_mint != *your_staking_mint.key
+            || your_pool_data.your_rewards_mint != *your_rewards_mint.key
+        {
+            msg!("CustomError::MintMismatched");
+            return Err(CustomError::MintMismatched.into());
+        }
+        Some(TokenPrices {
+            staking_price,
+            staking_decimals: token::unpack_mint(your_staking_mint)?.decimals,
+            rewards_price,
+            rewards_decimals: token::unpack_mint(your_rewards_mint)?.decimals,
+        })
+    };
+
+    let projection = utils::reward_projection(
+        &your_pool_data,
+        deposit,
+        prices,
+        Clock::get()?.unix_timestamp,
+    )?;
+    debug_msg!("current_apr_bps: {}", projection.current_apr_bps);
+    debug_msg!("current_apy_bps: {}", projection.current_apy_bps);
+    debug_msg!("projected_apr_bps: {}", projection.projected_apr_bps);
+    debug_msg!("projected_apy_bps: {}", projection.projected_apy_bps);
+    set_return_data(&projection.pack());
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use crate::{
+    error::CustomError, events::StakingEvent, state::YourPool, token,
+    utils::constants::MAX_REWARD_FEE_BPS,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Sets the protocol fee taken from each rewards claim, in basis points, and the rewards token
+/// account it is paid to. The fee is capped at `MAX_REWARD_FEE_BPS`.
+///
+/// 0. `[signer]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Pool Storage Account
+/// 2. `[]` Fee Recipient Rewards Token Account
+pub fn process_set_fee(
+    accounts: &[AccountInfo],
+    reward_fee_bps: u16,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_owner_wallet_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let fee_recipient_account = next_account_info(account_info_iter)?;
+
+    if !pool_owner_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if reward_fee_bps > MAX_REWARD_FEE_BPS {
+        msg!("CustomError::RewardFeeTooHigh");
+        return Err(CustomError::RewardFeeTooHigh.into());
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    if your_pool_data.owner_wallet != *pool_owner_wallet_account.key {
+        msg!("CustomError::PoolOwnerMismatched");
+        return Err(CustomError::PoolOwnerMismatched.into());
+    }
+    // V1 pools keep paying the fixed legacy amount and have no room for the fee settings
+    if your_pool_data.is_v1() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    if !token::is_token_program(fee_recipient_account.owner)
+        || token::unpack_token_account(fee_recipient_account)?.mint
+            != your_pool_data.your_rewards_mint
+    {
+        msg!("CustomError::InvalidFeeRecipient");
+        return Err(CustomError::InvalidFeeRecipient.into());
+    }
+
+    if { your_pool_data.reward_fee_bps } != reward_fee_bps
+        || your_pool_data.fee_recipient != *fee_recipient_account.key
+    {
+        your_pool_data.reward_fee_bps = reward_fee_bps;
+        your_pool_data.fee_recipient = *fee_recipient_account.key;
+        your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    }
+
+    StakingEvent::RewardFeeSet {
+        pool: *your_pool_storage_account.key,
+        reward_fee_bps,
+        fee_recipient: *fee_recipient_account.key,
+        timestamp: Clock::get()?.unix_timestamp,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::create_user::create_and_allocate_account_raw,
+    state::{AccTypesWithVersion, NftWeight, YourPool, NFT_WEIGHT_STORAGE_TOTAL_BYTES},
+    utils::constants::NFT_WEIGHT_SEED,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Sets the reward weight of an NFT, derived off-chain from the collection's trait table.
+/// The weight only applies to stakes made after the change.
+///
+/// 0. `[signer, writable]` Pool Owner Wallet Account
+/// 1. `[]` YOUR Pool Storage Account
+/// 2. `[]` NFT Mint
+/// 3. `[writable]` NFT Weight Account (PDA of pool storage, NFT mint and "nft_weight")
+/// 4. `[]` System Program
+pub fn process_set_nft_weight(
+    accounts: &[AccountInfo],
+    weight: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_owner_wallet_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let nft_mint = next_account_info(account_info_iter)?;
+    let nft_weight_account = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !pool_owner_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if *system_program_info.key != system_program::id() {
+        msg!("CustomError::InvalidSystemProgram");
+        return Err(CustomError::InvalidSystemProgram.into());
+    }
+
+    if weight == 0u64 {
+        msg!("CustomError::AmountMustBeGreaterThanZero");
+        return Err(CustomError::AmountMustBeGreaterThanZero.into());
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let your_pool_data = YourPool::unpack_versioned(&your_pool_storage_account.try_borrow_data()?)?;
+    if your_pool_data.owner_wallet != *pool_owner_wallet_account.key {
+        msg!("CustomError::PoolOwnerMismatched");
+        return Err(CustomError::PoolOwnerMismatched.into());
+    }
+    if !your_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    let (nft_weight_address, bump_seed) = get_nft_record_address_and_bump_seed(
+        your_pool_storage_account.key,
+        nft_mint.key,
+        NFT_WEIGHT_SEED,
+        program_id,
+    );
+    if nft_weight_address != *nft_weight_account.key {
+        msg!("Error: NFT Weight address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if nft_weight_account.data_is_empty() {
+        create_and_allocate_account_raw(
+            *program_id,
+            nft_weight_account,
+            system_program_info,
+            pool_owner_wallet_account,
+            NFT_WEIGHT_STORAGE_TOTAL_BYTES,
+            &[
+                &your_pool_storage_account.key.to_bytes(),
+                &nft_mint.key.to_bytes(),
+                NFT_WEIGHT_SEED,
+                &[bump_seed],
+            ],
+        )?;
+    }
+
+    let nft_weight_data = NftWeight {
+        acc_type: AccTypesWithVersion::NftWeightDataV1 as u8,
+        your_pool: *your_pool_storage_account.key,
+        nft_mint: *nft_mint.key,
+        weight,
+    };
+    nft_weight_data.pack(&mut nft_weight_account.try_borrow_mut_data()?)?;
+    debug_msg!("NFT weight: {}", weight);
+
+    StakingEvent::NftWeightSet {
+        pool: *your_pool_storage_account.key,
+        mint: *nft_mint.key,
+        weight,
+        timestamp: Clock::get()?.unix_timestamp,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Derives the address of an NFT record of the pool, `record_seed` being `NFT_ESCROW_SEED`,
+/// `NFT_STAKE_SEED` or `NFT_WEIGHT_SEED`
+pub fn get_nft_record_address(
+    pool_storage: &Pubkey,
+    nft_mint: &Pubkey,
+    record_seed: &[u8],
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_nft_record_address_and_bump_seed(pool_storage, nft_mint, record_seed, program_id).0
+}
+
+pub fn get_nft_record_address_and_bump_seed(
+    pool_storage: &Pubkey,
+    nft_mint: &Pubkey,
+    record_seed: &[u8],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool_storage.to_bytes(), &nft_mint.to_bytes(), record_seed],
+        program_id,
+    )
+}
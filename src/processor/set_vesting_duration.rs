@@ -0,0 +1,64 @@
+use crate::{error::CustomError, events::StakingEvent, state::YourPool};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Sets the period, in seconds, over which claimed rewards unlock linearly. Zero pays claims
+/// out directly. Rewards already vesting keep their schedule.
+///
+/// 0. `[signer]` Pool Owner Wallet Account
+/// 1. `[writable]` YOUR Pool Storage Account
+pub fn process_set_vesting_duration(
+    accounts: &[AccountInfo],
+    vesting_duration: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_owner_wallet_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+
+    if !pool_owner_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if vesting_duration > i64::MAX as u64 {
+        msg!("CustomError::AmountOverflow");
+        return Err(CustomError::AmountOverflow.into());
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    if your_pool_data.owner_wallet != *pool_owner_wallet_account.key {
+        msg!("CustomError::PoolOwnerMismatched");
+        return Err(CustomError::PoolOwnerMismatched.into());
+    }
+    // V1 pools keep paying the fixed legacy amount and have no room for the vesting settings
+    if your_pool_data.is_v1() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    if { your_pool_data.vesting_duration } != vesting_duration {
+        your_pool_data.vesting_duration = vesting_duration;
+        your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    }
+
+    StakingEvent::VestingDurationSet {
+        pool: *your_pool_storage_account.key,
+        vesting_duration,
+        timestamp: Clock::get()?.unix_timestamp,
+    }
+    .emit()?;
+
+    Ok(())
+}
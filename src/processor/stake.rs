@@ -1,24 +1,31 @@
 use crate::{
     error::CustomError,
+    events::StakingEvent,
     processor::create_user::get_user_storage_address_and_bump_seed,
-    state::{
-        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES,
-        YOUR_POOL_STORAGE_TOTAL_BYTES,
-    },
+    state::{User, YourPool},
+    token, utils,
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
-    program::invoke,
+    program::invoke_signed,
     program_error::ProgramError,
-    program_pack::Pack,
     pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
 };
-use spl_token::state::Account as TokenAccount;
 
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[writable]` YOUR Staking Vault
+/// 4. `[writable]` User YOUR Token Account
+/// 5. `[]` YOUR Staking Mint
+/// 6. `[]` Token Program
+/// 7. `[writable]` Receipt Mint, only for pools with a receipt mint
+/// 8. `[writable]` User Receipt Token Account, only for pools with a receipt mint
+/// 9. `[]` Pool Signer PDA, only for pools with a receipt mint
 pub fn process_stake(
     accounts: &[AccountInfo],
     amount_to_deposit: u64,
@@ -30,12 +37,17 @@ pub fn process_stake(
     let your_pool_storage_account = next_account_info(account_info_iter)?;
     let your_staking_vault = next_account_info(account_info_iter)?;
     let user_your_ata = next_account_info(account_info_iter)?;
+    let your_staking_mint = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
 
     if !user_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
     }
+    if !token::is_token_program(token_program.key) {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
 
     if amount_to_deposit == 0u64 {
         msg!("CustomError::AmountMustBeGreaterThanZero");
@@ -52,32 +64,25 @@ pub fn process_stake(
         return Err(ProgramError::InvalidSeeds);
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
     }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
 
-    if user_storage_account.data_len() != USER_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
+    if your_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
     }
-
-    let mut user_data_byte_array = user_storage_account.data.try_borrow_mut().unwrap();
-    let mut user_storage_data: User =
-        User::try_from_slice(&user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]).unwrap();
-    if user_storage_data.acc_type != AccTypesWithVersion::UserDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    if your_pool_data.is_paused() {
+        msg!("CustomError::PoolPaused");
+        return Err(CustomError::PoolPaused.into());
     }
 
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+
     if user_storage_data.user_wallet != *user_wallet_account.key {
         msg!("CustomError::UserStorageAuthorityMismatched");
         return Err(CustomError::UserStorageAuthorityMismatched.into());
@@ -91,39 +96,99 @@ pub fn process_stake(
         msg!("CustomError::AccountOwnerShouldBeTokenProgram");
         return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
     }
-    let your_staking_vault_data = TokenAccount::unpack(&your_staking_vault.data.borrow())?;
-    let (pool_signer_address, _bump_seed) =
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
+    let (pool_signer_address, bump_seed) =
         Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
-    if your_staking_vault_data.owner != pool_signer_address {
+    if your_staking_vault_data.owner != pool_signer_address
+        || your_pool_data.your_staking_vault != *your_staking_vault.key
+    {
         msg!("CustomError::InvalidStakingVault");
         return Err(CustomError::InvalidStakingVault.into());
     }
 
-    msg!("Calling the token program to transfer to Staking Vault...");
-    invoke(
-        &spl_token::instruction::transfer(
-            token_program.key,
-            user_your_ata.key,
-            your_staking_vault.key,
-            user_wallet_account.key,
-            &[],
-            amount_to_deposit,
-        )?,
-        &[
-            user_your_ata.clone(),
-            your_staking_vault.clone(),
-            user_wallet_account.clone(),
-            token_program.clone(),
-        ],
+    let user_your_ata_data = token::unpack_token_account(user_your_ata)?;
+    if user_your_ata_data.mint != your_staking_vault_data.mint
+        || *your_staking_mint.key != your_staking_vault_data.mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    debug_msg!("Calling the token program to transfer to Staking Vault...");
+    // Mints with a transfer fee credit the vault with less than the deposited amount
+    let amount_received = token::transfer_checked(
+        token_program,
+        user_your_ata,
+        your_staking_mint,
+        your_staking_vault,
+        user_wallet_account,
+        amount_to_deposit,
+        &[],
     )?;
+
+    if your_pool_data.has_receipt_mint() {
+        let receipt_mint = next_account_info(account_info_iter)?;
+        let user_receipt_account = next_account_info(account_info_iter)?;
+        let pool_signer_pda = next_account_info(account_info_iter)?;
+
+        if your_pool_data.receipt_mint != *receipt_mint.key {
+            msg!("CustomError::InvalidReceiptMint");
+            return Err(CustomError::InvalidReceiptMint.into());
+        }
+        if *pool_signer_pda.key != pool_signer_address {
+            msg!("CustomError::DerivedKeyInvalid");
+            return Err(CustomError::DerivedKeyInvalid.into());
+        }
+
+        debug_msg!("Calling the token program to mint receipt tokens to User...");
+        invoke_signed(
+            &token::instruction_for(
+                token_program.key,
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    receipt_mint.key,
+                    user_receipt_account.key,
+                    &pool_signer_address,
+                    &[&pool_signer_address],
+                    amount_received,
+                ),
+            )?,
+            &[
+                receipt_mint.clone(),
+                user_receipt_account.clone(),
+                pool_signer_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[&your_pool_storage_account.key.to_bytes(), &[bump_seed]]],
+        )?;
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+
     user_storage_data.balance_your_staked = user_storage_data
         .balance_your_staked
-        .checked_add(amount_to_deposit)
+        .checked_add(amount_received)
         .ok_or(CustomError::AmountOverflow)?;
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
-    user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&user_storage_data.try_to_vec().unwrap());
+    // V1 pools track neither the stake total nor the rewards, so staking leaves them unchanged
+    if !your_pool_data.is_v1() {
+        your_pool_data.total_your_staked = your_pool_data
+            .total_your_staked
+            .checked_add(amount_received)
+            .ok_or(CustomError::AmountOverflow)?;
+        your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    }
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+
+    StakingEvent::Staked {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        amount: amount_received,
+        balance_staked: user_storage_data.balance_your_staked,
+        total_staked: your_pool_data.total_your_staked,
+        timestamp: now,
+    }
+    .emit()?;
 
     Ok(())
 }
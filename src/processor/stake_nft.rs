@@ -0,0 +1,257 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    metadata,
+    processor::{
+        create_user::{create_and_allocate_account_raw, get_user_storage_address_and_bump_seed},
+        set_nft_weight::get_nft_record_address_and_bump_seed,
+    },
+    state::{
+        AccTypesWithVersion, NftStake, NftWeight, User, YourPool, NFT_STAKE_STORAGE_TOTAL_BYTES,
+    },
+    token, utils,
+    utils::constants::{NFT_BASE_WEIGHT, NFT_ESCROW_SEED, NFT_STAKE_SEED, NFT_WEIGHT_SEED},
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// 0. `[signer, writable]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[]` NFT Mint
+/// 4. `[]` NFT Metadata Account (Metaplex)
+/// 5. `[writable]` User NFT Token Account
+/// 6. `[writable]` NFT Escrow (PDA of pool storage, NFT mint and "nft_escrow")
+/// 7. `[writable]` NFT Stake Account (PDA of pool storage, NFT mint and "nft_stake")
+/// 8. `[]` NFT Weight Account (PDA of pool storage, NFT mint and "nft_weight"), may be empty
+/// 9. `[]` Token Program
+/// 10. `[]` System Program
+/// 11. `[]` Rent Sysvar
+pub fn process_stake_nft(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_wallet_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let nft_mint = next_account_info(account_info_iter)?;
+    let nft_metadata = next_account_info(account_info_iter)?;
+    let user_nft_account = next_account_info(account_info_iter)?;
+    let nft_escrow = next_account_info(account_info_iter)?;
+    let nft_stake_account = next_account_info(account_info_iter)?;
+    let nft_weight_account = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_sysvar_info = next_account_info(account_info_iter)?;
+
+    if !user_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) || nft_mint.owner != token_program.key {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        msg!("CustomError::InvalidSystemProgram");
+        return Err(CustomError::InvalidSystemProgram.into());
+    }
+    if *rent_sysvar_info.key != sysvar::rent::id() {
+        msg!("ProgramError::InvalidArgument");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (user_storage_address, _bump_seed) = get_user_storage_address_and_bump_seed(
+        user_wallet_account.key,
+        your_pool_storage_account.key,
+        program_id,
+    );
+    if user_storage_address != *user_storage_account.key {
+        msg!("Error: User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    if !your_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+    if your_pool_data.is_paused() {
+        msg!("CustomError::PoolPaused");
+        return Err(CustomError::PoolPaused.into());
+    }
+
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+
+    if user_storage_data.user_wallet != *user_wallet_account.key {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+    if user_storage_data.your_pool != *your_pool_storage_account.key {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    let nft_mint_data = token::unpack_mint(nft_mint)?;
+    if nft_mint_data.decimals != 0 || nft_mint_data.supply != 1 {
+        msg!("CustomError::InvalidNftMetadata");
+        return Err(CustomError::InvalidNftMetadata.into());
+    }
+    if metadata::get_verified_collection(nft_metadata, nft_mint.key)?
+        != Some(your_pool_data.nft_collection)
+    {
+        msg!("CustomError::NftCollectionNotVerified");
+        return Err(CustomError::NftCollectionNotVerified.into());
+    }
+
+    let user_nft_account_data = token::unpack_token_account(user_nft_account)?;
+    if user_nft_account_data.mint != *nft_mint.key {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let (nft_escrow_address, nft_escrow_bump_seed) = get_nft_record_address_and_bump_seed(
+        your_pool_storage_account.key,
+        nft_mint.key,
+        NFT_ESCROW_SEED,
+        program_id,
+    );
+    let (nft_stake_address, nft_stake_bump_seed) = get_nft_record_address_and_bump_seed(
+        your_pool_storage_account.key,
+        nft_mint.key,
+        NFT_STAKE_SEED,
+        program_id,
+    );
+    let (nft_weight_address, _bump_seed) = get_nft_record_address_and_bump_seed(
+        your_pool_storage_account.key,
+        nft_mint.key,
+        NFT_WEIGHT_SEED,
+        program_id,
+    );
+    if nft_escrow_address != *nft_escrow.key
+        || nft_stake_address != *nft_stake_account.key
+        || nft_weight_address != *nft_weight_account.key
+    {
+        msg!("Error: NFT record address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    // NFTs without a weight set by the pool owner count as 1x
+    let weight = if nft_weight_account.data_is_empty() {
+        NFT_BASE_WEIGHT
+    } else {
+        if nft_weight_account.owner != program_id {
+            msg!("CustomError::ExpectedAccountTypeMismatched");
+            return Err(CustomError::ExpectedAccountTypeMismatched.into());
+        }
+        NftWeight::unpack(&nft_weight_account.try_borrow_data()?)?.weight
+    };
+
+    let (pool_signer_address, _bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+
+    debug_msg!("Creating the NFT escrow account owned by the token program...");
+    create_and_allocate_account_raw(
+        *token_program.key,
+        nft_escrow,
+        system_program_info,
+        user_wallet_account,
+        token::get_account_data_size(token_program, nft_mint)?,
+        &[
+            &your_pool_storage_account.key.to_bytes(),
+            &nft_mint.key.to_bytes(),
+            NFT_ESCROW_SEED,
+            &[nft_escrow_bump_seed],
+        ],
+    )?;
+    invoke(
+        &token::instruction_for(
+            token_program.key,
+            spl_token::instruction::initialize_account2(
+                &spl_token::id(),
+                nft_escrow.key,
+                nft_mint.key,
+                &pool_signer_address,
+            ),
+        )?,
+        &[
+            nft_escrow.clone(),
+            nft_mint.clone(),
+            rent_sysvar_info.clone(),
+            token_program.clone(),
+        ],
+    )?;
+
+    debug_msg!("Calling the token program to transfer the NFT to its escrow...");
+    token::transfer_checked(
+        token_program,
+        user_nft_account,
+        nft_mint,
+        nft_escrow,
+        user_wallet_account,
+        1u64,
+        &[],
+    )?;
+
+    create_and_allocate_account_raw(
+        *program_id,
+        nft_stake_account,
+        system_program_info,
+        user_wallet_account,
+        NFT_STAKE_STORAGE_TOTAL_BYTES,
+        &[
+            &your_pool_storage_account.key.to_bytes(),
+            &nft_mint.key.to_bytes(),
+            NFT_STAKE_SEED,
+            &[nft_stake_bump_seed],
+        ],
+    )?;
+    let nft_stake_data = NftStake {
+        acc_type: AccTypesWithVersion::NftStakeDataV1 as u8,
+        your_pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        nft_mint: *nft_mint.key,
+        weight,
+    };
+    nft_stake_data.pack(&mut nft_stake_account.try_borrow_mut_data()?)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+
+    user_storage_data.balance_your_staked = user_storage_data
+        .balance_your_staked
+        .checked_add(weight)
+        .ok_or(CustomError::AmountOverflow)?;
+    your_pool_data.total_your_staked = your_pool_data
+        .total_your_staked
+        .checked_add(weight)
+        .ok_or(CustomError::AmountOverflow)?;
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+
+    StakingEvent::NftStaked {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        nft_mint: *nft_mint.key,
+        weight,
+        balance_staked: user_storage_data.balance_your_staked,
+        total_staked: your_pool_data.total_your_staked,
+        timestamp: now,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -0,0 +1,246 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::create_user::{
+        create_and_allocate_account_raw, get_user_storage_address_and_bump_seed,
+    },
+    state::{
+        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES, USER_V1_STORAGE_TOTAL_BYTES,
+    },
+    utils,
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// 0. `[signer, writable]` Source User Wallet Account
+/// 1. `[writable]` Source User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[]` Destination User Wallet Account
+/// 4. `[writable]` Destination User Storage Account (created if it does not exist yet)
+/// 5. `[]` System Program
+pub fn process_transfer_position(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_wallet_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let destination_wallet_account = next_account_info(account_info_iter)?;
+    let destination_storage_account = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !user_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if *system_program_info.key != system_program::id() {
+        msg!("CustomError::InvalidSystemProgram");
+        return Err(CustomError::InvalidSystemProgram.into());
+    }
+
+    if amount == 0u64 {
+        msg!("CustomError::AmountMustBeGreaterThanZero");
+        return Err(CustomError::AmountMustBeGreaterThanZero.into());
+    }
+
+    if user_wallet_account.key == destination_wallet_account.key {
+        msg!("CustomError::SameSourceAndDestination");
+        return Err(CustomError::SameSourceAndDestination.into());
+    }
+
+    let (user_storage_address, _bump_seed) = get_user_storage_address_and_bump_seed(
+        user_wallet_account.key,
+        your_pool_storage_account.key,
+        program_id,
+    );
+    if user_storage_address != *user_storage_account.key {
+        msg!("Error: User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let (destination_storage_address, destination_bump_seed) =
+        get_user_storage_address_and_bump_seed(
+            destination_wallet_account.key,
+            your_pool_storage_account.key,
+            program_id,
+        );
+    if destination_storage_address != *destination_storage_account.key {
+        msg!("Error: Destination User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+
+    if your_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    // Receipt tokens stay in the source wallet, so the destination could never unstake them
+    if your_pool_data.has_receipt_mint() {
+        msg!("CustomError::ReceiptPoolNotTransferable");
+        return Err(CustomError::ReceiptPoolNotTransferable.into());
+    }
+
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+
+    if user_storage_data.user_wallet != *user_wallet_account.key {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+    if user_storage_data.your_pool != *your_pool_storage_account.key {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    // Only the active stake can move, tokens waiting for FinalUnstake stay with the source
+    let active_staked = user_storage_data
+        .balance_your_staked
+        .checked_sub(user_storage_data.unstake_pending)
+        .ok_or(CustomError::AmountOverflow)?;
+    if active_staked < amount {
+        msg!("CustomError::InsufficientStakeToTransfer");
+        return Err(CustomError::InsufficientStakeToTransfer.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+
+    let mut destination_storage_data = if destination_storage_account.data_is_empty() {
+        // Users of a pool that was not migrated yet keep the pool's V1 layout
+        let (user_acc_type, user_storage_total_bytes) = if your_pool_data.is_v1() {
+            (AccTypesWithVersion::UserDataV1, USER_V1_STORAGE_TOTAL_BYTES)
+        } else {
+            (AccTypesWithVersion::UserDataV2, USER_STORAGE_TOTAL_BYTES)
+        };
+
+        debug_msg!("Creating the Destination User Storage account...");
+        create_and_allocate_account_raw(
+            *program_id,
+            destination_storage_account,
+            system_program_info,
+            user_wallet_account,
+            user_storage_total_bytes,
+            &[
+                &destination_wallet_account.key.to_bytes(),
+                &your_pool_storage_account.key.to_bytes(),
+                &[destination_bump_seed],
+            ],
+        )?;
+
+        your_pool_data.user_stake_count = your_pool_data
+            .user_stake_count
+            .checked_add(1u32)
+            .ok_or(CustomError::AmountOverflow)?;
+
+        StakingEvent::UserCreated {
+            pool: *your_pool_storage_account.key,
+            user_wallet: *destination_wallet_account.key,
+            user_stake_count: your_pool_data.user_stake_count,
+            timestamp: now,
+        }
+        .emit()?;
+
+        User {
+            acc_type: user_acc_type as u8,
+            user_wallet: *destination_wallet_account.key,
+            your_pool: *your_pool_storage_account.key,
+            balance_your_staked: 0u64,
+            unstake_pending: 0u64,
+            unstake_pending_date: 0i64,
+            nonce: destination_bump_seed,
+            claim_timeout_date: 0i64,
+            reward_per_token_complete: your_pool_data.reward_per_token_stored,
+            reward_per_token_pending: 0u64,
+            referrer: Pubkey::default(),
+            referral_rewards_owed: 0u64,
+            vesting_total: 0u64,
+            vesting_withdrawn: 0u64,
+            vesting_start: 0i64,
+            vesting_end: 0i64,
+            vesting_unlocked: 0u64,
+        }
+    } else {
+        if *destination_storage_account.owner != *program_id {
+            msg!("CustomError::UserStorageAccountAlreadyInitialized");
+            return Err(CustomError::UserStorageAccountAlreadyInitialized.into());
+        }
+        User::unpack_versioned(&destination_storage_account.try_borrow_data()?)?
+    };
+
+    if destination_storage_data.user_wallet != *destination_wallet_account.key
+        || destination_storage_data.your_pool != *your_pool_storage_account.key
+    {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    // Moving stake to a fresh wallet must not skip the source's claim timeout
+    destination_storage_data.claim_timeout_date = destination_storage_data
+        .claim_timeout_date
+        .max(user_storage_data.claim_timeout_date);
+
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+    utils::update_rewards(
+        &mut your_pool_data,
+        Some(&mut destination_storage_data),
+        now,
+    )?;
+
+    // Rewards settled so far follow the stake in proportion to the transferred amount
+    let rewards_transferred = (user_storage_data.reward_per_token_pending as u128)
+        .checked_mul(amount as u128)
+        .and_then(|value| value.checked_div(user_storage_data.balance_your_staked as u128))
+        .ok_or(CustomError::AmountOverflow)? as u64;
+
+    user_storage_data.balance_your_staked = user_storage_data
+        .balance_your_staked
+        .checked_sub(amount)
+        .ok_or(CustomError::AmountOverflow)?;
+    user_storage_data.reward_per_token_pending = user_storage_data
+        .reward_per_token_pending
+        .checked_sub(rewards_transferred)
+        .ok_or(CustomError::AmountOverflow)?;
+
+    destination_storage_data.balance_your_staked = destination_storage_data
+        .balance_your_staked
+        .checked_add(amount)
+        .ok_or(CustomError::AmountOverflow)?;
+    destination_storage_data.reward_per_token_pending = destination_storage_data
+        .reward_per_token_pending
+        .checked_add(rewards_transferred)
+        .ok_or(CustomError::AmountOverflow)?;
+
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+    destination_storage_data
+        .pack_versioned(&mut destination_storage_account.try_borrow_mut_data()?)?;
+
+    StakingEvent::PositionTransferred {
+        pool: *your_pool_storage_account.key,
+        from_wallet: *user_wallet_account.key,
+        to_wallet: *destination_wallet_account.key,
+        amount,
+        rewards_amount: rewards_transferred,
+        timestamp: now,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -1,24 +1,31 @@
 use crate::{
     error::CustomError,
+    events::StakingEvent,
     processor::create_user::get_user_storage_address_and_bump_seed,
-    state::{
-        AccTypesWithVersion, User, YourPool, USER_STORAGE_TOTAL_BYTES,
-        YOUR_POOL_STORAGE_TOTAL_BYTES,
-    },
+    state::{User, YourPool},
+    token,
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::invoke,
     program_error::ProgramError,
     pubkey::Pubkey,
     sysvar::clock::Clock,
     sysvar::Sysvar,
 };
 
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[]` YOUR Pool Storage Account, only read as unstaking settles at FinalUnstake
+/// 3. `[]` YOUR Staking Vault
+/// 4. `[]` User YOUR Token Account
+/// 5. `[]` Pool Signer PDA
+/// 6. `[]` Token Program
+/// 7. `[writable]` Receipt Mint, only for pools with a receipt mint
+/// 8. `[writable]` User Receipt Token Account, only for pools with a receipt mint
 pub fn process_unstake(
     accounts: &[AccountInfo],
     amount_to_withdraw: u64,
@@ -28,16 +35,16 @@ pub fn process_unstake(
     let user_wallet_account = next_account_info(account_info_iter)?;
     let user_storage_account = next_account_info(account_info_iter)?;
     let your_pool_storage_account = next_account_info(account_info_iter)?;
-    let _your_staking_vault = next_account_info(account_info_iter)?;
-    let _user_your_ata = next_account_info(account_info_iter)?;
-    let _pool_signer_pda = next_account_info(account_info_iter)?;
+    let your_staking_vault = next_account_info(account_info_iter)?;
+    let user_your_ata = next_account_info(account_info_iter)?;
+    let pool_signer_pda = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
 
     if !user_wallet_account.is_signer {
         msg!("ProgramError::MissingRequiredSignature");
         return Err(ProgramError::MissingRequiredSignature);
     }
-    if token_program.key != &spl_token::id() {
+    if !token::is_token_program(token_program.key) {
         msg!("CustomError::InvalidTokenProgram");
         return Err(CustomError::InvalidTokenProgram.into());
     }
@@ -57,31 +64,19 @@ pub fn process_unstake(
         return Err(ProgramError::InvalidSeeds);
     }
 
-    if your_pool_storage_account.data_len() != YOUR_POOL_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
-    }
-    let mut your_pool_data_byte_array = your_pool_storage_account.data.try_borrow_mut().unwrap();
-    let your_pool_data: YourPool =
-        YourPool::try_from_slice(&your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES])
-            .unwrap();
-    if your_pool_data.acc_type != AccTypesWithVersion::YourPoolDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
     }
+    let your_pool_data = YourPool::unpack_versioned(&your_pool_storage_account.try_borrow_data()?)?;
 
-    if user_storage_account.data_len() != USER_STORAGE_TOTAL_BYTES {
-        msg!("CustomError::DataSizeNotMatched");
-        return Err(CustomError::DataSizeNotMatched.into());
+    if your_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
     }
 
-    let mut user_data_byte_array = user_storage_account.data.try_borrow_mut().unwrap();
-    let mut user_storage_data: User =
-        User::try_from_slice(&user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]).unwrap();
-    if user_storage_data.acc_type != AccTypesWithVersion::UserDataV1 as u8 {
-        msg!("CustomError::ExpectedAccountTypeMismatched");
-        return Err(CustomError::ExpectedAccountTypeMismatched.into());
-    }
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
 
     if user_storage_data.user_wallet != *user_wallet_account.key {
         msg!("CustomError::UserStorageAuthorityMismatched");
@@ -92,21 +87,94 @@ pub fn process_unstake(
         return Err(CustomError::UserPoolMismatched.into());
     }
 
+    if your_staking_vault.owner != token_program.key {
+        msg!("CustomError::AccountOwnerShouldBeTokenProgram");
+        return Err(CustomError::AccountOwnerShouldBeTokenProgram.into());
+    }
+    let your_staking_vault_data = token::unpack_token_account(your_staking_vault)?;
+    let (pool_signer_address, _bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    if your_staking_vault_data.owner != pool_signer_address
+        || your_pool_data.your_staking_vault != *your_staking_vault.key
+    {
+        msg!("CustomError::InvalidStakingVault");
+        return Err(CustomError::InvalidStakingVault.into());
+    }
+
+    let user_your_ata_data = token::unpack_token_account(user_your_ata)?;
+    if user_your_ata_data.mint != your_staking_vault_data.mint {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
     if user_storage_data.balance_your_staked < amount_to_withdraw {
         msg!("CustomError::InsufficientFundsToUnstake");
         return Err(CustomError::InsufficientFundsToUnstake.into());
     }
 
-    let now = Clock::get()?.unix_timestamp as i64;
+    let now = Clock::get()?.unix_timestamp;
+
+    if your_pool_data.has_receipt_mint() {
+        let receipt_mint = next_account_info(account_info_iter)?;
+        let user_receipt_account = next_account_info(account_info_iter)?;
+
+        if your_pool_data.receipt_mint != *receipt_mint.key {
+            msg!("CustomError::InvalidReceiptMint");
+            return Err(CustomError::InvalidReceiptMint.into());
+        }
+
+        // Receipts burned here are only given back by FinalUnstake, so requests add up
+        // instead of replacing a pending amount that was already paid for.
+        let unstake_pending = user_storage_data
+            .unstake_pending
+            .checked_add(amount_to_withdraw)
+            .ok_or(CustomError::AmountOverflow)?;
+        if user_storage_data.balance_your_staked < unstake_pending {
+            msg!("CustomError::InsufficientFundsToUnstake");
+            return Err(CustomError::InsufficientFundsToUnstake.into());
+        }
 
-    user_storage_data.unstake_pending = amount_to_withdraw;
+        debug_msg!("Calling the token program to burn User receipt tokens...");
+        invoke(
+            &token::instruction_for(
+                token_program.key,
+                spl_token::instruction::burn(
+                    &spl_token::id(),
+                    user_receipt_account.key,
+                    receipt_mint.key,
+                    user_wallet_account.key,
+                    &[],
+                    amount_to_withdraw,
+                ),
+            )?,
+            &[
+                user_receipt_account.clone(),
+                receipt_mint.clone(),
+                user_wallet_account.clone(),
+                token_program.clone(),
+            ],
+        )?;
+        user_storage_data.unstake_pending = unstake_pending;
+    } else {
+        user_storage_data.unstake_pending = amount_to_withdraw;
+    }
     user_storage_data.unstake_pending_date = now + 2; // pending for 2 seconds
-    msg!("Moved amount to pending");
+    debug_msg!("Moved amount to pending");
+
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
 
-    your_pool_data_byte_array[0usize..YOUR_POOL_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&your_pool_data.try_to_vec().unwrap());
-    user_data_byte_array[0usize..USER_STORAGE_TOTAL_BYTES]
-        .copy_from_slice(&user_storage_data.try_to_vec().unwrap());
+    StakingEvent::UnstakeRequested {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        amount: amount_to_withdraw,
+        unstake_pending_date: user_storage_data.unstake_pending_date,
+        timestamp: now,
+    }
+    .emit()?;
 
     Ok(())
 }
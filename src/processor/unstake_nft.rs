@@ -0,0 +1,191 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::{
+        create_user::get_user_storage_address_and_bump_seed, set_nft_weight::get_nft_record_address,
+    },
+    state::{NftStake, User, YourPool},
+    token, utils,
+    utils::constants::{NFT_ESCROW_SEED, NFT_STAKE_SEED},
+};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// 0. `[signer, writable]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[]` NFT Mint
+/// 4. `[writable]` User NFT Token Account
+/// 5. `[writable]` NFT Escrow (PDA of pool storage, NFT mint and "nft_escrow")
+/// 6. `[writable]` NFT Stake Account (PDA of pool storage, NFT mint and "nft_stake")
+/// 7. `[]` Pool Signer PDA
+/// 8. `[]` Token Program
+pub fn process_unstake_nft(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_wallet_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let nft_mint = next_account_info(account_info_iter)?;
+    let user_nft_account = next_account_info(account_info_iter)?;
+    let nft_escrow = next_account_info(account_info_iter)?;
+    let nft_stake_account = next_account_info(account_info_iter)?;
+    let pool_signer_pda = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if !user_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) || nft_escrow.owner != token_program.key {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+
+    let (user_storage_address, _bump_seed) = get_user_storage_address_and_bump_seed(
+        user_wallet_account.key,
+        your_pool_storage_account.key,
+        program_id,
+    );
+    if user_storage_address != *user_storage_account.key {
+        msg!("Error: User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if get_nft_record_address(
+        your_pool_storage_account.key,
+        nft_mint.key,
+        NFT_ESCROW_SEED,
+        program_id,
+    ) != *nft_escrow.key
+        || get_nft_record_address(
+            your_pool_storage_account.key,
+            nft_mint.key,
+            NFT_STAKE_SEED,
+            program_id,
+        ) != *nft_stake_account.key
+    {
+        msg!("Error: NFT record address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+    if !your_pool_data.is_nft_pool() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+
+    if user_storage_data.user_wallet != *user_wallet_account.key {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+    if user_storage_data.your_pool != *your_pool_storage_account.key {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    if nft_stake_account.owner != program_id {
+        msg!("CustomError::ExpectedAccountTypeMismatched");
+        return Err(CustomError::ExpectedAccountTypeMismatched.into());
+    }
+    let mut nft_stake_data_byte_array = nft_stake_account.try_borrow_mut_data()?;
+    let nft_stake_data = NftStake::unpack(&nft_stake_data_byte_array)?;
+    if nft_stake_data.user_wallet != *user_wallet_account.key {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+
+    if token::unpack_token_account(user_nft_account)?.mint != *nft_mint.key {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let (pool_signer_address, bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    let pool_signer_seeds: &[&[u8]] = &[&your_pool_storage_account.key.to_bytes(), &[bump_seed]];
+
+    debug_msg!("Calling the token program to return the NFT to User...");
+    token::transfer_checked(
+        token_program,
+        nft_escrow,
+        nft_mint,
+        user_nft_account,
+        pool_signer_pda,
+        1u64,
+        &[pool_signer_seeds],
+    )?;
+
+    debug_msg!("Calling the token program to close the NFT escrow...");
+    invoke_signed(
+        &token::instruction_for(
+            token_program.key,
+            spl_token::instruction::close_account(
+                &spl_token::id(),
+                nft_escrow.key,
+                user_wallet_account.key,
+                &pool_signer_address,
+                &[&pool_signer_address],
+            ),
+        )?,
+        &[
+            nft_escrow.clone(),
+            user_wallet_account.clone(),
+            pool_signer_pda.clone(),
+            token_program.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+
+    let now = Clock::get()?.unix_timestamp;
+    utils::update_rewards(&mut your_pool_data, Some(&mut user_storage_data), now)?;
+
+    user_storage_data.balance_your_staked = user_storage_data
+        .balance_your_staked
+        .checked_sub(nft_stake_data.weight)
+        .ok_or(CustomError::AmountOverflow)?;
+    your_pool_data.total_your_staked = your_pool_data
+        .total_your_staked
+        .checked_sub(nft_stake_data.weight)
+        .ok_or(CustomError::AmountOverflow)?;
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+
+    StakingEvent::NftUnstaked {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        nft_mint: *nft_mint.key,
+        weight: nft_stake_data.weight,
+        balance_staked: user_storage_data.balance_your_staked,
+        total_staked: your_pool_data.total_your_staked,
+        timestamp: now,
+    }
+    .emit()?;
+
+    debug_msg!("Closing the NFT Stake account and transferring lamports to User wallet...");
+    utils::close_account(
+        nft_stake_account,
+        user_wallet_account,
+        &mut nft_stake_data_byte_array,
+    )?;
+
+    Ok(())
+}
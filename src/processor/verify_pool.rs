@@ -0,0 +1,63 @@
+use crate::{error::CustomError, state::YourPool, token, utils};
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Fails unless the pool vaults hold what the pool owes: every staked token in the staking
+/// vault and the outstanding rewards in the rewards vault. Rewards are accrued up to now in
+/// memory only, nothing is written.
+///
+/// 0. `[]` YOUR Pool Storage Account
+/// 1. `[]` YOUR Staking Vault
+/// 2. `[]` YOUR Rewards Vault
+pub fn process_verify_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_staking_vault = next_account_info(account_info_iter)?;
+    let your_rewards_vault = next_account_info(account_info_iter)?;
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data =
+        YourPool::unpack_versioned(&your_pool_storage_account.try_borrow_data()?)?;
+    // V1 pools pay a fixed legacy amount and keep no rewards ledger to verify
+    if your_pool_data.is_v1() {
+        msg!("CustomError::PoolModeMismatched");
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+
+    if your_pool_data.your_staking_vault != *your_staking_vault.key
+        || !token::is_token_program(your_staking_vault.owner)
+    {
+        msg!("CustomError::InvalidStakingVault");
+        return Err(CustomError::InvalidStakingVault.into());
+    }
+    if your_pool_data.your_rewards_vault != *your_rewards_vault.key
+        || !token::is_token_program(your_rewards_vault.owner)
+    {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+    let staking_vault_amount = token::unpack_token_account(your_staking_vault)?.amount;
+    let rewards_vault_amount = token::unpack_token_account(your_rewards_vault)?.amount;
+
+    utils::update_rewards(&mut your_pool_data, None, Clock::get()?.unix_timestamp)?;
+
+    debug_msg!("total_your_staked: {}", {
+        your_pool_data.total_your_staked
+    });
+    debug_msg!("staking_vault_amount: {}", staking_vault_amount);
+    debug_msg!(
+        "outstanding_rewards: {}",
+        utils::outstanding_rewards(&your_pool_data)?
+    );
+    debug_msg!("rewards_vault_amount: {}", rewards_vault_amount);
+    utils::verify_pool_solvency(&your_pool_data, staking_vault_amount, rewards_vault_amount)
+}
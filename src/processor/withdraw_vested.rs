@@ -0,0 +1,124 @@
+use crate::{
+    error::CustomError,
+    events::StakingEvent,
+    processor::create_user::get_user_storage_address_and_bump_seed,
+    state::{User, YourPool},
+    token, utils,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+/// Pays out the claimed rewards unlocked so far by the user's vesting schedule
+///
+/// 0. `[signer]` User Wallet Account
+/// 1. `[writable]` User Storage Account
+/// 2. `[writable]` YOUR Pool Storage Account
+/// 3. `[writable]` YOUR Rewards Vault
+/// 4. `[writable]` User Rewards Token Account
+/// 5. `[]` Pool Signer PDA
+/// 6. `[]` YOUR Rewards Mint
+/// 7. `[]` Token Program of the rewards mint
+pub fn process_withdraw_vested(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_wallet_account = next_account_info(account_info_iter)?;
+    let user_storage_account = next_account_info(account_info_iter)?;
+    let your_pool_storage_account = next_account_info(account_info_iter)?;
+    let your_rewards_vault = next_account_info(account_info_iter)?;
+    let user_rewards_ata = next_account_info(account_info_iter)?;
+    let pool_signer_pda = next_account_info(account_info_iter)?;
+    let your_rewards_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if !user_wallet_account.is_signer {
+        msg!("ProgramError::MissingRequiredSignature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) {
+        msg!("CustomError::InvalidTokenProgram");
+        return Err(CustomError::InvalidTokenProgram.into());
+    }
+
+    let (user_storage_address, _bump_seed) = get_user_storage_address_and_bump_seed(
+        user_wallet_account.key,
+        your_pool_storage_account.key,
+        program_id,
+    );
+    if user_storage_address != *user_storage_account.key {
+        msg!("Error: User Storage address does not match seed derivation");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if your_pool_storage_account.owner != program_id {
+        msg!("CustomError::AccountOwnerShouldBeProgram");
+        return Err(CustomError::AccountOwnerShouldBeProgram.into());
+    }
+    let mut your_pool_data_byte_array = your_pool_storage_account.try_borrow_mut_data()?;
+    let mut your_pool_data = YourPool::unpack_versioned(&your_pool_data_byte_array)?;
+
+    let mut user_data_byte_array = user_storage_account.try_borrow_mut_data()?;
+    let mut user_storage_data = User::unpack_versioned(&user_data_byte_array)?;
+
+    if user_storage_data.user_wallet != *user_wallet_account.key {
+        msg!("CustomError::UserStorageAuthorityMismatched");
+        return Err(CustomError::UserStorageAuthorityMismatched.into());
+    }
+    if user_storage_data.your_pool != *your_pool_storage_account.key {
+        msg!("CustomError::UserPoolMismatched");
+        return Err(CustomError::UserPoolMismatched.into());
+    }
+
+    let (pool_signer_address, bump_seed) =
+        Pubkey::find_program_address(&[&your_pool_storage_account.key.to_bytes()], program_id);
+    if *pool_signer_pda.key != pool_signer_address {
+        msg!("CustomError::DerivedKeyInvalid");
+        return Err(CustomError::DerivedKeyInvalid.into());
+    }
+    if your_pool_data.your_rewards_vault != *your_rewards_vault.key {
+        msg!("CustomError::InvalidRewardsVault");
+        return Err(CustomError::InvalidRewardsVault.into());
+    }
+    if token::unpack_token_account(user_rewards_ata)?.mint != your_pool_data.your_rewards_mint
+        || *your_rewards_mint.key != your_pool_data.your_rewards_mint
+    {
+        msg!("CustomError::MintMismatched");
+        return Err(CustomError::MintMismatched.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let amount_to_withdraw = utils::withdraw_vested(&mut user_storage_data, now)?;
+    if amount_to_withdraw == 0u64 {
+        msg!("CustomError::AmountMustBeGreaterThanZero");
+        return Err(CustomError::AmountMustBeGreaterThanZero.into());
+    }
+
+    debug_msg!("Calling the token program to transfer vested YOUR to User from Rewards Vault...");
+    token::transfer_checked(
+        token_program,
+        your_rewards_vault,
+        your_rewards_mint,
+        user_rewards_ata,
+        pool_signer_pda,
+        amount_to_withdraw,
+        &[&[&your_pool_storage_account.key.to_bytes(), &[bump_seed]]],
+    )?;
+    utils::pay_out_rewards(&mut your_pool_data, amount_to_withdraw);
+
+    your_pool_data.pack_versioned(&mut your_pool_data_byte_array)?;
+    user_storage_data.pack_versioned(&mut user_data_byte_array)?;
+
+    StakingEvent::VestedWithdrawn {
+        pool: *your_pool_storage_account.key,
+        user_wallet: *user_wallet_account.key,
+        amount: amount_to_withdraw,
+        timestamp: now,
+    }
+    .emit()?;
+
+    Ok(())
+}
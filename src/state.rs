@@ -1,14 +1,21 @@
+use crate::error::CustomError;
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::pubkey::Pubkey;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+use std::mem::size_of;
 
 pub enum AccTypesWithVersion {
     YourPoolDataV1 = 2,
     UserDataV1 = 3,
+    YourPoolDataV2 = 4,
+    UserDataV2 = 5,
+    NftStakeDataV1 = 6,
+    NftWeightDataV1 = 7,
 }
 
-pub const YOUR_POOL_STORAGE_TOTAL_BYTES: usize = 94; // Should be 2 bytes less than real size of
+pub const YOUR_POOL_V1_STORAGE_TOTAL_BYTES: usize = 94;
 #[derive(Clone, BorshDeserialize, BorshSerialize, Copy)]
-pub struct YourPool {
+pub struct YourPoolV1 {
     pub acc_type: u8,
     pub owner_wallet: Pubkey,
     pub your_staking_vault: Pubkey,
@@ -19,8 +26,63 @@ pub struct YourPool {
     pub reward_duration_end: u64,
 }
 
-pub const USER_STORAGE_TOTAL_BYTES: usize = 98;
+pub const USER_V1_STORAGE_TOTAL_BYTES: usize = 98;
 #[derive(Clone, BorshDeserialize, BorshSerialize, Copy)]
+pub struct UserV1 {
+    pub acc_type: u8,
+    pub user_wallet: Pubkey,
+    pub your_pool: Pubkey,
+    pub balance_your_staked: u64,
+    pub unstake_pending: u64,
+    pub unstake_pending_date: i64,
+    pub nonce: u8,
+    pub claim_timeout_date: i64,
+}
+
+// V2 accounts are allocated with headroom past the serialized fields, so fields
+// appended to the end of the structs read as zero on already existing accounts.
+//
+// V2 and NFT structs are packed in the little-endian field order Borsh wrote them in,
+// so they are copied out of and back into account data with a bytemuck cast instead of being
+// parsed field by field. Processors work on the copy and write it back when it changed.
+// Fields wider than a byte are unaligned: copy them out, e.g. `{ pool.total_your_staked }`,
+// before formatting or borrowing them.
+pub const YOUR_POOL_STORAGE_TOTAL_BYTES: usize = 512;
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct YourPool {
+    pub acc_type: u8,
+    pub owner_wallet: Pubkey,
+    pub your_staking_vault: Pubkey,
+    pub your_reward_rate: u64,
+    pub your_epoch_duration: u64,
+    pub user_stake_count: u32,
+    pub pda_nonce: u8,
+    pub reward_duration_end: u64,
+    pub your_staking_mint: Pubkey,
+    pub your_rewards_vault: Pubkey,
+    pub your_rewards_mint: Pubkey,
+    pub total_your_staked: u64,
+    pub migrated_from: Pubkey,
+    pub pool_index: u64,
+    pub reward_per_token_stored: u128,
+    pub last_update_time: u64,
+    pub receipt_mint: Pubkey,
+    pub nft_collection: Pubkey,
+    pub referral_bps: u16,
+    pub referral_budget: u64,
+    pub reward_fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    pub vesting_duration: u64,
+    /// Rewards accrued to users and referrers that the rewards vault still holds for them
+    pub rewards_owed: u64,
+    /// Non-zero while the pool owner has paused new stakes
+    pub paused: u8,
+}
+
+pub const USER_STORAGE_TOTAL_BYTES: usize = 256;
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
 pub struct User {
     pub acc_type: u8,
     pub user_wallet: Pubkey,
@@ -30,4 +92,268 @@ pub struct User {
     pub unstake_pending_date: i64,
     pub nonce: u8,
     pub claim_timeout_date: i64,
+    pub reward_per_token_complete: u128,
+    pub reward_per_token_pending: u64,
+    pub referrer: Pubkey,
+    pub referral_rewards_owed: u64,
+    pub vesting_total: u64,
+    pub vesting_withdrawn: u64,
+    pub vesting_start: i64,
+    pub vesting_end: i64,
+    pub vesting_unlocked: u64,
+}
+
+/// Escrow record of a staked NFT, PDA of pool storage, NFT mint and "nft_stake"
+pub const NFT_STAKE_STORAGE_TOTAL_BYTES: usize = 128;
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct NftStake {
+    pub acc_type: u8,
+    pub your_pool: Pubkey,
+    pub user_wallet: Pubkey,
+    pub nft_mint: Pubkey,
+    pub weight: u64,
+}
+
+/// Reward weight of an NFT set by the pool owner, PDA of pool storage, NFT mint and "nft_weight"
+pub const NFT_WEIGHT_STORAGE_TOTAL_BYTES: usize = 96;
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct NftWeight {
+    pub acc_type: u8,
+    pub your_pool: Pubkey,
+    pub nft_mint: Pubkey,
+    pub weight: u64,
+}
+
+/// Copies a packed struct out of the start of account data
+fn load<T: Pod>(data: &[u8]) -> Result<T, ProgramError> {
+    data.get(..size_of::<T>())
+        .map(bytemuck::from_bytes::<T>)
+        .copied()
+        .ok_or_else(|| CustomError::DataSizeNotMatched.into())
+}
+
+/// Writes a packed struct to the start of account data
+fn store<T: Pod>(value: &T, data: &mut [u8]) -> Result<(), ProgramError> {
+    data.get_mut(..size_of::<T>())
+        .ok_or(CustomError::DataSizeNotMatched)?
+        .copy_from_slice(bytemuck::bytes_of(value));
+    Ok(())
+}
+
+impl From<YourPoolV1> for YourPool {
+    fn from(pool: YourPoolV1) -> Self {
+        YourPool {
+            acc_type: pool.acc_type,
+            owner_wallet: pool.owner_wallet,
+            your_staking_vault: pool.your_staking_vault,
+            your_reward_rate: pool.your_reward_rate,
+            your_epoch_duration: pool.your_epoch_duration,
+            user_stake_count: pool.user_stake_count,
+            pda_nonce: pool.pda_nonce,
+            reward_duration_end: pool.reward_duration_end,
+            your_staking_mint: Pubkey::default(),
+            your_rewards_vault: Pubkey::default(),
+            your_rewards_mint: Pubkey::default(),
+            total_your_staked: 0u64,
+            migrated_from: Pubkey::default(),
+            pool_index: 0u64,
+            reward_per_token_stored: 0u128,
+            last_update_time: 0u64,
+            receipt_mint: Pubkey::default(),
+            nft_collection: Pubkey::default(),
+            referral_bps: 0u16,
+            referral_budget: 0u64,
+            reward_fee_bps: 0u16,
+            fee_recipient: Pubkey::default(),
+            vesting_duration: 0u64,
+            rewards_owed: 0u64,
+            paused: 0u8,
+        }
+    }
+}
+
+impl From<YourPool> for YourPoolV1 {
+    fn from(pool: YourPool) -> Self {
+        YourPoolV1 {
+            acc_type: pool.acc_type,
+            owner_wallet: pool.owner_wallet,
+            your_staking_vault: pool.your_staking_vault,
+            your_reward_rate: pool.your_reward_rate,
+            your_epoch_duration: pool.your_epoch_duration,
+            user_stake_count: pool.user_stake_count,
+            pda_nonce: pool.pda_nonce,
+            reward_duration_end: pool.reward_duration_end,
+        }
+    }
+}
+
+impl From<UserV1> for User {
+    fn from(user: UserV1) -> Self {
+        User {
+            acc_type: user.acc_type,
+            user_wallet: user.user_wallet,
+            your_pool: user.your_pool,
+            balance_your_staked: user.balance_your_staked,
+            unstake_pending: user.unstake_pending,
+            unstake_pending_date: user.unstake_pending_date,
+            nonce: user.nonce,
+            claim_timeout_date: user.claim_timeout_date,
+            reward_per_token_complete: 0u128,
+            reward_per_token_pending: 0u64,
+            referrer: Pubkey::default(),
+            referral_rewards_owed: 0u64,
+            vesting_total: 0u64,
+            vesting_withdrawn: 0u64,
+            vesting_start: 0i64,
+            vesting_end: 0i64,
+            vesting_unlocked: 0u64,
+        }
+    }
+}
+
+impl From<User> for UserV1 {
+    fn from(user: User) -> Self {
+        UserV1 {
+            acc_type: user.acc_type,
+            user_wallet: user.user_wallet,
+            your_pool: user.your_pool,
+            balance_your_staked: user.balance_your_staked,
+            unstake_pending: user.unstake_pending,
+            unstake_pending_date: user.unstake_pending_date,
+            nonce: user.nonce,
+            claim_timeout_date: user.claim_timeout_date,
+        }
+    }
+}
+
+impl YourPool {
+    pub fn is_v1(&self) -> bool {
+        self.acc_type == AccTypesWithVersion::YourPoolDataV1 as u8
+    }
+
+    /// Pools created with a receipt mint issue 1:1 transferable receipts for the staked tokens
+    pub fn has_receipt_mint(&self) -> bool {
+        self.receipt_mint != Pubkey::default()
+    }
+
+    /// Pools created for a Metaplex collection stake NFTs instead of fungible tokens
+    pub fn is_nft_pool(&self) -> bool {
+        self.nft_collection != Pubkey::default()
+    }
+
+    /// Pools with a vesting duration lock claimed rewards and release them linearly
+    pub fn has_vesting(&self) -> bool {
+        self.vesting_duration != 0u64
+    }
+
+    /// Paused pools take no new stake, staked tokens and rewards can still be withdrawn
+    pub fn is_paused(&self) -> bool {
+        self.paused != 0u8
+    }
+
+    /// Reads a V1 or V2 pool, keeping the stored `acc_type` so it is written back in the same layout
+    pub fn unpack_versioned(data: &[u8]) -> Result<YourPool, ProgramError> {
+        let (your_pool_data, expected_acc_type) = match data.len() {
+            YOUR_POOL_V1_STORAGE_TOTAL_BYTES => (
+                YourPool::from(YourPoolV1::try_from_slice(data)?),
+                AccTypesWithVersion::YourPoolDataV1,
+            ),
+            YOUR_POOL_STORAGE_TOTAL_BYTES => {
+                (load::<YourPool>(data)?, AccTypesWithVersion::YourPoolDataV2)
+            }
+            _ => {
+                msg!("CustomError::DataSizeNotMatched");
+                return Err(CustomError::DataSizeNotMatched.into());
+            }
+        };
+        if your_pool_data.acc_type != expected_acc_type as u8 {
+            msg!("CustomError::ExpectedAccountTypeMismatched");
+            return Err(CustomError::ExpectedAccountTypeMismatched.into());
+        }
+        Ok(your_pool_data)
+    }
+
+    pub fn pack_versioned(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if !self.is_v1() {
+            return store(self, data);
+        }
+        let serialized = YourPoolV1::from(*self).try_to_vec()?;
+        data.get_mut(..serialized.len())
+            .ok_or(CustomError::DataSizeNotMatched)?
+            .copy_from_slice(&serialized);
+        Ok(())
+    }
+}
+
+impl User {
+    pub fn is_v1(&self) -> bool {
+        self.acc_type == AccTypesWithVersion::UserDataV1 as u8
+    }
+
+    pub fn has_referrer(&self) -> bool {
+        self.referrer != Pubkey::default()
+    }
+
+    /// Reads a V1 or V2 user, keeping the stored `acc_type` so it is written back in the same layout
+    pub fn unpack_versioned(data: &[u8]) -> Result<User, ProgramError> {
+        let (user_storage_data, expected_acc_type) = match data.len() {
+            USER_V1_STORAGE_TOTAL_BYTES => (
+                User::from(UserV1::try_from_slice(data)?),
+                AccTypesWithVersion::UserDataV1,
+            ),
+            USER_STORAGE_TOTAL_BYTES => (load::<User>(data)?, AccTypesWithVersion::UserDataV2),
+            _ => {
+                msg!("CustomError::DataSizeNotMatched");
+                return Err(CustomError::DataSizeNotMatched.into());
+            }
+        };
+        if user_storage_data.acc_type != expected_acc_type as u8 {
+            msg!("CustomError::ExpectedAccountTypeMismatched");
+            return Err(CustomError::ExpectedAccountTypeMismatched.into());
+        }
+        Ok(user_storage_data)
+    }
+
+    pub fn pack_versioned(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if !self.is_v1() {
+            return store(self, data);
+        }
+        let serialized = UserV1::from(*self).try_to_vec()?;
+        data.get_mut(..serialized.len())
+            .ok_or(CustomError::DataSizeNotMatched)?
+            .copy_from_slice(&serialized);
+        Ok(())
+    }
+}
+
+impl NftStake {
+    pub fn unpack(data: &[u8]) -> Result<NftStake, ProgramError> {
+        let nft_stake_data = load::<NftStake>(data)?;
+        if nft_stake_data.acc_type != AccTypesWithVersion::NftStakeDataV1 as u8 {
+            msg!("CustomError::ExpectedAccountTypeMismatched");
+            return Err(CustomError::ExpectedAccountTypeMismatched.into());
+        }
+        Ok(nft_stake_data)
+    }
+
+    pub fn pack(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        store(self, data)
+    }
+}
+
+impl NftWeight {
+    pub fn unpack(data: &[u8]) -> Result<NftWeight, ProgramError> {
+        let nft_weight_data = load::<NftWeight>(data)?;
+        if nft_weight_data.acc_type != AccTypesWithVersion::NftWeightDataV1 as u8 {
+            msg!("CustomError::ExpectedAccountTypeMismatched");
+            return Err(CustomError::ExpectedAccountTypeMismatched.into());
+        }
+        Ok(nft_weight_data)
+    }
+
+    pub fn pack(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        store(self, data)
+    }
 }
@@ -0,0 +1,178 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+use std::convert::TryInto;
+
+pub mod token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+// Token-2022 keeps the SPL Token layout and appends an account type byte and the
+// extensions (type, length, value) after it, mints being padded to the account size.
+const ACCOUNT_TYPE_INDEX: usize = TokenAccount::LEN;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+const EXTENSION_TYPE_TRANSFER_FEE_CONFIG: u16 = 1;
+
+const INSTRUCTION_GET_ACCOUNT_DATA_SIZE: u8 = 21;
+const INSTRUCTION_TRANSFER_FEE_EXTENSION: u8 = 26;
+const TRANSFER_FEE_INSTRUCTION_HARVEST_WITHHELD_TOKENS_TO_MINT: u8 = 4;
+
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == token_2022::id()
+}
+
+/// Points an instruction built by `spl_token` at the given token program, the base
+/// instructions of Token-2022 being encoded the same way
+pub fn instruction_for(
+    token_program_id: &Pubkey,
+    instruction: Result<Instruction, ProgramError>,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = instruction?;
+    instruction.program_id = *token_program_id;
+    Ok(instruction)
+}
+
+/// Unpacks the base state of a token account of either token program
+pub fn unpack_token_account(account_info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    let data = account_info.try_borrow_data()?;
+    if data.len() != TokenAccount::LEN
+        && data.get(ACCOUNT_TYPE_INDEX) != Some(&ACCOUNT_TYPE_ACCOUNT)
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    TokenAccount::unpack(&data[..TokenAccount::LEN])
+}
+
+/// Unpacks the base state of a mint of either token program
+pub fn unpack_mint(account_info: &AccountInfo) -> Result<Mint, ProgramError> {
+    let data = account_info.try_borrow_data()?;
+    if data.len() != Mint::LEN && data.get(ACCOUNT_TYPE_INDEX) != Some(&ACCOUNT_TYPE_MINT) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Mint::unpack(&data[..Mint::LEN])
+}
+
+/// Whether the mint is a Token-2022 mint with the transfer fee extension
+pub fn has_transfer_fee(mint_info: &AccountInfo) -> Result<bool, ProgramError> {
+    let data = mint_info.try_borrow_data()?;
+    if data.get(ACCOUNT_TYPE_INDEX) != Some(&ACCOUNT_TYPE_MINT) {
+        return Ok(false);
+    }
+    let mut extensions = &data[ACCOUNT_TYPE_INDEX + 1..];
+    while let (Some(extension_type), Some(length)) = (extensions.get(0..2), extensions.get(2..4)) {
+        let extension_type = u16::from_le_bytes([extension_type[0], extension_type[1]]);
+        if extension_type == EXTENSION_TYPE_TRANSFER_FEE_CONFIG {
+            return Ok(true);
+        }
+        let length = u16::from_le_bytes([length[0], length[1]]) as usize;
+        extensions = match extensions.get(4 + length..) {
+            Some(rest) => rest,
+            None => return Ok(false),
+        };
+    }
+    Ok(false)
+}
+
+/// Size of a token account for the mint, including the extensions Token-2022 requires for it
+pub fn get_account_data_size<'a>(
+    token_program: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+) -> Result<usize, ProgramError> {
+    if *token_program.key == spl_token::id() {
+        return Ok(TokenAccount::LEN);
+    }
+    invoke(
+        &Instruction {
+            program_id: *token_program.key,
+            accounts: vec![AccountMeta::new_readonly(*mint_info.key, false)],
+            data: vec![INSTRUCTION_GET_ACCOUNT_DATA_SIZE],
+        },
+        &[mint_info.clone(), token_program.clone()],
+    )?;
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *token_program.key => data
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(|bytes| u64::from_le_bytes(bytes) as usize)
+            .ok_or(ProgramError::InvalidAccountData),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Transfers with `transfer_checked`, signing with `signer_seeds` when the authority is a PDA.
+/// Returns the amount credited to `destination`, which is lower than `amount` for mints with
+/// a transfer fee.
+pub fn transfer_checked<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64, ProgramError> {
+    let decimals = unpack_mint(mint)?.decimals;
+    let destination_amount_before = unpack_token_account(destination)?.amount;
+    invoke_signed(
+        &instruction_for(
+            token_program.key,
+            spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                source.key,
+                mint.key,
+                destination.key,
+                authority.key,
+                &[],
+                amount,
+                decimals,
+            ),
+        )?,
+        &[
+            source.clone(),
+            mint.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )?;
+    let destination_amount_after = unpack_token_account(destination)?.amount;
+    destination_amount_after
+        .checked_sub(destination_amount_before)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+/// Moves the transfer fees withheld in `accounts` to the mint, Token-2022 refusing to close
+/// token accounts that still hold withheld fees
+pub fn harvest_withheld_tokens_to_mint<'a>(
+    token_program: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    accounts: &[&AccountInfo<'a>],
+) -> ProgramResult {
+    let mut account_metas = vec![AccountMeta::new(*mint.key, false)];
+    let mut account_infos = vec![mint.clone()];
+    for account in accounts {
+        account_metas.push(AccountMeta::new(*account.key, false));
+        account_infos.push((*account).clone());
+    }
+    account_infos.push(token_program.clone());
+    invoke(
+        &Instruction {
+            program_id: *token_program.key,
+            accounts: account_metas,
+            data: vec![
+                INSTRUCTION_TRANSFER_FEE_EXTENSION,
+                TRANSFER_FEE_INSTRUCTION_HARVEST_WITHHELD_TOKENS_TO_MINT,
+            ],
+        },
+        &account_infos,
+    )
+}
@@ -2,14 +2,35 @@ use std::cell::RefMut;
 use std::convert::TryInto;
 
 use crate::error::CustomError;
+use crate::state::{User, YourPool};
 use solana_program::account_info::AccountInfo;
+use solana_program::msg;
 use solana_program::program_error::ProgramError;
 
-// to avoid rounding errors
-const PRECISION: u128 = u64::MAX as u128;
+// Reward per token is a 64.64 fixed-point number, which keeps the accumulators written with
+// the former u64::MAX scaling valid
+const PRECISION_BITS: u32 = 64;
+const PRECISION: u128 = 1u128 << PRECISION_BITS;
 
 pub mod constants {
     pub const MIN_DURATION: u64 = 86400; // 1 day
+    pub const STAKING_VAULT_SEED: &[u8] = b"staking";
+    pub const REWARDS_VAULT_SEED: &[u8] = b"rewards";
+    pub const RECEIPT_MINT_SEED: &[u8] = b"receipt";
+    pub const NFT_ESCROW_SEED: &[u8] = b"nft_escrow";
+    pub const NFT_STAKE_SEED: &[u8] = b"nft_stake";
+    pub const NFT_WEIGHT_SEED: &[u8] = b"nft_weight";
+    pub const NFT_BASE_WEIGHT: u64 = 10_000; // weights are in basis points, 1x
+    pub const BPS_DENOMINATOR: u64 = 10_000;
+    pub const MAX_REWARD_FEE_BPS: u16 = 2_000; // 20% of each claim
+
+    // The rewards of one period, reward_rate * reward_duration, must also fit in u64, so the
+    // highest supported reward rate is u64::MAX / reward_duration. Reward per token then stays
+    // within u128 for the whole period, even with a single base unit staked.
+    pub const MIN_REWARD_DURATION: u64 = MIN_DURATION;
+    pub const MAX_REWARD_DURATION: u64 = 10 * 365 * 86400; // 10 years
+    pub const SECONDS_PER_DAY: u64 = 86400;
+    pub const SECONDS_PER_YEAR: u64 = 365 * SECONDS_PER_DAY;
 }
 
 pub fn close_account(
@@ -26,6 +47,29 @@ pub fn close_account(
     Ok(())
 }
 
+/// `value * PRECISION / divisor` rounded down, without the u128 overflow of the product
+fn mul_precision_div(value: u128, divisor: u64) -> Option<u128> {
+    let divisor = divisor as u128;
+    let quotient = value / divisor;
+    if quotient >> (128 - PRECISION_BITS) != 0 {
+        return None;
+    }
+    // The remainder is below a u64 divisor, so shifting it by 64 bits fits in u128
+    let remainder = ((value % divisor) << PRECISION_BITS) / divisor;
+    (quotient << PRECISION_BITS).checked_add(remainder)
+}
+
+/// `value * scaled / PRECISION` rounded down, without the u128 overflow of the product
+fn mul_div_precision(value: u64, scaled: u128) -> Option<u128> {
+    let value = value as u128;
+    let high = value.checked_mul(scaled >> PRECISION_BITS)?;
+    let low = (value * (scaled & (PRECISION - 1))) >> PRECISION_BITS;
+    high.checked_add(low)
+}
+
+/// Adds the rewards accrued per staked token since the last update to
+/// `your_reward_per_token_stored`. Accruals of up to `u64::MAX` never overflow, whatever the
+/// amount staked.
 pub fn rewards_per_token(
     total_your_staked: u64,
     last_time_reward_applicable: u64,
@@ -36,26 +80,23 @@ pub fn rewards_per_token(
     if total_your_staked == 0 {
         return Ok(your_reward_per_token_stored);
     }
-    let new_reward_per_token_stored: u128 = (your_reward_rate as u128)
+    let new_rewards: u128 = (your_reward_rate as u128)
         .checked_mul(
             (last_time_reward_applicable as u128)
                 .checked_sub(total_stake_last_update_time as u128)
                 .ok_or(CustomError::AmountOverflow)?,
         )
         .ok_or(CustomError::AmountOverflow)?;
-    let new_reward_per_token_stored_with_precision: u128 = new_reward_per_token_stored
-        .checked_mul(PRECISION)
-        .ok_or(CustomError::AmountOverflow)?;
     let updated_rewards_per_token_stored = your_reward_per_token_stored
         .checked_add(
-            new_reward_per_token_stored_with_precision
-                .checked_div(total_your_staked as u128)
-                .ok_or(CustomError::AmountOverflow)?,
+            mul_precision_div(new_rewards, total_your_staked).ok_or(CustomError::AmountOverflow)?,
         )
         .ok_or(CustomError::AmountOverflow)?;
-    return Ok(updated_rewards_per_token_stored);
+    Ok(updated_rewards_per_token_stored)
 }
 
+/// Adds the rewards of `balance_your_staked` since `reward_per_token_complete` to
+/// `reward_per_token_pending`, rounded down so the pool never pays out more than it accrued
 pub fn earned(
     balance_your_staked: u64,
     reward_per_token_stored: u128,
@@ -65,18 +106,342 @@ pub fn earned(
     let diff_reward_per_token = reward_per_token_stored
         .checked_sub(reward_per_token_complete)
         .ok_or(CustomError::AmountOverflow)?;
-    let mul = ((balance_your_staked as u128)
-        .checked_mul(diff_reward_per_token)
-        .ok_or(CustomError::AmountOverflow)?)
-    .checked_div(PRECISION)
-    .ok_or(CustomError::AmountOverflow)? as u64;
+    let rewards: u64 = mul_div_precision(balance_your_staked, diff_reward_per_token)
+        .and_then(|rewards| rewards.try_into().ok())
+        .ok_or(CustomError::AmountOverflow)?;
     let updated_reward_per_token_pending = reward_per_token_pending
-        .checked_add(mul)
+        .checked_add(rewards)
         .ok_or(CustomError::AmountOverflow)?;
-    return Ok(updated_reward_per_token_pending);
+    Ok(updated_reward_per_token_pending)
 }
 
 pub fn last_time_reward_applicable(reward_duration_end: u64, now_unix_timestamp: i64) -> u64 {
-    return std::cmp::min(now_unix_timestamp.try_into().unwrap(), reward_duration_end);
+    std::cmp::min(now_unix_timestamp.max(0) as u64, reward_duration_end)
+}
+
+/// Accrues the pool rewards up to `now` and settles them into `user` when given.
+/// V1 accounts do not track rewards and are left untouched.
+pub fn update_rewards(
+    your_pool_data: &mut YourPool,
+    user_storage_data: Option<&mut User>,
+    now_unix_timestamp: i64,
+) -> Result<(), ProgramError> {
+    if your_pool_data.is_v1() {
+        return Ok(());
+    }
+    let last_time_reward_applicable =
+        last_time_reward_applicable(your_pool_data.reward_duration_end, now_unix_timestamp);
+    your_pool_data.reward_per_token_stored = rewards_per_token(
+        your_pool_data.total_your_staked,
+        last_time_reward_applicable,
+        your_pool_data.last_update_time,
+        your_pool_data.your_reward_rate,
+        your_pool_data.reward_per_token_stored,
+    )?;
+    // Stakers are owed at most what was emitted while someone was staked, rounding down their
+    // shares only leaves dust in the vault
+    if your_pool_data.total_your_staked != 0u64 {
+        let rewards_accrued = last_time_reward_applicable
+            .checked_sub(your_pool_data.last_update_time)
+            .and_then(|elapsed| elapsed.checked_mul(your_pool_data.your_reward_rate))
+            .ok_or(CustomError::AmountOverflow)?;
+        your_pool_data.rewards_owed = your_pool_data
+            .rewards_owed
+            .checked_add(rewards_accrued)
+            .ok_or(CustomError::AmountOverflow)?;
+    }
+    your_pool_data.last_update_time = last_time_reward_applicable;
+
+    if let Some(user_storage_data) = user_storage_data {
+        let reward_per_token_pending = earned(
+            user_storage_data.balance_your_staked,
+            your_pool_data.reward_per_token_stored,
+            user_storage_data.reward_per_token_complete,
+            user_storage_data.reward_per_token_pending,
+        )?;
+        if user_storage_data.has_referrer() {
+            let rewards_earned = reward_per_token_pending
+                .checked_sub(user_storage_data.reward_per_token_pending)
+                .ok_or(CustomError::AmountOverflow)?;
+            settle_referral_rewards(your_pool_data, user_storage_data, rewards_earned)?;
+        }
+        user_storage_data.reward_per_token_pending = reward_per_token_pending;
+        user_storage_data.reward_per_token_complete = your_pool_data.reward_per_token_stored;
+    }
+    Ok(())
+}
+
+/// Rewards a claim by `user` would settle at `now`, before the reward fee and any vesting.
+/// Nothing is written: the pool and the user are updated on copies. V1 accounts report 0.
+pub fn pending_rewards(
+    your_pool_data: &YourPool,
+    user_storage_data: &User,
+    now_unix_timestamp: i64,
+) -> Result<u64, ProgramError> {
+    let mut your_pool_data = *your_pool_data;
+    let mut user_storage_data = *user_storage_data;
+    update_rewards(
+        &mut your_pool_data,
+        Some(&mut user_storage_data),
+        now_unix_timestamp,
+    )?;
+    Ok(user_storage_data.reward_per_token_pending)
+}
+
+/// Prices of one whole token of each pool mint in a common quote unit, e.g. micro-USD, with the
+/// mint decimals to value base units
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenPrices {
+    pub staking_price: u64,
+    pub staking_decimals: u8,
+    pub rewards_price: u64,
+    pub rewards_decimals: u8,
+}
+
+/// APR and APY of a deposit in basis points. The current rates assume the reward rate goes on
+/// for a year, the projected ones stop the rewards at the end of the reward period.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RewardProjection {
+    pub current_apr_bps: u64,
+    pub current_apy_bps: u64,
+    pub projected_apr_bps: u64,
+    pub projected_apy_bps: u64,
+}
+
+impl RewardProjection {
+    pub const LEN: usize = 32;
+
+    /// Little-endian u64s in field order, the return data of QueryRewardProjection
+    pub fn pack(&self) -> [u8; RewardProjection::LEN] {
+        let mut data = [0u8; RewardProjection::LEN];
+        data[..8].copy_from_slice(&self.current_apr_bps.to_le_bytes());
+        data[8..16].copy_from_slice(&self.current_apy_bps.to_le_bytes());
+        data[16..24].copy_from_slice(&self.projected_apr_bps.to_le_bytes());
+        data[24..].copy_from_slice(&self.projected_apy_bps.to_le_bytes());
+        data
+    }
+
+    pub fn unpack(data: &[u8]) -> Result<RewardProjection, ProgramError> {
+        let field = |index: usize| {
+            data.get(index * 8..index * 8 + 8)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u64::from_le_bytes)
+                .ok_or(ProgramError::InvalidAccountData)
+        };
+        Ok(RewardProjection {
+            current_apr_bps: field(0)?,
+            current_apy_bps: field(1)?,
+            projected_apr_bps: field(2)?,
+            projected_apy_bps: field(3)?,
+        })
+    }
+}
+
+/// Projects the rewards of a `deposit` joining the pool stake at `now`. Without prices, reward
+/// and staking base units are valued the same, which holds for pools paying rewards in their
+/// staking mint; NFT pools count the stake in weight. APY compounds the rewards daily, and
+/// rates that do not fit the u128 math saturate at `u64::MAX`.
+pub fn reward_projection(
+    your_pool_data: &YourPool,
+    deposit: u64,
+    prices: Option<TokenPrices>,
+    now_unix_timestamp: i64,
+) -> Result<RewardProjection, ProgramError> {
+    // V1 pools pay a fixed legacy amount instead of a reward rate
+    if your_pool_data.is_v1() {
+        return Err(CustomError::PoolModeMismatched.into());
+    }
+    let remaining_duration = your_pool_data
+        .reward_duration_end
+        .saturating_sub(now_unix_timestamp.max(0) as u64);
+    if remaining_duration == 0 {
+        return Ok(RewardProjection::default());
+    }
+
+    // Yearly rewards of a staked base unit, sharing the rate with the stake after the deposit
+    let mut numerator = your_pool_data.your_reward_rate as u128
+        * constants::SECONDS_PER_YEAR as u128
+        * constants::BPS_DENOMINATOR as u128;
+    let mut denominator = std::cmp::max(
+        your_pool_data.total_your_staked as u128 + deposit as u128,
+        1u128,
+    );
+    if let Some(prices) = prices {
+        let decimals_scale = |decimals: u8, other_decimals: u8| {
+            10u128.checked_pow(decimals.saturating_sub(other_decimals) as u32)
+        };
+        numerator = decimals_scale(prices.staking_decimals, prices.rewards_decimals)
+            .and_then(|scale| numerator.checked_mul(scale))
+            .and_then(|numerator| numerator.checked_mul(prices.rewards_price as u128))
+            .unwrap_or(u128::MAX);
+        denominator = match decimals_scale(prices.rewards_decimals, prices.staking_decimals)
+            .and_then(|scale| denominator.checked_mul(scale))
+            .and_then(|denominator| denominator.checked_mul(prices.staking_price as u128))
+        {
+            // A denominator past u128 leaves less than one basis point
+            Some(0) | None => return Ok(RewardProjection::default()),
+            Some(denominator) => denominator,
+        };
+    }
+    let current_apr_bps = (numerator / denominator).try_into().unwrap_or(u64::MAX);
+
+    let projected_duration = std::cmp::min(remaining_duration, constants::SECONDS_PER_YEAR);
+    Ok(RewardProjection {
+        current_apr_bps,
+        current_apy_bps: compounded_bps(current_apr_bps, constants::SECONDS_PER_YEAR),
+        projected_apr_bps: (current_apr_bps as u128 * projected_duration as u128
+            / constants::SECONDS_PER_YEAR as u128) as u64,
+        projected_apy_bps: compounded_bps(current_apr_bps, projected_duration),
+    })
+}
+
+/// Growth in basis points of a stake whose rewards at `apr_bps` are restaked daily for
+/// `duration` seconds, the last partial day earning simple interest
+fn compounded_bps(apr_bps: u64, duration: u64) -> u64 {
+    const SCALE: u128 = 1_000_000_000_000;
+    let mul = |a: u128, b: u128| a.checked_mul(b).map(|product| product / SCALE);
+    let daily_rate = apr_bps as u128 * SCALE / (constants::BPS_DENOMINATOR as u128 * 365);
+
+    let mut growth = Some(SCALE);
+    let mut daily_growth = Some(SCALE + daily_rate);
+    let mut days = duration / constants::SECONDS_PER_DAY;
+    while days > 0 {
+        if days & 1 == 1 {
+            growth = growth.zip(daily_growth).and_then(|(a, b)| mul(a, b));
+        }
+        days >>= 1;
+        if days > 0 {
+            daily_growth = daily_growth.and_then(|b| mul(b, b));
+        }
+    }
+    let partial_day_growth = SCALE
+        + daily_rate * (duration % constants::SECONDS_PER_DAY) as u128
+            / constants::SECONDS_PER_DAY as u128;
+    growth
+        .and_then(|growth| mul(growth, partial_day_growth))
+        .and_then(|growth| (growth - SCALE).checked_mul(constants::BPS_DENOMINATOR as u128))
+        .and_then(|growth_bps| (growth_bps / SCALE).try_into().ok())
+        .unwrap_or(u64::MAX)
+}
+
+/// Records `amount` of owed rewards leaving the rewards vault. Pools created before the
+/// ledger existed start it at zero, so it floors at zero instead of failing the payout.
+pub fn pay_out_rewards(your_pool_data: &mut YourPool, amount: u64) {
+    your_pool_data.rewards_owed = your_pool_data.rewards_owed.saturating_sub(amount);
+}
+
+/// Rewards the rewards vault has to hold: the rewards owed to users and referrers and the
+/// referral budget not handed out yet
+pub fn outstanding_rewards(your_pool_data: &YourPool) -> Result<u64, ProgramError> {
+    your_pool_data
+        .rewards_owed
+        .checked_add(your_pool_data.referral_budget)
+        .ok_or_else(|| CustomError::AmountOverflow.into())
+}
+
+/// Checks the vaults against the pool ledger: the staking vault has to hold every staked
+/// token, pending unstakes included, and the rewards vault the outstanding rewards. NFT pools
+/// keep their NFTs in escrow accounts, so only their rewards vault is checked. The pool should
+/// be updated to the current time first.
+pub fn verify_pool_solvency(
+    your_pool_data: &YourPool,
+    staking_vault_amount: u64,
+    rewards_vault_amount: u64,
+) -> Result<(), ProgramError> {
+    if !your_pool_data.is_nft_pool() && staking_vault_amount < your_pool_data.total_your_staked {
+        msg!("CustomError::StakingVaultInsolvent");
+        return Err(CustomError::StakingVaultInsolvent.into());
+    }
+    if rewards_vault_amount < outstanding_rewards(your_pool_data)? {
+        msg!("CustomError::RewardsVaultInsolvent");
+        return Err(CustomError::RewardsVaultInsolvent.into());
+    }
+    Ok(())
+}
+
+/// Sets aside the referrer's share of `rewards_earned` by the user. The share comes out of
+/// the pool referral budget, never out of the user's own rewards, and stops once it is spent.
+fn settle_referral_rewards(
+    your_pool_data: &mut YourPool,
+    user_storage_data: &mut User,
+    rewards_earned: u64,
+) -> Result<(), ProgramError> {
+    let referral_rewards = ((rewards_earned as u128)
+        .checked_mul(your_pool_data.referral_bps as u128)
+        .ok_or(CustomError::AmountOverflow)?
+        / constants::BPS_DENOMINATOR as u128) as u64;
+    let referral_rewards = std::cmp::min(referral_rewards, your_pool_data.referral_budget);
+    your_pool_data.referral_budget -= referral_rewards;
+    your_pool_data.rewards_owed = your_pool_data
+        .rewards_owed
+        .checked_add(referral_rewards)
+        .ok_or(CustomError::AmountOverflow)?;
+    user_storage_data.referral_rewards_owed = user_storage_data
+        .referral_rewards_owed
+        .checked_add(referral_rewards)
+        .ok_or(CustomError::AmountOverflow)?;
+    Ok(())
+}
+
+/// Part of the user's current vesting schedule released at `now`, withdrawn or not
+fn vested_in_schedule(user_storage_data: &User, now: i64) -> Result<u64, ProgramError> {
+    if now >= user_storage_data.vesting_end {
+        return Ok(user_storage_data.vesting_total);
+    }
+    if now <= user_storage_data.vesting_start {
+        return Ok(0u64);
+    }
+    let elapsed = (now as i128 - user_storage_data.vesting_start as i128) as u128;
+    let duration =
+        (user_storage_data.vesting_end as i128 - user_storage_data.vesting_start as i128) as u128;
+    Ok(((user_storage_data.vesting_total as u128)
+        .checked_mul(elapsed)
+        .ok_or(CustomError::AmountOverflow)?
+        / duration) as u64)
+}
+
+/// Vested rewards the user can withdraw at `now`
+pub fn withdrawable_vested(user_storage_data: &User, now: i64) -> Result<u64, ProgramError> {
+    vested_in_schedule(user_storage_data, now)?
+        .checked_sub(user_storage_data.vesting_withdrawn)
+        .and_then(|vested| vested.checked_add(user_storage_data.vesting_unlocked))
+        .ok_or_else(|| CustomError::AmountOverflow.into())
 }
 
+/// Locks `amount` of claimed rewards for `vesting_duration` seconds. What the current schedule
+/// already released stays withdrawable and its locked remainder is vested again with `amount`.
+pub fn add_vesting(
+    user_storage_data: &mut User,
+    amount: u64,
+    vesting_duration: u64,
+    now: i64,
+) -> Result<(), ProgramError> {
+    let vested = vested_in_schedule(user_storage_data, now)?;
+    user_storage_data.vesting_unlocked = withdrawable_vested(user_storage_data, now)?;
+    user_storage_data.vesting_total = (user_storage_data.vesting_total - vested)
+        .checked_add(amount)
+        .ok_or(CustomError::AmountOverflow)?;
+    user_storage_data.vesting_withdrawn = 0u64;
+    user_storage_data.vesting_start = now;
+    user_storage_data.vesting_end = now
+        .checked_add(vesting_duration as i64)
+        .ok_or(CustomError::AmountOverflow)?;
+    Ok(())
+}
+
+/// Marks everything released so far as withdrawn and returns its amount
+pub fn withdraw_vested(user_storage_data: &mut User, now: i64) -> Result<u64, ProgramError> {
+    let amount = withdrawable_vested(user_storage_data, now)?;
+    user_storage_data.vesting_withdrawn = vested_in_schedule(user_storage_data, now)?;
+    user_storage_data.vesting_unlocked = 0u64;
+    Ok(amount)
+}
+
+/// Claimed rewards the user has not withdrawn yet, locked or not
+pub fn vesting_balance(user_storage_data: &User) -> Result<u64, ProgramError> {
+    user_storage_data
+        .vesting_total
+        .checked_sub(user_storage_data.vesting_withdrawn)
+        .map(|locked| locked.saturating_add(user_storage_data.vesting_unlocked))
+        .ok_or_else(|| CustomError::AmountOverflow.into())
+}
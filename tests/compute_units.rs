@@ -0,0 +1,989 @@
+#![cfg(feature = "test-bpf")]
+//! Compute unit budgets of the instructions, run against the BPF build with `cargo test-bpf`.
+//! Each test writes the accounts its instruction reads straight into the bank, so the budget
+//! meters that single instruction and nothing spent on setting it up. A test failing with
+//! `ComputationalBudgetExceeded` means the instruction got more expensive than its budget.
+
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use your_staking::{
+    instruction::Instruction as StakingInstruction,
+    metadata::{get_metadata_address, mpl_token_metadata},
+    processor::{
+        create_user::get_user_storage_address,
+        initialize_pool::{get_pool_storage_address, get_vault_address},
+        set_nft_weight::get_nft_record_address,
+    },
+    state::{
+        AccTypesWithVersion, NftStake, User, UserV1, YourPool, YourPoolV1,
+        NFT_STAKE_STORAGE_TOTAL_BYTES, USER_STORAGE_TOTAL_BYTES, YOUR_POOL_STORAGE_TOTAL_BYTES,
+    },
+    utils::constants::{
+        NFT_BASE_WEIGHT, NFT_ESCROW_SEED, NFT_STAKE_SEED, NFT_WEIGHT_SEED, REWARDS_VAULT_SEED,
+        STAKING_VAULT_SEED,
+    },
+};
+
+const REWARD_DURATION: u64 = 7 * 86400;
+const FUND_AMOUNT: u64 = 604_800_000_000;
+const STAKE_AMOUNT: u64 = 1_000_000_000;
+const LAMPORTS: u64 = 10_000_000_000;
+
+// Budgets of the instructions, in compute units. They are estimates still to be measured
+const INITIALIZE_POOL_BUDGET: u64 = 80_000;
+const CREATE_USER_BUDGET: u64 = 25_000;
+const STAKE_BUDGET: u64 = 30_000;
+const UNSTAKE_BUDGET: u64 = 20_000;
+const CLAIM_REWARDS_BUDGET: u64 = 35_000;
+const CLOSE_POOL_BUDGET: u64 = 50_000;
+const CLOSE_USER_BUDGET: u64 = 20_000;
+const FINAL_UNSTAKE_BUDGET: u64 = 30_000;
+const MIGRATE_POOL_BUDGET: u64 = 60_000;
+const MIGRATE_USER_BUDGET: u64 = 25_000;
+const TRANSFER_POSITION_BUDGET: u64 = 35_000;
+const MIGRATE_STAKE_BUDGET: u64 = 40_000;
+const STAKE_NFT_BUDGET: u64 = 80_000;
+const UNSTAKE_NFT_BUDGET: u64 = 40_000;
+const SET_NFT_WEIGHT_BUDGET: u64 = 25_000;
+const CONFIGURE_REFERRALS_BUDGET: u64 = 25_000;
+const CLAIM_REFERRAL_REWARDS_BUDGET: u64 = 35_000;
+const SET_FEE_BUDGET: u64 = 10_000;
+const SET_VESTING_DURATION_BUDGET: u64 = 10_000;
+const WITHDRAW_VESTED_BUDGET: u64 = 30_000;
+const VERIFY_POOL_BUDGET: u64 = 15_000;
+const QUERY_PENDING_REWARDS_BUDGET: u64 = 10_000;
+const QUERY_REWARD_PROJECTION_BUDGET: u64 = 40_000;
+const SET_PAUSED_BUDGET: u64 = 10_000;
+const FUND_POOL_BUDGET: u64 = 30_000;
+
+struct Bench {
+    context: ProgramTestContext,
+    program_id: Pubkey,
+    now: i64,
+    owner: Keypair,
+    staking_mint: Pubkey,
+    rewards_mint: Pubkey,
+    pool_storage: Pubkey,
+    staking_vault: Pubkey,
+    rewards_vault: Pubkey,
+    pool_signer: Pubkey,
+    pool_data: YourPool,
+}
+
+struct BenchUser {
+    wallet: Keypair,
+    storage: Pubkey,
+    staking_account: Pubkey,
+    rewards_account: Pubkey,
+    user_data: User,
+}
+
+impl Bench {
+    /// Starts a bank metering the program at `budget` units, with both mints and an owner
+    /// wallet but no pool yet
+    async fn start(budget: u64) -> Bench {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("your_staking", program_id, None);
+        program_test.set_bpf_compute_max_units(budget);
+        let mut context = program_test.start_with_context().await;
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+
+        let owner = Keypair::new();
+        let staking_mint = Pubkey::new_unique();
+        let rewards_mint = Pubkey::new_unique();
+        let pool_storage = get_pool_storage_address(&owner.pubkey(), &staking_mint, 0, &program_id);
+        let mut bench = Bench {
+            program_id,
+            now: clock.unix_timestamp,
+            staking_vault: get_vault_address(&pool_storage, STAKING_VAULT_SEED, &program_id),
+            rewards_vault: get_vault_address(&pool_storage, REWARDS_VAULT_SEED, &program_id),
+            pool_signer: Pubkey::find_program_address(&[&pool_storage.to_bytes()], &program_id).0,
+            pool_data: pool_data(&owner.pubkey(), &staking_mint, &rewards_mint, &program_id),
+            context,
+            owner,
+            staking_mint,
+            rewards_mint,
+            pool_storage,
+        };
+        bench.set_wallet(&bench.owner.pubkey());
+        bench.set_mint(&staking_mint);
+        bench.set_mint(&rewards_mint);
+        bench
+    }
+
+    /// Starts a bank holding a pool halfway through its reward period, with its vaults
+    async fn start_with_pool(budget: u64) -> Bench {
+        let mut bench = Bench::start(budget).await;
+        bench.pool_data.last_update_time = (bench.now - 100) as u64;
+        bench.pool_data.reward_duration_end = bench.now as u64 + REWARD_DURATION / 2;
+        bench.write_pool();
+        let (staking_vault, staking_mint, pool_signer) =
+            (bench.staking_vault, bench.staking_mint, bench.pool_signer);
+        bench.set_token_account(&staking_vault, &staking_mint, &pool_signer, 0);
+        let (rewards_vault, rewards_mint) = (bench.rewards_vault, bench.rewards_mint);
+        bench.set_token_account(&rewards_vault, &rewards_mint, &pool_signer, FUND_AMOUNT);
+        bench
+    }
+
+    fn set_account(&mut self, address: &Pubkey, lamports: u64, data: Vec<u8>, owner: &Pubkey) {
+        let account = Account {
+            lamports,
+            data,
+            owner: *owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.context
+            .set_account(address, &AccountSharedData::from(account));
+    }
+
+    fn set_wallet(&mut self, wallet: &Pubkey) {
+        self.set_account(wallet, LAMPORTS, vec![], &system_program::id());
+    }
+
+    fn set_mint(&mut self, mint: &Pubkey) {
+        self.set_mint_with_supply(mint, u64::MAX / 2, 9);
+    }
+
+    fn set_mint_with_supply(&mut self, mint: &Pubkey, supply: u64, decimals: u8) {
+        let mut data = vec![0u8; Mint::LEN];
+        Mint {
+            mint_authority: COption::None,
+            supply,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(mint, LAMPORTS, data, &spl_token::id());
+    }
+
+    /// Turns the pool into an NFT pool and adds an NFT of its verified collection
+    fn add_nft(&mut self) -> Pubkey {
+        let collection = Pubkey::new_unique();
+        self.pool_data.nft_collection = collection;
+        self.write_pool();
+        let nft_mint = Pubkey::new_unique();
+        self.set_mint_with_supply(&nft_mint, 1, 0);
+
+        // Metaplex metadata up to its collection field
+        let mut data = vec![4u8]; // Key::MetadataV1
+        data.extend_from_slice(&Pubkey::new_unique().to_bytes()); // update_authority
+        data.extend_from_slice(&nft_mint.to_bytes());
+        data.extend_from_slice(&[0u8; 12]); // empty name, symbol and uri
+        data.extend_from_slice(&[0u8; 2]); // seller_fee_basis_points
+        data.push(0u8); // no creators
+        data.extend_from_slice(&[0u8, 1u8]); // primary_sale_happened, is_mutable
+        data.extend_from_slice(&[0u8, 0u8]); // no edition_nonce and token_standard
+        data.extend_from_slice(&[1u8, 1u8]); // verified collection
+        data.extend_from_slice(&collection.to_bytes());
+        self.set_account(
+            &get_metadata_address(&nft_mint),
+            LAMPORTS,
+            data,
+            &mpl_token_metadata::id(),
+        );
+        nft_mint
+    }
+
+    fn nft_record(&self, nft_mint: &Pubkey, record_seed: &[u8]) -> Pubkey {
+        get_nft_record_address(&self.pool_storage, nft_mint, record_seed, &self.program_id)
+    }
+
+    fn set_token_account(&mut self, address: &Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64) {
+        let mut data = vec![0u8; TokenAccount::LEN];
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(address, LAMPORTS, data, &spl_token::id());
+    }
+
+    fn new_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let address = Pubkey::new_unique();
+        self.set_token_account(&address, mint, owner, amount);
+        address
+    }
+
+    fn write_pool(&mut self) {
+        let mut data = vec![0u8; YOUR_POOL_STORAGE_TOTAL_BYTES];
+        self.pool_data.pack_versioned(&mut data).unwrap();
+        let (pool_storage, program_id) = (self.pool_storage, self.program_id);
+        self.set_account(&pool_storage, LAMPORTS, data, &program_id);
+    }
+
+    fn write_user(&mut self, user: &BenchUser) {
+        let mut data = vec![0u8; USER_STORAGE_TOTAL_BYTES];
+        user.user_data.pack_versioned(&mut data).unwrap();
+        let program_id = self.program_id;
+        self.set_account(&user.storage, LAMPORTS, data, &program_id);
+    }
+
+    /// Adds a user with `balance` staked, `configure` adjusting its account before it is written
+    fn add_user(&mut self, balance: u64, configure: impl FnOnce(&mut User)) -> BenchUser {
+        let wallet = Keypair::new();
+        self.set_wallet(&wallet.pubkey());
+        let (staking_mint, rewards_mint) = (self.staking_mint, self.rewards_mint);
+        let mut user = BenchUser {
+            storage: get_user_storage_address(
+                &wallet.pubkey(),
+                &self.pool_storage,
+                &self.program_id,
+            ),
+            staking_account: self.new_token_account(&staking_mint, &wallet.pubkey(), STAKE_AMOUNT),
+            rewards_account: self.new_token_account(&rewards_mint, &wallet.pubkey(), 0),
+            user_data: user_data(&wallet.pubkey(), &self.pool_storage),
+            wallet,
+        };
+        user.user_data.balance_your_staked = balance;
+        user.user_data.reward_per_token_complete = self.pool_data.reward_per_token_stored;
+        configure(&mut user.user_data);
+        self.write_user(&user);
+
+        self.pool_data.user_stake_count += 1;
+        self.pool_data.total_your_staked += balance;
+        self.write_pool();
+        let (staking_vault, pool_signer) = (self.staking_vault, self.pool_signer);
+        let total_your_staked = self.pool_data.total_your_staked;
+        self.set_token_account(
+            &staking_vault,
+            &staking_mint,
+            &pool_signer,
+            total_your_staked,
+        );
+        user
+    }
+
+    /// Runs `instruction` alone in a transaction, failing when it exceeds the budget
+    async fn process(&mut self, instruction: Instruction, signers: &[&Keypair]) {
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    fn instruction(
+        &self,
+        instruction: StakingInstruction,
+        accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: instruction.pack(),
+        }
+    }
+}
+
+fn pool_data(
+    owner: &Pubkey,
+    staking_mint: &Pubkey,
+    rewards_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> YourPool {
+    let pool_storage = get_pool_storage_address(owner, staking_mint, 0, program_id);
+    YourPool {
+        acc_type: AccTypesWithVersion::YourPoolDataV2 as u8,
+        owner_wallet: *owner,
+        your_staking_vault: get_vault_address(&pool_storage, STAKING_VAULT_SEED, program_id),
+        your_reward_rate: FUND_AMOUNT / REWARD_DURATION,
+        your_epoch_duration: REWARD_DURATION,
+        user_stake_count: 0,
+        pda_nonce: 0,
+        reward_duration_end: 0,
+        your_staking_mint: *staking_mint,
+        your_rewards_vault: get_vault_address(&pool_storage, REWARDS_VAULT_SEED, program_id),
+        your_rewards_mint: *rewards_mint,
+        total_your_staked: 0,
+        migrated_from: Pubkey::default(),
+        pool_index: 0,
+        reward_per_token_stored: 1 << 64,
+        last_update_time: 0,
+        receipt_mint: Pubkey::default(),
+        nft_collection: Pubkey::default(),
+        referral_bps: 0,
+        referral_budget: 0,
+        reward_fee_bps: 0,
+        fee_recipient: Pubkey::default(),
+        vesting_duration: 0,
+        rewards_owed: FUND_AMOUNT / 2,
+        paused: 0,
+    }
+}
+
+fn user_data(wallet: &Pubkey, pool_storage: &Pubkey) -> User {
+    User {
+        acc_type: AccTypesWithVersion::UserDataV2 as u8,
+        user_wallet: *wallet,
+        your_pool: *pool_storage,
+        balance_your_staked: 0,
+        unstake_pending: 0,
+        unstake_pending_date: 0,
+        nonce: 0,
+        claim_timeout_date: 0,
+        reward_per_token_complete: 0,
+        reward_per_token_pending: 0,
+        referrer: Pubkey::default(),
+        referral_rewards_owed: 0,
+        vesting_total: 0,
+        vesting_withdrawn: 0,
+        vesting_start: 0,
+        vesting_end: 0,
+        vesting_unlocked: 0,
+    }
+}
+
+#[tokio::test]
+async fn initialize_pool_compute_units() {
+    let mut bench = Bench::start(INITIALIZE_POOL_BUDGET).await;
+    let (rewards_mint, owner) = (bench.rewards_mint, bench.owner.pubkey());
+    let owner_rewards_account = bench.new_token_account(&rewards_mint, &owner, FUND_AMOUNT);
+    let instruction = bench.instruction(
+        StakingInstruction::InitializePool {
+            reward_duration: REWARD_DURATION,
+            pool_nonce: 0,
+            fund_amount: FUND_AMOUNT,
+            pool_index: 0,
+            with_receipt_mint: false,
+            nft_pool: false,
+        },
+        vec![
+            AccountMeta::new(owner, true),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.staking_mint, false),
+            AccountMeta::new(bench.staking_vault, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(owner_rewards_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn create_user_compute_units() {
+    let mut bench = Bench::start_with_pool(CREATE_USER_BUDGET).await;
+    let wallet = Keypair::new();
+    bench.set_wallet(&wallet.pubkey());
+    let storage =
+        get_user_storage_address(&wallet.pubkey(), &bench.pool_storage, &bench.program_id);
+    let instruction = bench.instruction(
+        StakingInstruction::CreateUser {
+            nonce: 0,
+            referrer: None,
+        },
+        vec![
+            AccountMeta::new(wallet.pubkey(), true),
+            AccountMeta::new(storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&wallet]).await;
+}
+
+#[tokio::test]
+async fn stake_compute_units() {
+    let mut bench = Bench::start_with_pool(STAKE_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |_| {});
+    let instruction = bench.instruction(
+        StakingInstruction::Stake {
+            amount_to_deposit: STAKE_AMOUNT,
+        },
+        vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.staking_vault, false),
+            AccountMeta::new(user.staking_account, false),
+            AccountMeta::new_readonly(bench.staking_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn unstake_compute_units() {
+    let mut bench = Bench::start_with_pool(UNSTAKE_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |_| {});
+    let instruction = bench.instruction(
+        StakingInstruction::Unstake {
+            amount_to_withdraw: STAKE_AMOUNT,
+        },
+        vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new_readonly(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.staking_vault, false),
+            AccountMeta::new_readonly(user.staking_account, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn final_unstake_compute_units() {
+    let mut bench = Bench::start_with_pool(FINAL_UNSTAKE_BUDGET).await;
+    let unstake_pending_date = bench.now - 1;
+    let user = bench.add_user(STAKE_AMOUNT, |user_data| {
+        user_data.unstake_pending = STAKE_AMOUNT;
+        user_data.unstake_pending_date = unstake_pending_date;
+    });
+    let instruction = bench.instruction(
+        StakingInstruction::FinalUnstake {},
+        vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.staking_vault, false),
+            AccountMeta::new(user.staking_account, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(bench.staking_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn claim_rewards_compute_units() {
+    let mut bench = Bench::start_with_pool(CLAIM_REWARDS_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |user_data| {
+        user_data.reward_per_token_pending = 1_000_000;
+    });
+    let instruction = bench.instruction(
+        StakingInstruction::ClaimRewards {},
+        vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.staking_vault, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new(user.rewards_account, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn close_user_compute_units() {
+    let mut bench = Bench::start_with_pool(CLOSE_USER_BUDGET).await;
+    let user = bench.add_user(0, |_| {});
+    let instruction = bench.instruction(
+        StakingInstruction::CloseUser {},
+        vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn close_pool_compute_units() {
+    let mut bench = Bench::start_with_pool(CLOSE_POOL_BUDGET).await;
+    bench.pool_data.reward_duration_end = (bench.now - 1) as u64;
+    bench.write_pool();
+    let (staking_mint, rewards_mint, owner) =
+        (bench.staking_mint, bench.rewards_mint, bench.owner.pubkey());
+    let staking_refund = bench.new_token_account(&staking_mint, &owner, 0);
+    let rewards_refund = bench.new_token_account(&rewards_mint, &owner, 0);
+    let instruction = bench.instruction(
+        StakingInstruction::ClosePool {},
+        vec![
+            AccountMeta::new(owner, true),
+            AccountMeta::new(bench.staking_vault, false),
+            AccountMeta::new(staking_refund, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new(rewards_refund, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(bench.staking_mint, false),
+            AccountMeta::new(bench.rewards_mint, false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn transfer_position_compute_units() {
+    let mut bench = Bench::start_with_pool(TRANSFER_POSITION_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |_| {});
+    let destination_wallet = Pubkey::new_unique();
+    let destination_storage =
+        get_user_storage_address(&destination_wallet, &bench.pool_storage, &bench.program_id);
+    let instruction = bench.instruction(
+        StakingInstruction::TransferPosition {
+            amount: STAKE_AMOUNT / 2,
+        },
+        vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(destination_wallet, false),
+            AccountMeta::new(destination_storage, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn migrate_pool_compute_units() {
+    let mut bench = Bench::start(MIGRATE_POOL_BUDGET).await;
+    let pool_v1 = Pubkey::new_unique();
+    let (pool_v1_signer, pool_v1_nonce) =
+        Pubkey::find_program_address(&[&pool_v1.to_bytes()], &bench.program_id);
+    let (staking_mint, rewards_mint) = (bench.staking_mint, bench.rewards_mint);
+    let staking_vault = bench.new_token_account(&staking_mint, &pool_v1_signer, STAKE_AMOUNT);
+    let rewards_vault = bench.new_token_account(&rewards_mint, &pool_v1_signer, FUND_AMOUNT);
+    let pool_v1_data = YourPoolV1 {
+        acc_type: AccTypesWithVersion::YourPoolDataV1 as u8,
+        owner_wallet: bench.owner.pubkey(),
+        your_staking_vault: staking_vault,
+        your_reward_rate: FUND_AMOUNT / REWARD_DURATION,
+        your_epoch_duration: REWARD_DURATION,
+        user_stake_count: 1,
+        pda_nonce: pool_v1_nonce,
+        reward_duration_end: bench.now as u64 + REWARD_DURATION / 2,
+    };
+    let program_id = bench.program_id;
+    bench.set_account(
+        &pool_v1,
+        LAMPORTS,
+        pool_v1_data.try_to_vec().unwrap(),
+        &program_id,
+    );
+    let owner = bench.owner.pubkey();
+    let instruction = bench.instruction(
+        StakingInstruction::MigratePool { pool_index: 0 },
+        vec![
+            AccountMeta::new(owner, true),
+            AccountMeta::new(pool_v1, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.staking_mint, false),
+            AccountMeta::new(staking_vault, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new(rewards_vault, false),
+            AccountMeta::new_readonly(pool_v1_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn migrate_user_compute_units() {
+    let mut bench = Bench::start_with_pool(MIGRATE_USER_BUDGET).await;
+    let pool_v1 = Pubkey::new_unique();
+    bench.pool_data.migrated_from = pool_v1;
+    bench.pool_data.total_your_staked = STAKE_AMOUNT;
+    bench.write_pool();
+    let wallet = Keypair::new();
+    bench.set_wallet(&wallet.pubkey());
+    let user_v1 = get_user_storage_address(&wallet.pubkey(), &pool_v1, &bench.program_id);
+    let user_v1_data = UserV1 {
+        acc_type: AccTypesWithVersion::UserDataV1 as u8,
+        user_wallet: wallet.pubkey(),
+        your_pool: pool_v1,
+        balance_your_staked: STAKE_AMOUNT,
+        unstake_pending: 0,
+        unstake_pending_date: 0,
+        nonce: 0,
+        claim_timeout_date: 0,
+    };
+    let program_id = bench.program_id;
+    bench.set_account(
+        &user_v1,
+        LAMPORTS,
+        user_v1_data.try_to_vec().unwrap(),
+        &program_id,
+    );
+    let storage =
+        get_user_storage_address(&wallet.pubkey(), &bench.pool_storage, &bench.program_id);
+    let instruction = bench.instruction(
+        StakingInstruction::MigrateUser {},
+        vec![
+            AccountMeta::new(wallet.pubkey(), true),
+            AccountMeta::new(user_v1, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(storage, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&wallet]).await;
+}
+
+#[tokio::test]
+async fn migrate_stake_compute_units() {
+    let mut bench = Bench::start_with_pool(MIGRATE_STAKE_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |_| {});
+
+    // A second pool of the same owner and staking mint, with a user account but no stake yet
+    let program_id = bench.program_id;
+    let destination_pool =
+        get_pool_storage_address(&bench.owner.pubkey(), &bench.staking_mint, 1, &program_id);
+    let destination_pool_signer =
+        Pubkey::find_program_address(&[&destination_pool.to_bytes()], &program_id).0;
+    let staking_mint = bench.staking_mint;
+    let destination_vault = bench.new_token_account(&staking_mint, &destination_pool_signer, 0);
+    let mut destination_pool_data = bench.pool_data;
+    destination_pool_data.your_staking_vault = destination_vault;
+    destination_pool_data.pool_index = 1;
+    destination_pool_data.total_your_staked = 0;
+    destination_pool_data.user_stake_count = 1;
+    let mut data = vec![0u8; YOUR_POOL_STORAGE_TOTAL_BYTES];
+    destination_pool_data.pack_versioned(&mut data).unwrap();
+    bench.set_account(&destination_pool, LAMPORTS, data, &program_id);
+    let destination_storage =
+        get_user_storage_address(&user.wallet.pubkey(), &destination_pool, &program_id);
+    let mut data = vec![0u8; USER_STORAGE_TOTAL_BYTES];
+    user_data(&user.wallet.pubkey(), &destination_pool)
+        .pack_versioned(&mut data)
+        .unwrap();
+    bench.set_account(&destination_storage, LAMPORTS, data, &program_id);
+
+    let instruction = bench.instruction(
+        StakingInstruction::MigrateStake {},
+        vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.staking_vault, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new(destination_storage, false),
+            AccountMeta::new(destination_pool, false),
+            AccountMeta::new(destination_vault, false),
+            AccountMeta::new_readonly(bench.staking_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn stake_nft_compute_units() {
+    let mut bench = Bench::start_with_pool(STAKE_NFT_BUDGET).await;
+    let nft_mint = bench.add_nft();
+    let user = bench.add_user(0, |_| {});
+    let user_nft_account = bench.new_token_account(&nft_mint, &user.wallet.pubkey(), 1);
+    let instruction = bench.instruction(
+        StakingInstruction::StakeNft {},
+        vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(nft_mint, false),
+            AccountMeta::new_readonly(get_metadata_address(&nft_mint), false),
+            AccountMeta::new(user_nft_account, false),
+            AccountMeta::new(bench.nft_record(&nft_mint, NFT_ESCROW_SEED), false),
+            AccountMeta::new(bench.nft_record(&nft_mint, NFT_STAKE_SEED), false),
+            AccountMeta::new_readonly(bench.nft_record(&nft_mint, NFT_WEIGHT_SEED), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn unstake_nft_compute_units() {
+    let mut bench = Bench::start_with_pool(UNSTAKE_NFT_BUDGET).await;
+    let nft_mint = bench.add_nft();
+    let user = bench.add_user(NFT_BASE_WEIGHT, |_| {});
+    let user_nft_account = bench.new_token_account(&nft_mint, &user.wallet.pubkey(), 0);
+    let (nft_escrow, pool_signer) = (
+        bench.nft_record(&nft_mint, NFT_ESCROW_SEED),
+        bench.pool_signer,
+    );
+    bench.set_token_account(&nft_escrow, &nft_mint, &pool_signer, 1);
+    let nft_stake = bench.nft_record(&nft_mint, NFT_STAKE_SEED);
+    let mut data = vec![0u8; NFT_STAKE_STORAGE_TOTAL_BYTES];
+    NftStake {
+        acc_type: AccTypesWithVersion::NftStakeDataV1 as u8,
+        your_pool: bench.pool_storage,
+        user_wallet: user.wallet.pubkey(),
+        nft_mint,
+        weight: NFT_BASE_WEIGHT,
+    }
+    .pack(&mut data)
+    .unwrap();
+    let program_id = bench.program_id;
+    bench.set_account(&nft_stake, LAMPORTS, data, &program_id);
+    let instruction = bench.instruction(
+        StakingInstruction::UnstakeNft {},
+        vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(nft_mint, false),
+            AccountMeta::new(user_nft_account, false),
+            AccountMeta::new(nft_escrow, false),
+            AccountMeta::new(nft_stake, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn set_nft_weight_compute_units() {
+    let mut bench = Bench::start_with_pool(SET_NFT_WEIGHT_BUDGET).await;
+    let nft_mint = bench.add_nft();
+    let instruction = bench.instruction(
+        StakingInstruction::SetNftWeight {
+            weight: 2 * NFT_BASE_WEIGHT,
+        },
+        vec![
+            AccountMeta::new(bench.owner.pubkey(), true),
+            AccountMeta::new_readonly(bench.pool_storage, false),
+            AccountMeta::new_readonly(nft_mint, false),
+            AccountMeta::new(bench.nft_record(&nft_mint, NFT_WEIGHT_SEED), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn configure_referrals_compute_units() {
+    let mut bench = Bench::start_with_pool(CONFIGURE_REFERRALS_BUDGET).await;
+    let (rewards_mint, owner) = (bench.rewards_mint, bench.owner.pubkey());
+    let owner_rewards_account = bench.new_token_account(&rewards_mint, &owner, FUND_AMOUNT);
+    let instruction = bench.instruction(
+        StakingInstruction::ConfigureReferrals {
+            referral_bps: 500,
+            fund_amount: FUND_AMOUNT,
+        },
+        vec![
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new(owner_rewards_account, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn claim_referral_rewards_compute_units() {
+    let mut bench = Bench::start_with_pool(CLAIM_REFERRAL_REWARDS_BUDGET).await;
+    bench.pool_data.referral_bps = 500;
+    bench.pool_data.referral_budget = FUND_AMOUNT / 10;
+    let referrer = Keypair::new();
+    let referrer_key = referrer.pubkey();
+    let user = bench.add_user(STAKE_AMOUNT, |user_data| {
+        user_data.referrer = referrer_key;
+        user_data.referral_rewards_owed = 1_000_000;
+    });
+    let rewards_mint = bench.rewards_mint;
+    let referrer_rewards_account = bench.new_token_account(&rewards_mint, &referrer_key, 0);
+    let instruction = bench.instruction(
+        StakingInstruction::ClaimReferralRewards {},
+        vec![
+            AccountMeta::new_readonly(referrer_key, true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new(referrer_rewards_account, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&referrer]).await;
+}
+
+#[tokio::test]
+async fn set_fee_compute_units() {
+    let mut bench = Bench::start_with_pool(SET_FEE_BUDGET).await;
+    let (rewards_mint, owner) = (bench.rewards_mint, bench.owner.pubkey());
+    let fee_recipient = bench.new_token_account(&rewards_mint, &owner, 0);
+    let instruction = bench.instruction(
+        StakingInstruction::SetFee {
+            reward_fee_bps: 100,
+        },
+        vec![
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new_readonly(fee_recipient, false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn set_vesting_duration_compute_units() {
+    let mut bench = Bench::start_with_pool(SET_VESTING_DURATION_BUDGET).await;
+    let instruction = bench.instruction(
+        StakingInstruction::SetVestingDuration {
+            vesting_duration: 86400,
+        },
+        vec![
+            AccountMeta::new_readonly(bench.owner.pubkey(), true),
+            AccountMeta::new(bench.pool_storage, false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn withdraw_vested_compute_units() {
+    let mut bench = Bench::start_with_pool(WITHDRAW_VESTED_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |user_data| {
+        user_data.vesting_unlocked = 1_000_000;
+    });
+    let instruction = bench.instruction(
+        StakingInstruction::WithdrawVested {},
+        vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new(user.rewards_account, false),
+            AccountMeta::new_readonly(bench.pool_signer, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    bench.process(instruction, &[&user.wallet]).await;
+}
+
+#[tokio::test]
+async fn verify_pool_compute_units() {
+    let mut bench = Bench::start_with_pool(VERIFY_POOL_BUDGET).await;
+    bench.add_user(STAKE_AMOUNT, |_| {});
+    let instruction = bench.instruction(
+        StakingInstruction::VerifyPool {},
+        vec![
+            AccountMeta::new_readonly(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.staking_vault, false),
+            AccountMeta::new_readonly(bench.rewards_vault, false),
+        ],
+    );
+    bench.process(instruction, &[]).await;
+}
+
+#[tokio::test]
+async fn query_pending_rewards_compute_units() {
+    let mut bench = Bench::start_with_pool(QUERY_PENDING_REWARDS_BUDGET).await;
+    let user = bench.add_user(STAKE_AMOUNT, |_| {});
+    let instruction = bench.instruction(
+        StakingInstruction::QueryPendingRewards {},
+        vec![
+            AccountMeta::new_readonly(bench.pool_storage, false),
+            AccountMeta::new_readonly(user.storage, false),
+        ],
+    );
+    bench.process(instruction, &[]).await;
+}
+
+#[tokio::test]
+async fn query_reward_projection_compute_units() {
+    let mut bench = Bench::start_with_pool(QUERY_REWARD_PROJECTION_BUDGET).await;
+    bench.add_user(STAKE_AMOUNT, |_| {});
+    let instruction = bench.instruction(
+        StakingInstruction::QueryRewardProjection {
+            deposit: STAKE_AMOUNT,
+            staking_price: 1_500_000,
+            rewards_price: 250_000,
+        },
+        vec![
+            AccountMeta::new_readonly(bench.pool_storage, false),
+            AccountMeta::new_readonly(bench.staking_mint, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+        ],
+    );
+    bench.process(instruction, &[]).await;
+}
+
+#[tokio::test]
+async fn set_paused_compute_units() {
+    let mut bench = Bench::start_with_pool(SET_PAUSED_BUDGET).await;
+    let instruction = bench.instruction(
+        StakingInstruction::SetPaused { paused: true },
+        vec![
+            AccountMeta::new_readonly(bench.owner.pubkey(), true),
+            AccountMeta::new(bench.pool_storage, false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
+
+#[tokio::test]
+async fn fund_pool_compute_units() {
+    let mut bench = Bench::start_with_pool(FUND_POOL_BUDGET).await;
+    bench.add_user(STAKE_AMOUNT, |_| {});
+    let (rewards_mint, owner) = (bench.rewards_mint, bench.owner.pubkey());
+    let owner_rewards_account = bench.new_token_account(&rewards_mint, &owner, FUND_AMOUNT);
+    let instruction = bench.instruction(
+        StakingInstruction::FundPool {
+            amount: FUND_AMOUNT,
+        },
+        vec![
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(bench.pool_storage, false),
+            AccountMeta::new(bench.rewards_vault, false),
+            AccountMeta::new(owner_rewards_account, false),
+            AccountMeta::new_readonly(bench.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    );
+    let owner = Keypair::from_bytes(&bench.owner.to_bytes()).unwrap();
+    bench.process(instruction, &[&owner]).await;
+}
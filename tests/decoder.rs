@@ -0,0 +1,153 @@
+#![cfg(feature = "decoder")]
+
+use serde_json::json;
+use solana_program::pubkey::Pubkey;
+use your_staking::{
+    decoder::{decode_account, DecodedAccount},
+    error::CustomError,
+    state::{
+        AccTypesWithVersion, NftWeight, User, YourPool, NFT_WEIGHT_STORAGE_TOTAL_BYTES,
+        USER_V1_STORAGE_TOTAL_BYTES, YOUR_POOL_STORAGE_TOTAL_BYTES,
+    },
+};
+
+// 2021-12-01T00:00:00Z
+const TIMESTAMP: i64 = 1_638_316_800;
+
+fn pool(acc_type: AccTypesWithVersion) -> YourPool {
+    YourPool {
+        acc_type: acc_type as u8,
+        owner_wallet: Pubkey::new_unique(),
+        your_staking_vault: Pubkey::new_unique(),
+        your_reward_rate: 1_000,
+        your_epoch_duration: 604_800,
+        user_stake_count: 3,
+        pda_nonce: 255,
+        reward_duration_end: TIMESTAMP as u64,
+        your_staking_mint: Pubkey::new_unique(),
+        your_rewards_vault: Pubkey::new_unique(),
+        your_rewards_mint: Pubkey::new_unique(),
+        total_your_staked: 5_000,
+        migrated_from: Pubkey::default(),
+        pool_index: 1,
+        reward_per_token_stored: u128::MAX,
+        last_update_time: 0,
+        receipt_mint: Pubkey::default(),
+        nft_collection: Pubkey::default(),
+        referral_bps: 500,
+        referral_budget: 10_000,
+        reward_fee_bps: 100,
+        fee_recipient: Pubkey::new_unique(),
+        vesting_duration: 86_400,
+        rewards_owed: 42,
+        paused: 0,
+    }
+}
+
+fn user(acc_type: AccTypesWithVersion) -> User {
+    User {
+        acc_type: acc_type as u8,
+        user_wallet: Pubkey::new_unique(),
+        your_pool: Pubkey::new_unique(),
+        balance_your_staked: 7,
+        unstake_pending: 0,
+        unstake_pending_date: 0,
+        nonce: 1,
+        claim_timeout_date: TIMESTAMP,
+        reward_per_token_complete: 0,
+        reward_per_token_pending: 0,
+        referrer: Pubkey::default(),
+        referral_rewards_owed: 0,
+        vesting_total: 0,
+        vesting_withdrawn: 0,
+        vesting_start: 0,
+        vesting_end: 0,
+        vesting_unlocked: 0,
+    }
+}
+
+#[test]
+fn decodes_pool_with_base58_keys_and_rfc3339_dates() {
+    let your_pool_data = pool(AccTypesWithVersion::YourPoolDataV2);
+    let mut data = vec![0u8; YOUR_POOL_STORAGE_TOTAL_BYTES];
+    your_pool_data.pack_versioned(&mut data).unwrap();
+
+    let decoded = serde_json::to_value(decode_account(&data).unwrap()).unwrap();
+    assert_eq!(decoded["type"], "pool");
+    assert_eq!(decoded["version"], 2);
+    assert_eq!(
+        decoded["ownerWallet"],
+        bs58::encode(your_pool_data.owner_wallet).into_string()
+    );
+    assert_eq!(decoded["rewardDurationEnd"], "2021-12-01T00:00:00Z");
+    assert_eq!(decoded["lastUpdateTime"], json!(null));
+    assert_eq!(decoded["rewardPerTokenStored"], u128::MAX.to_string());
+    assert_eq!(decoded["totalStaked"], 5_000);
+    assert_eq!(decoded["rewardsOwed"], 42);
+    assert_eq!(decoded["paused"], false);
+}
+
+#[test]
+fn decodes_v1_user() {
+    let user_storage_data = user(AccTypesWithVersion::UserDataV1);
+    let mut data = vec![0u8; USER_V1_STORAGE_TOTAL_BYTES];
+    user_storage_data.pack_versioned(&mut data).unwrap();
+
+    match decode_account(&data).unwrap() {
+        DecodedAccount::User(user_view) => {
+            assert_eq!(user_view.version, 1);
+            assert_eq!(user_view.pool, user_storage_data.your_pool.to_string());
+            assert_eq!(user_view.balance_staked, 7);
+            assert_eq!(user_view.unstake_pending_date, None);
+            assert_eq!(
+                user_view.claim_timeout_date.as_deref(),
+                Some("2021-12-01T00:00:00Z")
+            );
+        }
+        _ => panic!("expected a user account"),
+    }
+}
+
+#[test]
+fn decodes_nft_weight() {
+    let nft_weight_data = NftWeight {
+        acc_type: AccTypesWithVersion::NftWeightDataV1 as u8,
+        your_pool: Pubkey::new_unique(),
+        nft_mint: Pubkey::new_unique(),
+        weight: 15_000,
+    };
+    let mut data = vec![0u8; NFT_WEIGHT_STORAGE_TOTAL_BYTES];
+    nft_weight_data.pack(&mut data).unwrap();
+
+    let decoded = serde_json::to_value(decode_account(&data).unwrap()).unwrap();
+    assert_eq!(
+        decoded,
+        json!({
+            "type": "nftWeight",
+            "pool": nft_weight_data.your_pool.to_string(),
+            "nftMint": nft_weight_data.nft_mint.to_string(),
+            "weight": 15_000,
+        })
+    );
+}
+
+#[test]
+fn rejects_unknown_and_truncated_accounts() {
+    assert_eq!(
+        decode_account(&[]),
+        Err(CustomError::ExpectedAccountTypeMismatched.into())
+    );
+    assert_eq!(
+        decode_account(&[0u8; 64]),
+        Err(CustomError::ExpectedAccountTypeMismatched.into())
+    );
+
+    let mut data = vec![0u8; YOUR_POOL_STORAGE_TOTAL_BYTES];
+    pool(AccTypesWithVersion::YourPoolDataV2)
+        .pack_versioned(&mut data)
+        .unwrap();
+    assert_eq!(
+        decode_account(&data[..100]),
+        Err(CustomError::DataSizeNotMatched.into())
+    );
+}
@@ -0,0 +1,382 @@
+use num_bigint::BigUint;
+use proptest::prelude::*;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use your_staking::{
+    error::CustomError,
+    state::{AccTypesWithVersion, User, YourPool},
+    utils::{
+        constants::{MAX_REWARD_DURATION, SECONDS_PER_YEAR},
+        earned, last_time_reward_applicable, pending_rewards, reward_projection, rewards_per_token,
+        RewardProjection, TokenPrices,
+    },
+};
+
+// Fixed-point factor of `reward_per_token_stored`, kept in sync with `utils::PRECISION`
+const PRECISION: u128 = 1 << 64;
+
+// Largest `reward_rate * elapsed` a reward update is documented to accept, the rewards of a
+// whole funded period
+const MAX_REWARDS_PER_UPDATE: u128 = u64::MAX as u128;
+
+// 1 billion tokens of 9 decimals, the whole supply staked
+const MAX_TOTAL_STAKED: u64 = 1_000_000_000 * 1_000_000_000;
+
+fn big(value: u128) -> BigUint {
+    BigUint::from(value)
+}
+
+/// Exact `reward_per_token_stored` after an update, None when it does not fit in u128
+fn reference_rewards_per_token(
+    total_staked: u64,
+    elapsed: u64,
+    reward_rate: u64,
+    reward_per_token_stored: u128,
+) -> Option<u128> {
+    let added = big(reward_rate as u128) * big(elapsed as u128) * big(PRECISION)
+        / big(total_staked as u128);
+    let updated = added + big(reward_per_token_stored);
+    (updated <= big(u128::MAX)).then(|| updated.to_string().parse().unwrap())
+}
+
+/// Exact rewards of `balance` for a `reward_per_token` difference, None when they do not fit
+/// in u64
+fn reference_earned(balance: u64, reward_per_token: u128) -> Option<u64> {
+    let rewards = big(balance as u128) * big(reward_per_token) / big(PRECISION);
+    (rewards <= big(u64::MAX as u128)).then(|| rewards.to_string().parse().unwrap())
+}
+
+/// A pool with a fixed reward rate and stakers whose balances change over time
+struct Model {
+    reward_rate: u64,
+    now: u64,
+    last_update_time: u64,
+    reward_per_token_stored: u128,
+    balances: Vec<u64>,
+    reward_per_token_complete: Vec<u128>,
+    pending: Vec<u64>,
+}
+
+impl Model {
+    fn total_staked(&self) -> u64 {
+        self.balances.iter().sum()
+    }
+
+    fn settle(&mut self, user: usize) {
+        self.reward_per_token_stored = rewards_per_token(
+            self.total_staked(),
+            self.now,
+            self.last_update_time,
+            self.reward_rate,
+            self.reward_per_token_stored,
+        )
+        .unwrap();
+        self.last_update_time = self.now;
+        self.pending[user] = earned(
+            self.balances[user],
+            self.reward_per_token_stored,
+            self.reward_per_token_complete[user],
+            self.pending[user],
+        )
+        .unwrap();
+        self.reward_per_token_complete[user] = self.reward_per_token_stored;
+    }
+}
+
+proptest! {
+    #[test]
+    fn rewards_per_token_matches_reference(
+        total_staked in 1..=u64::MAX,
+        last_update_time in 0..=u32::MAX as u64,
+        elapsed in 0..=u32::MAX as u64,
+        reward_rate in any::<u64>(),
+        reward_per_token_stored in any::<u128>(),
+    ) {
+        let result = rewards_per_token(
+            total_staked,
+            last_update_time + elapsed,
+            last_update_time,
+            reward_rate,
+            reward_per_token_stored,
+        );
+        // Overflows are reported instead of panicking or wrapping, and only when the exact
+        // result does not fit
+        let expected = reference_rewards_per_token(
+            total_staked,
+            elapsed,
+            reward_rate,
+            reward_per_token_stored,
+        )
+        .ok_or_else(|| ProgramError::from(CustomError::AmountOverflow));
+        prop_assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn earned_matches_reference(
+        balance in any::<u64>(),
+        reward_per_token in any::<u128>(),
+    ) {
+        let expected = reference_earned(balance, reward_per_token)
+            .ok_or_else(|| ProgramError::from(CustomError::AmountOverflow));
+        prop_assert_eq!(earned(balance, reward_per_token, 0, 0), expected);
+    }
+
+    #[test]
+    fn rewards_per_token_does_not_overflow_within_bounds(
+        total_staked in 1..=u64::MAX,
+        reward_rate in 1..=u64::MAX,
+        elapsed in any::<u64>(),
+    ) {
+        let elapsed = (elapsed as u128 % (MAX_REWARDS_PER_UPDATE / reward_rate as u128 + 1)) as u64;
+        let reward_per_token_stored =
+            rewards_per_token(total_staked, elapsed, 0, reward_rate, 0);
+        prop_assert!(reward_per_token_stored.is_ok());
+        // Nor do the rewards of any staker, who holds at most the whole stake
+        prop_assert!(earned(total_staked, reward_per_token_stored.unwrap(), 0, 0).is_ok());
+    }
+
+    #[test]
+    fn rewards_per_token_is_unchanged_without_stake(
+        elapsed in any::<u64>(),
+        reward_rate in any::<u64>(),
+        reward_per_token_stored in any::<u128>(),
+    ) {
+        prop_assert_eq!(
+            rewards_per_token(0, elapsed, 0, reward_rate, reward_per_token_stored),
+            Ok(reward_per_token_stored)
+        );
+    }
+
+    #[test]
+    fn earned_never_overpays_and_loses_less_than_two_units(
+        total_staked in 1..=MAX_TOTAL_STAKED,
+        balance in any::<u64>(),
+        reward_rate in 1..=u64::MAX,
+        elapsed in any::<u64>(),
+    ) {
+        let balance = balance % total_staked + 1;
+        let elapsed = (elapsed as u128 % (MAX_REWARDS_PER_UPDATE / reward_rate as u128 + 1)) as u64;
+        let reward_per_token_stored =
+            rewards_per_token(total_staked, elapsed, 0, reward_rate, 0).unwrap();
+        let rewards = earned(balance, reward_per_token_stored, 0, 0).unwrap();
+
+        // Exact share of the user is reward_rate * elapsed * balance / total_staked
+        let exact_numerator =
+            big(reward_rate as u128) * big(elapsed as u128) * big(balance as u128);
+        prop_assert!(big(rewards as u128) * big(total_staked as u128) <= exact_numerator);
+        prop_assert!(big(rewards as u128 + 2) * big(total_staked as u128) > exact_numerator);
+    }
+
+    #[test]
+    fn stakers_never_earn_more_than_the_emission(
+        reward_rate in 1..=1_000_000_000_000u64,
+        initial_balances in prop::collection::vec(1..=MAX_TOTAL_STAKED / 8, 1..8),
+        steps in prop::collection::vec((1..=86_400u64, any::<prop::sample::Index>(), 1..=MAX_TOTAL_STAKED / 8), 1..32),
+    ) {
+        let users = initial_balances.len();
+        let mut model = Model {
+            reward_rate,
+            now: 0,
+            last_update_time: 0,
+            reward_per_token_stored: 0,
+            balances: initial_balances,
+            reward_per_token_complete: vec![0; users],
+            pending: vec![0; users],
+        };
+        for (elapsed, user, balance) in &steps {
+            let user = user.index(users);
+            model.now += elapsed;
+            model.settle(user);
+            model.balances[user] = *balance;
+        }
+        for user in 0..users {
+            model.settle(user);
+        }
+
+        // Someone is staked all along, so the whole emission is owed up to rounding
+        let emission = reward_rate as u128 * model.now as u128;
+        let paid: u128 = model.pending.iter().map(|pending| *pending as u128).sum();
+        prop_assert!(paid <= emission);
+        let settlements = (steps.len() + users) as u128;
+        prop_assert!(emission - paid <= 2 * settlements);
+    }
+}
+
+#[test]
+fn rewards_per_token_supports_highest_rate_for_longest_period() {
+    let reward_rate = u64::MAX / MAX_REWARD_DURATION;
+    let reward_per_token_stored =
+        rewards_per_token(1, MAX_REWARD_DURATION, 0, reward_rate, 0).unwrap();
+    assert_eq!(
+        earned(1, reward_per_token_stored, 0, 0),
+        Ok(reward_rate * MAX_REWARD_DURATION)
+    );
+}
+
+#[test]
+fn rewards_per_token_reports_overflow_past_bounds() {
+    let reward_rate = u64::MAX;
+    let elapsed = (MAX_REWARDS_PER_UPDATE / reward_rate as u128 + 1) as u64;
+    assert_eq!(
+        rewards_per_token(1, elapsed, 0, reward_rate, 0),
+        Err(CustomError::AmountOverflow.into())
+    );
+}
+
+#[test]
+fn last_time_reward_applicable_clamps_clock_before_epoch() {
+    assert_eq!(last_time_reward_applicable(100, -1), 0);
+    assert_eq!(last_time_reward_applicable(100, i64::MIN), 0);
+    assert_eq!(last_time_reward_applicable(100, 50), 50);
+    assert_eq!(last_time_reward_applicable(100, 150), 100);
+}
+
+/// A V2 pool with a single user who staked `balance` at `start`
+fn sole_staker(reward_rate: u64, start: u64, end: u64, balance: u64) -> (YourPool, User) {
+    let your_pool = Pubkey::new_unique();
+    let your_pool_data = YourPool {
+        acc_type: AccTypesWithVersion::YourPoolDataV2 as u8,
+        owner_wallet: Pubkey::new_unique(),
+        your_staking_vault: Pubkey::new_unique(),
+        your_reward_rate: reward_rate,
+        your_epoch_duration: end - start,
+        user_stake_count: 1,
+        pda_nonce: 0,
+        reward_duration_end: end,
+        your_staking_mint: Pubkey::new_unique(),
+        your_rewards_vault: Pubkey::new_unique(),
+        your_rewards_mint: Pubkey::new_unique(),
+        total_your_staked: balance,
+        migrated_from: Pubkey::default(),
+        pool_index: 0,
+        reward_per_token_stored: 0,
+        last_update_time: start,
+        receipt_mint: Pubkey::default(),
+        nft_collection: Pubkey::default(),
+        referral_bps: 0,
+        referral_budget: 0,
+        reward_fee_bps: 0,
+        fee_recipient: Pubkey::default(),
+        vesting_duration: 0,
+        rewards_owed: 0,
+        paused: 0,
+    };
+    let user_storage_data = User {
+        acc_type: AccTypesWithVersion::UserDataV2 as u8,
+        user_wallet: Pubkey::new_unique(),
+        your_pool,
+        balance_your_staked: balance,
+        unstake_pending: 0,
+        unstake_pending_date: 0,
+        nonce: 0,
+        claim_timeout_date: 0,
+        reward_per_token_complete: 0,
+        reward_per_token_pending: 0,
+        referrer: Pubkey::default(),
+        referral_rewards_owed: 0,
+        vesting_total: 0,
+        vesting_withdrawn: 0,
+        vesting_start: 0,
+        vesting_end: 0,
+        vesting_unlocked: 0,
+    };
+    (your_pool_data, user_storage_data)
+}
+
+#[test]
+fn pending_rewards_of_sole_staker_accrue_until_period_end() {
+    let (your_pool_data, user_storage_data) = sole_staker(1_000, 100, 1_100, 3);
+    assert_eq!(
+        pending_rewards(&your_pool_data, &user_storage_data, 100),
+        Ok(0)
+    );
+    // Rounding down the reward per token loses less than a base unit per staked token
+    let halfway = pending_rewards(&your_pool_data, &user_storage_data, 600).unwrap();
+    assert!(halfway <= 500_000 && halfway + 3 > 500_000);
+    assert_eq!(
+        pending_rewards(&your_pool_data, &user_storage_data, 1_100),
+        pending_rewards(&your_pool_data, &user_storage_data, 5_000)
+    );
+    assert_eq!({ your_pool_data.reward_per_token_stored }, 0);
+}
+
+// A reward rate of 1 over this much stake pays 10% a year
+const TEN_PERCENT_STAKE: u64 = SECONDS_PER_YEAR * 10;
+
+#[test]
+fn reward_projection_shares_the_rate_with_the_deposit() {
+    let now = 1_000;
+    let (your_pool_data, _) = sole_staker(1, now, now + SECONDS_PER_YEAR / 2, 100_000);
+    let projection = reward_projection(
+        &your_pool_data,
+        TEN_PERCENT_STAKE - 100_000,
+        None,
+        now as i64,
+    )
+    .unwrap();
+    assert_eq!(projection.current_apr_bps, 1_000);
+    assert_eq!(projection.projected_apr_bps, 500);
+
+    // Daily compounding, within the rounding of the fixed-point growth
+    let apy = (1.0 + 0.1 / 365.0_f64).powi(365) - 1.0;
+    assert!((projection.current_apy_bps as f64 - apy * 10_000.0).abs() <= 1.0);
+    let half_year_apy = (1.0 + 0.1 / 365.0_f64).powf(182.5) - 1.0;
+    assert!((projection.projected_apy_bps as f64 - half_year_apy * 10_000.0).abs() <= 1.0);
+    assert!(projection.projected_apy_bps >= projection.projected_apr_bps);
+
+    let ended = reward_projection(&your_pool_data, 1, None, (now + SECONDS_PER_YEAR) as i64);
+    assert_eq!(ended, Ok(RewardProjection::default()));
+}
+
+#[test]
+fn reward_projection_values_tokens_with_prices_and_decimals() {
+    let (your_pool_data, _) = sole_staker(1, 0, SECONDS_PER_YEAR, TEN_PERCENT_STAKE);
+    let projection = |prices| {
+        reward_projection(&your_pool_data, 0, Some(prices), 0)
+            .unwrap()
+            .current_apr_bps
+    };
+    // Rewards worth half the staked token
+    assert_eq!(
+        projection(TokenPrices {
+            staking_price: 2_000_000,
+            staking_decimals: 6,
+            rewards_price: 1_000_000,
+            rewards_decimals: 6,
+        }),
+        500
+    );
+    // A base unit of the 9 decimals staking mint is worth a thousandth of a rewards base unit
+    assert_eq!(
+        projection(TokenPrices {
+            staking_price: 1_000_000,
+            staking_decimals: 9,
+            rewards_price: 1_000_000,
+            rewards_decimals: 6,
+        }),
+        1_000_000
+    );
+    assert_eq!(
+        projection(TokenPrices {
+            staking_price: 0,
+            staking_decimals: 6,
+            rewards_price: 1_000_000,
+            rewards_decimals: 6,
+        }),
+        0
+    );
+}
+
+#[test]
+fn reward_projection_round_trips_through_return_data() {
+    let projection = RewardProjection {
+        current_apr_bps: 1,
+        current_apy_bps: 2,
+        projected_apr_bps: 3,
+        projected_apy_bps: u64::MAX,
+    };
+    assert_eq!(RewardProjection::unpack(&projection.pack()), Ok(projection));
+    assert_eq!(
+        RewardProjection::unpack(&projection.pack()[..31]),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
@@ -0,0 +1,3178 @@
+#![cfg(feature = "test-bpf")]
+
+use assert_matches::assert_matches;
+use borsh::{BorshDeserialize, BorshSerialize};
+use lazy_static::lazy_static;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    system_instruction, system_program, sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+use std::{
+    convert::TryInto,
+    sync::{Mutex, Once},
+};
+use your_staking::{
+    error::CustomError,
+    events::StakingEvent,
+    instruction::Instruction as StakingInstruction,
+    metadata::{get_metadata_address, mpl_token_metadata},
+    processor::{
+        create_user::get_user_storage_address,
+        initialize_pool::{get_pool_storage_address, get_vault_address},
+        set_nft_weight::get_nft_record_address,
+        Processor,
+    },
+    state::{AccTypesWithVersion, User, UserV1, YourPool, YourPoolV1},
+    token::token_2022,
+    utils::{
+        self,
+        constants::{
+            BPS_DENOMINATOR, MAX_REWARD_DURATION, MAX_REWARD_FEE_BPS, MIN_REWARD_DURATION,
+            NFT_ESCROW_SEED, NFT_STAKE_SEED, NFT_WEIGHT_SEED, RECEIPT_MINT_SEED,
+            REWARDS_VAULT_SEED, SECONDS_PER_YEAR, STAKING_VAULT_SEED,
+        },
+        RewardProjection, TokenPrices,
+    },
+};
+
+const REWARD_DURATION: u64 = 7 * 86400;
+const FUND_AMOUNT: u64 = 604_800_000_000;
+const STAKE_AMOUNT: u64 = 1_000_000_000;
+const CLAIM_TIMEOUT: i64 = 86400;
+
+struct TestPool {
+    program_id: Pubkey,
+    owner: Keypair,
+    staking_mint: Pubkey,
+    rewards_mint: Pubkey,
+    owner_staking_account: Pubkey,
+    owner_rewards_account: Pubkey,
+    pool_storage: Pubkey,
+    staking_vault: Pubkey,
+    rewards_vault: Pubkey,
+    pool_signer: Pubkey,
+}
+
+struct TestUser {
+    wallet: Keypair,
+    storage: Pubkey,
+    staking_account: Pubkey,
+    rewards_account: Pubkey,
+}
+
+fn program_test(program_id: Pubkey) -> ProgramTest {
+    ProgramTest::new("your_staking", program_id, processor!(Processor::process))
+}
+
+/// Runs the program natively even under `cargo test-bpf`, so its events and return data are
+/// recorded by `RecordingSyscallStubs`
+fn native_program_test(program_id: Pubkey) -> ProgramTest {
+    let mut program_test = program_test(program_id);
+    program_test.prefer_bpf(false);
+    program_test
+}
+
+/// Starts the bank. Every test starts through here, so the recording stubs are in place
+/// before any transaction runs.
+async fn start(program_test: ProgramTest) -> ProgramTestContext {
+    static RECORDING: Once = Once::new();
+    // The stubs of solana-program-test are installed by the first bank of the process
+    let context = program_test.start_with_context().await;
+    RECORDING.call_once(|| {
+        let stubs = program_stubs::set_syscall_stubs(Box::new(NoSyscallStubs));
+        program_stubs::set_syscall_stubs(Box::new(RecordingSyscallStubs(stubs)));
+    });
+    context
+}
+
+/// Data the native program emitted. BanksClient 1.8 returns neither transaction logs nor
+/// return data, so both are recorded from the syscalls instead.
+#[derive(Default)]
+struct Emitted {
+    events: Vec<Vec<u8>>,
+    return_data: Vec<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref EMITTED: Mutex<Emitted> = Mutex::default();
+    // Return data does not name its pool, so the tests reading it run one at a time
+    static ref RETURN_DATA_LOCK: Mutex<()> = Mutex::default();
+}
+
+struct NoSyscallStubs;
+
+impl SyscallStubs for NoSyscallStubs {}
+
+/// Forwards to the stubs of solana-program-test and records events and return data
+struct RecordingSyscallStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for RecordingSyscallStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&mut self, data: &[u8]) {
+        EMITTED.lock().unwrap().return_data.push(data.to_vec());
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let mut emitted = EMITTED.lock().unwrap();
+        emitted
+            .events
+            .extend(fields.iter().map(|field| field.to_vec()));
+        self.0.sol_log_data(fields)
+    }
+}
+
+fn event_pool(event: &StakingEvent) -> Pubkey {
+    match event {
+        StakingEvent::PoolInitialized { pool, .. }
+        | StakingEvent::Staked { pool, .. }
+        | StakingEvent::UnstakeRequested { pool, .. }
+        | StakingEvent::Unstaked { pool, .. }
+        | StakingEvent::RewardsClaimed { pool, .. }
+        | StakingEvent::PoolFunded { pool, .. }
+        | StakingEvent::PoolClosed { pool, .. }
+        | StakingEvent::UserClosed { pool, .. }
+        | StakingEvent::UserCreated { pool, .. }
+        | StakingEvent::PoolMigrated { pool, .. }
+        | StakingEvent::UserMigrated { pool, .. }
+        | StakingEvent::PositionTransferred { pool, .. }
+        | StakingEvent::StakeMigrated { pool, .. }
+        | StakingEvent::NftStaked { pool, .. }
+        | StakingEvent::NftUnstaked { pool, .. }
+        | StakingEvent::ReferralsConfigured { pool, .. }
+        | StakingEvent::ReferralRewardsClaimed { pool, .. }
+        | StakingEvent::RewardFeeSet { pool, .. }
+        | StakingEvent::RewardFeeCollected { pool, .. }
+        | StakingEvent::VestingDurationSet { pool, .. }
+        | StakingEvent::RewardsVested { pool, .. }
+        | StakingEvent::VestedWithdrawn { pool, .. }
+        | StakingEvent::NftWeightSet { pool, .. }
+        | StakingEvent::PausedSet { pool, .. } => *pool,
+    }
+}
+
+/// Takes the recorded events of `pool` in emission order, leaving those of other tests
+fn take_events(pool: &Pubkey) -> Vec<StakingEvent> {
+    let mut events = vec![];
+    EMITTED.lock().unwrap().events.retain(|data| {
+        let event = StakingEvent::try_from_slice(data).unwrap();
+        if event_pool(&event) != *pool {
+            return true;
+        }
+        events.push(event);
+        false
+    });
+    events
+}
+
+async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransportError> {
+    // Identical transactions in a row need a new blockhash to get a new signature
+    let (blockhash, _fee_calculator) = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    context.last_blockhash = blockhash;
+
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Processes a query instruction and returns the return data it set
+#[allow(clippy::await_holding_lock)]
+async fn query(context: &mut ProgramTestContext, instruction: Instruction) -> Vec<u8> {
+    // A failed query panics with the lock held, which must not fail the other queries
+    let _guard = RETURN_DATA_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    EMITTED.lock().unwrap().return_data.clear();
+    process(context, &[instruction], &[]).await.unwrap();
+    EMITTED.lock().unwrap().return_data.pop().unwrap()
+}
+
+fn assert_custom_error(result: Result<(), TransportError>, error: CustomError) {
+    assert_matches!(
+        result,
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code)
+        ))) if code == error as u32
+    );
+}
+
+/// Moves the cluster clock `seconds` ahead
+async fn warp_seconds(context: &mut ProgramTestContext, seconds: i64) {
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    let unix_timestamp = clock.unix_timestamp + seconds;
+    // The working bank sits one slot past the root and the warp lands one slot past its frozen parent
+    let root_slot = context.banks_client.get_root_slot().await.unwrap();
+    context.warp_to_slot(root_slot + 3).unwrap();
+
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = clock.unix_timestamp.max(unix_timestamp);
+    context.set_account(
+        &sysvar::clock::id(),
+        &account::create_account_shared_data_for_test(&clock),
+    );
+}
+
+async fn create_mint(context: &mut ProgramTestContext) -> Pubkey {
+    create_mint_with_decimals(context, 9).await
+}
+
+async fn create_mint_with_decimals(context: &mut ProgramTestContext, decimals: u8) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(Mint::LEN),
+                Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &payer,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+async fn create_token_account(
+    context: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let token_account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let payer = context.payer.pubkey();
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &payer,
+            &token_account.pubkey(),
+            rent.minimum_balance(TokenAccount::LEN),
+            TokenAccount::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &token_account.pubkey(),
+            mint,
+            owner,
+        )
+        .unwrap(),
+    ];
+    if amount > 0 {
+        instructions.push(
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                &token_account.pubkey(),
+                &payer,
+                &[],
+                amount,
+            )
+            .unwrap(),
+        );
+    }
+    process(context, &instructions, &[&token_account])
+        .await
+        .unwrap();
+    token_account.pubkey()
+}
+
+async fn create_wallet(context: &mut ProgramTestContext) -> Keypair {
+    let wallet = Keypair::new();
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[system_instruction::transfer(
+            &payer,
+            &wallet.pubkey(),
+            1_000_000_000,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    wallet
+}
+
+async fn token_balance(context: &mut ProgramTestContext, token_account: &Pubkey) -> u64 {
+    let account = context
+        .banks_client
+        .get_account(*token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    TokenAccount::unpack(&account.data[..TokenAccount::LEN])
+        .unwrap()
+        .amount
+}
+
+async fn get_pool(context: &mut ProgramTestContext, pool: &TestPool) -> YourPool {
+    let account = context
+        .banks_client
+        .get_account(pool.pool_storage)
+        .await
+        .unwrap()
+        .unwrap();
+    YourPool::unpack_versioned(&account.data).unwrap()
+}
+
+/// Overwrites the pool storage data, to reach states the instructions can not produce
+async fn set_pool(context: &mut ProgramTestContext, pool: &TestPool, pool_data: &YourPool) {
+    let mut account = context
+        .banks_client
+        .get_account(pool.pool_storage)
+        .await
+        .unwrap()
+        .unwrap();
+    pool_data.pack_versioned(&mut account.data).unwrap();
+    context.set_account(
+        &pool.pool_storage,
+        &account::AccountSharedData::from(account),
+    );
+}
+
+async fn get_user(context: &mut ProgramTestContext, user: &TestUser) -> User {
+    let account = context
+        .banks_client
+        .get_account(user.storage)
+        .await
+        .unwrap()
+        .unwrap();
+    User::unpack_versioned(&account.data).unwrap()
+}
+
+async fn setup_pool(context: &mut ProgramTestContext, program_id: Pubkey) -> TestPool {
+    let owner = create_wallet(context).await;
+    let staking_mint = create_mint(context).await;
+    let rewards_mint = create_mint(context).await;
+    let owner_staking_account =
+        create_token_account(context, &staking_mint, &owner.pubkey(), 0).await;
+    let owner_rewards_account =
+        create_token_account(context, &rewards_mint, &owner.pubkey(), FUND_AMOUNT).await;
+    let pool_storage = get_pool_storage_address(&owner.pubkey(), &staking_mint, 0, &program_id);
+    TestPool {
+        program_id,
+        staking_mint,
+        rewards_mint,
+        owner_staking_account,
+        owner_rewards_account,
+        pool_storage,
+        staking_vault: get_vault_address(&pool_storage, STAKING_VAULT_SEED, &program_id),
+        rewards_vault: get_vault_address(&pool_storage, REWARDS_VAULT_SEED, &program_id),
+        pool_signer: Pubkey::find_program_address(&[&pool_storage.to_bytes()], &program_id).0,
+        owner,
+    }
+}
+
+/// The pool of the same owner and staking mint at `pool_index`
+fn pool_at_index(pool: &TestPool, pool_index: u64) -> TestPool {
+    let pool_storage = get_pool_storage_address(
+        &pool.owner.pubkey(),
+        &pool.staking_mint,
+        pool_index,
+        &pool.program_id,
+    );
+    TestPool {
+        program_id: pool.program_id,
+        owner: Keypair::from_bytes(&pool.owner.to_bytes()).unwrap(),
+        pool_storage,
+        staking_vault: get_vault_address(&pool_storage, STAKING_VAULT_SEED, &pool.program_id),
+        rewards_vault: get_vault_address(&pool_storage, REWARDS_VAULT_SEED, &pool.program_id),
+        pool_signer: Pubkey::find_program_address(&[&pool_storage.to_bytes()], &pool.program_id).0,
+        ..*pool
+    }
+}
+
+/// The same wallet and token accounts as `user`, with its storage in `pool`
+fn user_in_pool(user: &TestUser, pool: &TestPool) -> TestUser {
+    TestUser {
+        wallet: Keypair::from_bytes(&user.wallet.to_bytes()).unwrap(),
+        storage: get_user_storage_address(
+            &user.wallet.pubkey(),
+            &pool.pool_storage,
+            &pool.program_id,
+        ),
+        ..*user
+    }
+}
+
+async fn setup_user(context: &mut ProgramTestContext, pool: &TestPool) -> TestUser {
+    let wallet = create_wallet(context).await;
+    let staking_account =
+        create_token_account(context, &pool.staking_mint, &wallet.pubkey(), STAKE_AMOUNT).await;
+    let rewards_account =
+        create_token_account(context, &pool.rewards_mint, &wallet.pubkey(), 0).await;
+    TestUser {
+        storage: get_user_storage_address(&wallet.pubkey(), &pool.pool_storage, &pool.program_id),
+        wallet,
+        staking_account,
+        rewards_account,
+    }
+}
+
+fn initialize_pool_instruction(pool: &TestPool, fund_amount: u64) -> Instruction {
+    initialize_pool_instruction_with(pool, REWARD_DURATION, fund_amount, 0, false, false)
+}
+
+fn initialize_pool_instruction_with(
+    pool: &TestPool,
+    reward_duration: u64,
+    fund_amount: u64,
+    pool_index: u64,
+    with_receipt_mint: bool,
+    nft_pool: bool,
+) -> Instruction {
+    let data = StakingInstruction::InitializePool {
+        reward_duration,
+        pool_nonce: 0,
+        fund_amount,
+        pool_index,
+        with_receipt_mint,
+        nft_pool,
+    }
+    .pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(pool.staking_mint, false),
+            AccountMeta::new(pool.staking_vault, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new(pool.owner_rewards_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data,
+    }
+}
+
+fn create_user_instruction(pool: &TestPool, user: &TestUser) -> Instruction {
+    create_user_instruction_with_referrer(pool, user, None)
+}
+
+fn create_user_instruction_with_referrer(
+    pool: &TestPool,
+    user: &TestUser,
+    referrer: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: StakingInstruction::CreateUser { nonce: 0, referrer }.pack(),
+    }
+}
+
+fn stake_instruction(pool: &TestPool, user: &TestUser, amount: u64) -> Instruction {
+    let data = StakingInstruction::Stake {
+        amount_to_deposit: amount,
+    }
+    .pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.staking_vault, false),
+            AccountMeta::new(user.staking_account, false),
+            AccountMeta::new_readonly(pool.staking_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+fn unstake_instruction(pool: &TestPool, user: &TestUser, amount: u64) -> Instruction {
+    let data = StakingInstruction::Unstake {
+        amount_to_withdraw: amount,
+    }
+    .pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(pool.staking_vault, false),
+            AccountMeta::new_readonly(user.staking_account, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+fn final_unstake_instruction(pool: &TestPool, user: &TestUser) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.staking_vault, false),
+            AccountMeta::new(user.staking_account, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(pool.staking_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StakingInstruction::FinalUnstake {}.pack(),
+    }
+}
+
+fn claim_rewards_instruction(
+    pool: &TestPool,
+    user: &TestUser,
+    rewards_vault: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(pool.staking_vault, false),
+            AccountMeta::new(*rewards_vault, false),
+            AccountMeta::new(user.rewards_account, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StakingInstruction::ClaimRewards {}.pack(),
+    }
+}
+
+fn close_user_instruction(pool: &TestPool, user: &TestUser) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+        ],
+        data: StakingInstruction::CloseUser {}.pack(),
+    }
+}
+
+fn close_pool_instruction(pool: &TestPool, owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(pool.staking_vault, false),
+            AccountMeta::new(pool.owner_staking_account, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new(pool.owner_rewards_account, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(pool.staking_mint, false),
+            AccountMeta::new(pool.rewards_mint, false),
+        ],
+        data: StakingInstruction::ClosePool {}.pack(),
+    }
+}
+
+fn transfer_position_instruction(
+    pool: &TestPool,
+    user: &TestUser,
+    destination: &TestUser,
+    amount: u64,
+) -> Instruction {
+    let data = StakingInstruction::TransferPosition { amount }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(destination.wallet.pubkey(), false),
+            AccountMeta::new(destination.storage, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+fn migrate_stake_instruction(
+    pool: &TestPool,
+    user: &TestUser,
+    destination_pool: &TestPool,
+    destination_user: &TestUser,
+) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.staking_vault, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new(destination_user.storage, false),
+            AccountMeta::new(destination_pool.pool_storage, false),
+            AccountMeta::new(destination_pool.staking_vault, false),
+            AccountMeta::new_readonly(pool.staking_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StakingInstruction::MigrateStake {}.pack(),
+    }
+}
+
+fn set_nft_weight_instruction(pool: &TestPool, nft_mint: &Pubkey, weight: u64) -> Instruction {
+    let data = StakingInstruction::SetNftWeight { weight }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new_readonly(pool.pool_storage, false),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new(
+                get_nft_record_address(
+                    &pool.pool_storage,
+                    nft_mint,
+                    NFT_WEIGHT_SEED,
+                    &pool.program_id,
+                ),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+fn stake_nft_instruction(
+    pool: &TestPool,
+    user: &TestUser,
+    nft_mint: &Pubkey,
+    user_nft_account: &Pubkey,
+) -> Instruction {
+    let nft_record =
+        |seed| get_nft_record_address(&pool.pool_storage, nft_mint, seed, &pool.program_id);
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new_readonly(get_metadata_address(nft_mint), false),
+            AccountMeta::new(*user_nft_account, false),
+            AccountMeta::new(nft_record(NFT_ESCROW_SEED), false),
+            AccountMeta::new(nft_record(NFT_STAKE_SEED), false),
+            AccountMeta::new_readonly(nft_record(NFT_WEIGHT_SEED), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: StakingInstruction::StakeNft {}.pack(),
+    }
+}
+
+fn unstake_nft_instruction(
+    pool: &TestPool,
+    user: &TestUser,
+    nft_mint: &Pubkey,
+    user_nft_account: &Pubkey,
+) -> Instruction {
+    let nft_record =
+        |seed| get_nft_record_address(&pool.pool_storage, nft_mint, seed, &pool.program_id);
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new(*user_nft_account, false),
+            AccountMeta::new(nft_record(NFT_ESCROW_SEED), false),
+            AccountMeta::new(nft_record(NFT_STAKE_SEED), false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StakingInstruction::UnstakeNft {}.pack(),
+    }
+}
+
+fn configure_referrals_instruction(
+    pool: &TestPool,
+    referral_bps: u16,
+    fund_amount: u64,
+) -> Instruction {
+    let data = StakingInstruction::ConfigureReferrals {
+        referral_bps,
+        fund_amount,
+    }
+    .pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new(pool.owner_rewards_account, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+fn claim_referral_rewards_instruction(
+    pool: &TestPool,
+    signer: &Pubkey,
+    referee: &TestUser,
+    referrer_rewards_account: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(referee.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new(*referrer_rewards_account, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StakingInstruction::ClaimReferralRewards {}.pack(),
+    }
+}
+
+fn set_fee_instruction(
+    pool: &TestPool,
+    owner: &Pubkey,
+    reward_fee_bps: u16,
+    fee_recipient: &Pubkey,
+) -> Instruction {
+    let data = StakingInstruction::SetFee { reward_fee_bps }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(*fee_recipient, false),
+        ],
+        data,
+    }
+}
+
+fn set_vesting_duration_instruction(pool: &TestPool, vesting_duration: u64) -> Instruction {
+    let data = StakingInstruction::SetVestingDuration { vesting_duration }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new(pool.pool_storage, false),
+        ],
+        data,
+    }
+}
+
+fn set_paused_instruction(pool: &TestPool, paused: bool) -> Instruction {
+    let data = StakingInstruction::SetPaused { paused }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new(pool.pool_storage, false),
+        ],
+        data,
+    }
+}
+
+fn fund_pool_instruction(pool: &TestPool, amount: u64) -> Instruction {
+    let data = StakingInstruction::FundPool { amount }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(pool.owner.pubkey(), true),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new(pool.owner_rewards_account, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+fn withdraw_vested_instruction(pool: &TestPool, user: &TestUser) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.wallet.pubkey(), true),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new(user.rewards_account, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StakingInstruction::WithdrawVested {}.pack(),
+    }
+}
+
+fn migrate_pool_instruction(pool: &TestPool, pool_v1: &Pubkey) -> Instruction {
+    let data = StakingInstruction::MigratePool { pool_index: 0 }.pack();
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(pool.owner.pubkey(), true),
+            AccountMeta::new(*pool_v1, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new_readonly(pool.staking_mint, false),
+            AccountMeta::new(pool.staking_vault, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new_readonly(
+                Pubkey::find_program_address(&[&pool_v1.to_bytes()], &pool.program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+fn migrate_user_instruction(pool: &TestPool, user: &TestUser, user_v1: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new(user.wallet.pubkey(), true),
+            AccountMeta::new(*user_v1, false),
+            AccountMeta::new(pool.pool_storage, false),
+            AccountMeta::new(user.storage, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: StakingInstruction::MigrateUser {}.pack(),
+    }
+}
+
+fn verify_pool_instruction(pool: &TestPool, staking_vault: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(pool.pool_storage, false),
+            AccountMeta::new_readonly(*staking_vault, false),
+            AccountMeta::new_readonly(pool.rewards_vault, false),
+        ],
+        data: StakingInstruction::VerifyPool {}.pack(),
+    }
+}
+
+fn query_pending_rewards_instruction(pool: &TestPool, user: &TestUser) -> Instruction {
+    Instruction {
+        program_id: pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(pool.pool_storage, false),
+            AccountMeta::new_readonly(user.storage, false),
+        ],
+        data: StakingInstruction::QueryPendingRewards {}.pack(),
+    }
+}
+
+fn query_reward_projection_instruction(
+    pool: &TestPool,
+    deposit: u64,
+    staking_price: u64,
+    rewards_price: u64,
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(pool.pool_storage, false)];
+    if staking_price != 0 || rewards_price != 0 {
+        accounts.push(AccountMeta::new_readonly(pool.staking_mint, false));
+        accounts.push(AccountMeta::new_readonly(pool.rewards_mint, false));
+    }
+    Instruction {
+        program_id: pool.program_id,
+        accounts,
+        data: StakingInstruction::QueryRewardProjection {
+            deposit,
+            staking_price,
+            rewards_price,
+        }
+        .pack(),
+    }
+}
+
+/// Starts a context with an initialized pool and a user that staked `STAKE_AMOUNT`
+async fn setup_staked_user() -> (ProgramTestContext, TestPool, TestUser) {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let user = setup_user(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[
+            create_user_instruction(&pool, &user),
+            stake_instruction(&pool, &user, STAKE_AMOUNT),
+        ],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    (context, pool, user)
+}
+
+#[tokio::test]
+async fn test_pool_lifecycle() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let pool_data = get_pool(&mut context, &pool).await;
+    let staked_at = pool_data.last_update_time;
+    assert_eq!({ pool_data.total_your_staked }, STAKE_AMOUNT);
+    assert_eq!({ pool_data.user_stake_count }, 1);
+    assert_eq!(
+        { pool_data.your_reward_rate },
+        FUND_AMOUNT / REWARD_DURATION
+    );
+    assert_eq!(
+        token_balance(&mut context, &pool.staking_vault).await,
+        STAKE_AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut context, &pool.rewards_vault).await,
+        FUND_AMOUNT
+    );
+
+    warp_seconds(&mut context, 100).await;
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        { get_user(&mut context, &user).await.unstake_pending },
+        STAKE_AMOUNT
+    );
+
+    warp_seconds(&mut context, 3).await;
+    process(
+        &mut context,
+        &[final_unstake_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let user_data = get_user(&mut context, &user).await;
+    assert_eq!({ user_data.balance_your_staked }, 0);
+    assert_eq!({ user_data.unstake_pending }, 0);
+    assert!(user_data.reward_per_token_pending > 0);
+    assert_eq!(
+        token_balance(&mut context, &user.staking_account).await,
+        STAKE_AMOUNT
+    );
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert_eq!({ pool_data.total_your_staked }, 0);
+
+    // The sole staker earns the whole emission of the staked period, up to rounding
+    let rewards_emitted = pool_data.your_reward_rate * (pool_data.last_update_time - staked_at);
+    assert!(pool_data.last_update_time - staked_at >= 103);
+    assert!(user_data.reward_per_token_pending <= rewards_emitted);
+    assert!(rewards_emitted - user_data.reward_per_token_pending <= 2);
+
+    let rewards_pending = user_data.reward_per_token_pending;
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &user.rewards_account).await,
+        rewards_pending
+    );
+
+    process(
+        &mut context,
+        &[close_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert!(context
+        .banks_client
+        .get_account(user.storage)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!({ get_pool(&mut context, &pool).await.user_stake_count }, 0);
+
+    warp_seconds(&mut context, REWARD_DURATION as i64).await;
+    process(
+        &mut context,
+        &[close_pool_instruction(&pool, &pool.owner.pubkey())],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &pool.owner_rewards_account).await,
+        FUND_AMOUNT - rewards_pending
+    );
+    assert!(context
+        .banks_client
+        .get_account(pool.staking_vault)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(context
+        .banks_client
+        .get_account(pool.rewards_vault)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_events() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(native_program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    assert_matches!(
+        &take_events(&pool.pool_storage)[..],
+        [
+            StakingEvent::PoolInitialized { owner_wallet, .. },
+            StakingEvent::PoolFunded { amount, .. },
+        ] if *owner_wallet == pool.owner.pubkey() && *amount == FUND_AMOUNT
+    );
+
+    let user = setup_user(&mut context, &pool).await;
+    let other_user = setup_user(&mut context, &pool).await;
+    for (staker, amount) in [(&user, STAKE_AMOUNT), (&other_user, STAKE_AMOUNT / 2)] {
+        process(
+            &mut context,
+            &[
+                create_user_instruction(&pool, staker),
+                stake_instruction(&pool, staker, amount),
+            ],
+            &[&staker.wallet],
+        )
+        .await
+        .unwrap();
+    }
+    let events = take_events(&pool.pool_storage);
+    assert_eq!(events.len(), 4);
+    assert_matches!(
+        events[1],
+        StakingEvent::Staked {
+            user_wallet,
+            amount: STAKE_AMOUNT,
+            balance_staked: STAKE_AMOUNT,
+            total_staked: STAKE_AMOUNT,
+            ..
+        } if user_wallet == user.wallet.pubkey()
+    );
+    assert_matches!(
+        events[2],
+        StakingEvent::UserCreated { user_wallet, user_stake_count: 2, .. }
+            if user_wallet == other_user.wallet.pubkey()
+    );
+    assert_matches!(
+        events[3],
+        StakingEvent::Staked {
+            amount,
+            balance_staked,
+            total_staked,
+            ..
+        } if amount == STAKE_AMOUNT / 2
+            && balance_staked == STAKE_AMOUNT / 2
+            && total_staked == STAKE_AMOUNT + STAKE_AMOUNT / 2
+    );
+
+    warp_seconds(&mut context, 100).await;
+    let pool_data = get_pool(&mut context, &pool).await;
+    let user_data = get_user(&mut context, &user).await;
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let claimed = match take_events(&pool.pool_storage)[..] {
+        [StakingEvent::RewardsClaimed {
+            user_wallet,
+            amount,
+            claim_timeout_date,
+            timestamp,
+            ..
+        }] => {
+            assert_eq!(user_wallet, user.wallet.pubkey());
+            assert_eq!(
+                amount,
+                utils::pending_rewards(&pool_data, &user_data, timestamp).unwrap()
+            );
+            assert_eq!(claim_timeout_date, timestamp + CLAIM_TIMEOUT);
+            amount
+        }
+        ref events => panic!("unexpected events {:?}", events),
+    };
+    assert!(claimed > 0);
+    assert_eq!(
+        token_balance(&mut context, &user.rewards_account).await,
+        claimed
+    );
+
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_matches!(
+        take_events(&pool.pool_storage)[..],
+        [StakingEvent::UnstakeRequested {
+            amount: STAKE_AMOUNT,
+            unstake_pending_date,
+            timestamp,
+            ..
+        }] if unstake_pending_date == timestamp + 2
+    );
+
+    warp_seconds(&mut context, 3).await;
+    process(
+        &mut context,
+        &[final_unstake_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_matches!(
+        take_events(&pool.pool_storage)[..],
+        [StakingEvent::Unstaked {
+            amount: STAKE_AMOUNT,
+            balance_staked: 0,
+            total_staked,
+            ..
+        }] if total_staked == STAKE_AMOUNT / 2
+    );
+    assert_eq!(
+        { get_pool(&mut context, &pool).await.total_your_staked },
+        STAKE_AMOUNT / 2
+    );
+
+    warp_seconds(&mut context, CLAIM_TIMEOUT).await;
+    process(
+        &mut context,
+        &[
+            claim_rewards_instruction(&pool, &user, &pool.rewards_vault),
+            close_user_instruction(&pool, &user),
+        ],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_matches!(
+        take_events(&pool.pool_storage)[..],
+        [
+            StakingEvent::RewardsClaimed { .. },
+            StakingEvent::UserClosed {
+                user_wallet,
+                user_stake_count: 1,
+                ..
+            },
+        ] if user_wallet == user.wallet.pubkey()
+    );
+    assert_eq!({ get_pool(&mut context, &pool).await.user_stake_count }, 1);
+}
+
+#[tokio::test]
+async fn test_initialize_pool_errors() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+
+    let mut instruction = initialize_pool_instruction(&pool, FUND_AMOUNT);
+    instruction.accounts[8].pubkey = system_program::id();
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::InvalidTokenProgram);
+
+    let mut instruction = initialize_pool_instruction(&pool, FUND_AMOUNT);
+    instruction.accounts[9].pubkey = spl_token::id();
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::InvalidSystemProgram);
+
+    let mut instruction = initialize_pool_instruction(&pool, FUND_AMOUNT);
+    instruction.accounts[7].pubkey = pool.owner_staking_account;
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+
+    let instruction = initialize_pool_instruction_with(&pool, 0, FUND_AMOUNT, 0, false, false);
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::RewardDurationTooShort);
+
+    let instruction = initialize_pool_instruction_with(
+        &pool,
+        MIN_REWARD_DURATION - 1,
+        FUND_AMOUNT,
+        0,
+        false,
+        false,
+    );
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::RewardDurationTooShort);
+
+    let instruction = initialize_pool_instruction_with(
+        &pool,
+        MAX_REWARD_DURATION + 1,
+        FUND_AMOUNT,
+        0,
+        false,
+        false,
+    );
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::RewardDurationTooLong);
+
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT / 2)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let result = process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT / 2)],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolAddressAlreadyInitialized);
+}
+
+#[tokio::test]
+async fn test_initialize_pool_without_pool_index() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+
+    // Clients from before pool indexes stop after the fund amount
+    let mut instruction = initialize_pool_instruction(&pool, FUND_AMOUNT);
+    instruction.data.truncate(18);
+    process(&mut context, &[instruction], &[&pool.owner])
+        .await
+        .unwrap();
+
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert_eq!(pool_data.your_staking_vault, pool.staking_vault);
+    assert_eq!({ pool_data.pool_index }, 0);
+}
+
+#[tokio::test]
+async fn test_invalid_instruction() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    let mut instruction = close_user_instruction(&pool, &user);
+    instruction.data = vec![u8::MAX];
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::InvalidInstruction);
+
+    // Empty and truncated instruction data is rejected instead of panicking
+    for data in [vec![], vec![2u8, 1, 2, 3]] {
+        let mut instruction = close_user_instruction(&pool, &user);
+        instruction.data = data;
+        let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+        assert_custom_error(result, CustomError::InvalidInstruction);
+    }
+    // CreateUser carries a referrer or nothing at all after the nonce
+    let referrer = Some(Pubkey::new_unique());
+    for length in [3, 33, 35] {
+        let mut instruction = create_user_instruction_with_referrer(&pool, &user, referrer);
+        instruction.data.resize(length, 0u8);
+        let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+        assert_custom_error(result, CustomError::InvalidInstruction);
+    }
+}
+
+#[tokio::test]
+async fn test_create_user_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let result = process(
+        &mut context,
+        &[create_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserStorageAccountAlreadyInitialized);
+
+    let other_user = setup_user(&mut context, &pool).await;
+    let mut instruction = create_user_instruction(&pool, &other_user);
+    instruction.accounts[3].pubkey = spl_token::id();
+    let result = process(&mut context, &[instruction], &[&other_user.wallet]).await;
+    assert_custom_error(result, CustomError::InvalidSystemProgram);
+
+    let instruction =
+        create_user_instruction_with_referrer(&pool, &other_user, Some(other_user.wallet.pubkey()));
+    let result = process(&mut context, &[instruction], &[&other_user.wallet]).await;
+    assert_custom_error(result, CustomError::InvalidReferrer);
+}
+
+#[tokio::test]
+async fn test_stake_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let result = process(
+        &mut context,
+        &[stake_instruction(&pool, &user, 0)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AmountMustBeGreaterThanZero);
+
+    let mut instruction = stake_instruction(&pool, &user, 1);
+    instruction.accounts[6].pubkey = system_program::id();
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::InvalidTokenProgram);
+
+    let mut instruction = stake_instruction(&pool, &user, 1);
+    instruction.accounts[3].pubkey = pool.pool_storage;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::AccountOwnerShouldBeTokenProgram);
+
+    let mut instruction = stake_instruction(&pool, &user, 1);
+    instruction.accounts[3].pubkey = pool.rewards_vault;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::InvalidStakingVault);
+
+    let mut instruction = stake_instruction(&pool, &user, 1);
+    instruction.accounts[5].pubkey = pool.rewards_mint;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+
+    // Another pool's user account derives from another pool storage address
+    let other_pool = setup_pool(&mut context, pool.program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&other_pool, FUND_AMOUNT)],
+        &[&other_pool.owner],
+    )
+    .await
+    .unwrap();
+    let mut instruction = stake_instruction(&pool, &user, 1);
+    instruction.accounts[2].pubkey = other_pool.pool_storage;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_matches!(
+        result,
+        Err(TransportError::TransactionError(
+            TransactionError::InstructionError(_, InstructionError::InvalidSeeds)
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_unstake_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let result = process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, 0)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AmountMustBeGreaterThanZero);
+
+    let result = process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT + 1)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InsufficientFundsToUnstake);
+
+    let mut instruction = unstake_instruction(&pool, &user, 1);
+    instruction.accounts[5].pubkey = pool.pool_storage;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::DerivedKeyInvalid);
+
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT / 2)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    // The unstaked tokens stay in the vault until the cooldown is over
+    let result = process(
+        &mut context,
+        &[final_unstake_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserFinalUnstakeTimeout);
+
+    warp_seconds(&mut context, 3).await;
+    process(
+        &mut context,
+        &[final_unstake_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &user.staking_account).await,
+        STAKE_AMOUNT / 2
+    );
+    assert_eq!(
+        { get_user(&mut context, &user).await.balance_your_staked },
+        STAKE_AMOUNT / 2
+    );
+}
+
+#[tokio::test]
+async fn test_claim_rewards_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    warp_seconds(&mut context, 100).await;
+
+    let result = process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.staking_vault)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InvalidRewardsVault);
+
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let first_claim = token_balance(&mut context, &user.rewards_account).await;
+    assert!(first_claim > 0);
+
+    warp_seconds(&mut context, 100).await;
+    let result = process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserClaimRewardTimeout);
+
+    warp_seconds(&mut context, CLAIM_TIMEOUT).await;
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert!(token_balance(&mut context, &user.rewards_account).await > first_claim);
+}
+
+#[tokio::test]
+async fn test_close_user_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let result = process(
+        &mut context,
+        &[close_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserBalanceNotEmpty);
+
+    warp_seconds(&mut context, 100).await;
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    warp_seconds(&mut context, 3).await;
+    process(
+        &mut context,
+        &[final_unstake_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[close_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserRewardsNotClaimed);
+
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    process(
+        &mut context,
+        &[close_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_close_user_with_pending_unstake() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    // The stake only leaves the balance once the unstake is final
+    let result = process(
+        &mut context,
+        &[close_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserBalanceNotEmpty);
+
+    // A pending unstake always stays in the balance, so the balance is cleared by hand to reach
+    // the pending unstake check
+    let mut user_data = get_user(&mut context, &user).await;
+    user_data.balance_your_staked = 0;
+    let mut user_account = context
+        .banks_client
+        .get_account(user.storage)
+        .await
+        .unwrap()
+        .unwrap();
+    user_data.pack_versioned(&mut user_account.data).unwrap();
+    context.set_account(&user.storage, &user_account.into());
+
+    let result = process(
+        &mut context,
+        &[close_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::UserUnstakePendingNotEmpty);
+}
+
+#[tokio::test]
+async fn test_close_pool_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let impostor = create_wallet(&mut context).await;
+    let result = process(
+        &mut context,
+        &[close_pool_instruction(&pool, &impostor.pubkey())],
+        &[&impostor],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+
+    // The reward period is still running
+    let result = process(
+        &mut context,
+        &[close_pool_instruction(&pool, &pool.owner.pubkey())],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolStillActive);
+
+    // The reward period is over but the user still has its stake in the pool
+    warp_seconds(&mut context, REWARD_DURATION as i64 + 1).await;
+    let result = process(
+        &mut context,
+        &[close_pool_instruction(&pool, &pool.owner.pubkey())],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolStillActive);
+
+    let mut instruction = close_pool_instruction(&pool, &pool.owner.pubkey());
+    instruction.accounts[4].pubkey = user.staking_account;
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+}
+
+#[tokio::test]
+async fn test_pool_owned_by_other_program() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    let other_user = setup_user(&mut context, &pool).await;
+    let other_pool = pool_at_index(&pool, 1);
+    let other_pool_user = user_in_pool(&user, &other_pool);
+    let nft_mint = pool.staking_mint;
+
+    // A copy of the pool data that another program could have written
+    let mut pool_account = context
+        .banks_client
+        .get_account(pool.pool_storage)
+        .await
+        .unwrap()
+        .unwrap();
+    pool_account.owner = spl_token::id();
+    context.set_account(
+        &pool.pool_storage,
+        &account::AccountSharedData::from(pool_account),
+    );
+
+    for (instruction, signer) in [
+        (
+            create_user_instruction(&pool, &other_user),
+            &other_user.wallet,
+        ),
+        (stake_instruction(&pool, &user, 1), &user.wallet),
+        (unstake_instruction(&pool, &user, 1), &user.wallet),
+        (final_unstake_instruction(&pool, &user), &user.wallet),
+        (
+            claim_rewards_instruction(&pool, &user, &pool.rewards_vault),
+            &user.wallet,
+        ),
+        (close_user_instruction(&pool, &user), &user.wallet),
+        (
+            close_pool_instruction(&pool, &pool.owner.pubkey()),
+            &pool.owner,
+        ),
+        (
+            transfer_position_instruction(&pool, &user, &other_user, 1),
+            &user.wallet,
+        ),
+        (
+            migrate_stake_instruction(&pool, &user, &other_pool, &other_pool_user),
+            &user.wallet,
+        ),
+        (set_nft_weight_instruction(&pool, &nft_mint, 1), &pool.owner),
+        (
+            stake_nft_instruction(&pool, &user, &nft_mint, &user.staking_account),
+            &user.wallet,
+        ),
+        (configure_referrals_instruction(&pool, 0, 0), &pool.owner),
+        (
+            claim_referral_rewards_instruction(
+                &pool,
+                &user.wallet.pubkey(),
+                &user,
+                &user.rewards_account,
+            ),
+            &user.wallet,
+        ),
+        (
+            set_fee_instruction(&pool, &pool.owner.pubkey(), 0, &pool.owner_rewards_account),
+            &pool.owner,
+        ),
+        (set_vesting_duration_instruction(&pool, 0), &pool.owner),
+        (withdraw_vested_instruction(&pool, &user), &user.wallet),
+        (set_paused_instruction(&pool, true), &pool.owner),
+        (fund_pool_instruction(&pool, 1), &pool.owner),
+    ] {
+        let result = process(&mut context, &[instruction], &[signer]).await;
+        assert_custom_error(result, CustomError::AccountOwnerShouldBeProgram);
+    }
+    for instruction in [
+        verify_pool_instruction(&pool, &pool.staking_vault),
+        query_pending_rewards_instruction(&pool, &user),
+        query_reward_projection_instruction(&pool, 0, 0, 0),
+    ] {
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert_custom_error(result, CustomError::AccountOwnerShouldBeProgram);
+    }
+}
+
+#[tokio::test]
+async fn test_query_pending_rewards() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(native_program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let user = setup_user(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[
+            create_user_instruction(&pool, &user),
+            stake_instruction(&pool, &user, STAKE_AMOUNT),
+        ],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    warp_seconds(&mut context, 100).await;
+
+    let return_data = query(
+        &mut context,
+        query_pending_rewards_instruction(&pool, &user),
+    )
+    .await;
+    let pending_rewards = u64::from_le_bytes(return_data[..].try_into().unwrap());
+    // The same number clients get off-chain from the accounts and the cluster clock
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(
+        pending_rewards,
+        utils::pending_rewards(
+            &get_pool(&mut context, &pool).await,
+            &get_user(&mut context, &user).await,
+            clock.unix_timestamp,
+        )
+        .unwrap()
+    );
+    assert!(pending_rewards > 0);
+}
+
+#[tokio::test]
+async fn test_query_reward_projection() {
+    const STAKING_PRICE: u64 = 1_500_000;
+    const REWARDS_PRICE: u64 = 250_000;
+
+    let program_id = Pubkey::new_unique();
+    let mut context = start(native_program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let user = setup_user(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[
+            create_user_instruction(&pool, &user),
+            stake_instruction(&pool, &user, STAKE_AMOUNT),
+        ],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let return_data = query(
+        &mut context,
+        query_reward_projection_instruction(&pool, STAKE_AMOUNT, 0, 0),
+    )
+    .await;
+    assert_eq!(return_data.len(), RewardProjection::LEN);
+    let projection = RewardProjection::unpack(&return_data).unwrap();
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert_eq!(
+        projection,
+        utils::reward_projection(&pool_data, STAKE_AMOUNT, None, clock.unix_timestamp).unwrap()
+    );
+    assert!(projection.current_apr_bps > 0);
+    assert!(projection.current_apy_bps > projection.current_apr_bps);
+    assert!(projection.projected_apr_bps < projection.current_apr_bps);
+
+    let return_data = query(
+        &mut context,
+        query_reward_projection_instruction(&pool, STAKE_AMOUNT, STAKING_PRICE, REWARDS_PRICE),
+    )
+    .await;
+    let priced_projection = RewardProjection::unpack(&return_data).unwrap();
+    let prices = TokenPrices {
+        staking_price: STAKING_PRICE,
+        staking_decimals: 9,
+        rewards_price: REWARDS_PRICE,
+        rewards_decimals: 9,
+    };
+    assert_eq!(
+        priced_projection,
+        utils::reward_projection(&pool_data, STAKE_AMOUNT, Some(prices), clock.unix_timestamp)
+            .unwrap()
+    );
+    // Both mints have the same decimals, so only the price ratio scales the rate
+    assert_eq!(
+        priced_projection.current_apr_bps,
+        projection.current_apr_bps * REWARDS_PRICE / STAKING_PRICE
+    );
+
+    // The decimals are read from the pool's own mints only
+    let mut instruction =
+        query_reward_projection_instruction(&pool, STAKE_AMOUNT, STAKING_PRICE, REWARDS_PRICE);
+    instruction.accounts.swap(1, 2);
+    let result = process(&mut context, &[instruction], &[]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+}
+
+#[tokio::test]
+async fn test_query_reward_projection_without_stake() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(native_program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert_eq!({ pool_data.total_your_staked }, 0);
+
+    // The first deposit alone shares the whole reward rate
+    let return_data = query(
+        &mut context,
+        query_reward_projection_instruction(&pool, STAKE_AMOUNT, 0, 0),
+    )
+    .await;
+    let projection = RewardProjection::unpack(&return_data).unwrap();
+    assert_eq!(
+        projection.current_apr_bps,
+        pool_data.your_reward_rate * SECONDS_PER_YEAR * BPS_DENOMINATOR / STAKE_AMOUNT
+    );
+
+    // Without stake nor deposit the rate is not divided by zero
+    let return_data = query(
+        &mut context,
+        query_reward_projection_instruction(&pool, 0, 0, 0),
+    )
+    .await;
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(
+        RewardProjection::unpack(&return_data).unwrap(),
+        utils::reward_projection(&pool_data, 0, None, clock.unix_timestamp).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_verify_pool() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    warp_seconds(&mut context, 100).await;
+
+    let result = process(
+        &mut context,
+        &[verify_pool_instruction(&pool, &pool.rewards_vault)],
+        &[],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InvalidStakingVault);
+
+    process(
+        &mut context,
+        &[verify_pool_instruction(&pool, &pool.staking_vault)],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert!(pool_data.rewards_owed <= token_balance(&mut context, &pool.rewards_vault).await);
+    process(
+        &mut context,
+        &[verify_pool_instruction(&pool, &pool.staking_vault)],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_receipt_mint_pool() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    let receipt_mint = get_vault_address(&pool.pool_storage, RECEIPT_MINT_SEED, &program_id);
+
+    let mut instruction =
+        initialize_pool_instruction_with(&pool, REWARD_DURATION, FUND_AMOUNT, 0, true, false);
+    instruction
+        .accounts
+        .push(AccountMeta::new(receipt_mint, false));
+    process(&mut context, &[instruction], &[&pool.owner])
+        .await
+        .unwrap();
+    assert_eq!(
+        get_pool(&mut context, &pool).await.receipt_mint,
+        receipt_mint
+    );
+
+    let user = setup_user(&mut context, &pool).await;
+    let receipt_account =
+        create_token_account(&mut context, &receipt_mint, &user.wallet.pubkey(), 0).await;
+    process(
+        &mut context,
+        &[create_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let mut instruction = stake_instruction(&pool, &user, STAKE_AMOUNT);
+    instruction.accounts.extend([
+        AccountMeta::new(pool.staking_mint, false),
+        AccountMeta::new(receipt_account, false),
+        AccountMeta::new_readonly(pool.pool_signer, false),
+    ]);
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::InvalidReceiptMint);
+
+    let mut instruction = stake_instruction(&pool, &user, STAKE_AMOUNT);
+    instruction.accounts.extend([
+        AccountMeta::new(receipt_mint, false),
+        AccountMeta::new(receipt_account, false),
+        AccountMeta::new_readonly(pool.pool_signer, false),
+    ]);
+    process(&mut context, &[instruction], &[&user.wallet])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &receipt_account).await,
+        STAKE_AMOUNT
+    );
+
+    let mut instruction = unstake_instruction(&pool, &user, STAKE_AMOUNT / 4);
+    instruction.accounts.extend([
+        AccountMeta::new(receipt_mint, false),
+        AccountMeta::new(receipt_account, false),
+    ]);
+    process(&mut context, &[instruction], &[&user.wallet])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &receipt_account).await,
+        STAKE_AMOUNT - STAKE_AMOUNT / 4
+    );
+}
+
+#[tokio::test]
+async fn test_transfer_position() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    let destination = setup_user(&mut context, &pool).await;
+
+    warp_seconds(&mut context, 100).await;
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[transfer_position_instruction(
+            &pool,
+            &user,
+            &destination,
+            STAKE_AMOUNT + 1,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InsufficientStakeToTransfer);
+
+    let result = process(
+        &mut context,
+        &[transfer_position_instruction(&pool, &user, &user, 1)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::SameSourceAndDestination);
+
+    process(
+        &mut context,
+        &[transfer_position_instruction(
+            &pool,
+            &user,
+            &destination,
+            STAKE_AMOUNT / 4,
+        )],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let user_data = get_user(&mut context, &user).await;
+    let destination_data = get_user(&mut context, &destination).await;
+    assert_eq!(
+        { user_data.balance_your_staked },
+        STAKE_AMOUNT - STAKE_AMOUNT / 4
+    );
+    assert_eq!({ destination_data.balance_your_staked }, STAKE_AMOUNT / 4);
+    // The new account can not claim before the source could
+    assert_eq!({ destination_data.claim_timeout_date }, {
+        user_data.claim_timeout_date
+    });
+    assert_eq!({ get_pool(&mut context, &pool).await.user_stake_count }, 2);
+
+    let mut pool_data = get_pool(&mut context, &pool).await;
+    pool_data.receipt_mint = Pubkey::new_unique();
+    set_pool(&mut context, &pool, &pool_data).await;
+    let result = process(
+        &mut context,
+        &[transfer_position_instruction(
+            &pool,
+            &user,
+            &destination,
+            STAKE_AMOUNT / 4,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::ReceiptPoolNotTransferable);
+}
+
+#[tokio::test]
+async fn test_migrate_stake() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    let destination_pool = pool_at_index(&pool, 1);
+    let destination_user = user_in_pool(&user, &destination_pool);
+
+    let instruction =
+        initialize_pool_instruction_with(&destination_pool, REWARD_DURATION, 0, 1, false, false);
+    process(&mut context, &[instruction], &[&destination_pool.owner])
+        .await
+        .unwrap();
+
+    // The destination user has to be created first
+    let result = process(
+        &mut context,
+        &[migrate_stake_instruction(
+            &pool,
+            &user,
+            &destination_pool,
+            &destination_user,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AccountOwnerShouldBeProgram);
+
+    process(
+        &mut context,
+        &[create_user_instruction(
+            &destination_pool,
+            &destination_user,
+        )],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[migrate_stake_instruction(&pool, &user, &pool, &user)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::SameSourceAndDestination);
+
+    process(
+        &mut context,
+        &[migrate_stake_instruction(
+            &pool,
+            &user,
+            &destination_pool,
+            &destination_user,
+        )],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        { get_user(&mut context, &user).await.balance_your_staked },
+        0
+    );
+    assert_eq!(
+        {
+            get_user(&mut context, &destination_user)
+                .await
+                .balance_your_staked
+        },
+        STAKE_AMOUNT
+    );
+    assert_eq!(token_balance(&mut context, &pool.staking_vault).await, 0);
+    assert_eq!(
+        token_balance(&mut context, &destination_pool.staking_vault).await,
+        STAKE_AMOUNT
+    );
+    assert_eq!(
+        {
+            get_pool(&mut context, &destination_pool)
+                .await
+                .total_your_staked
+        },
+        STAKE_AMOUNT
+    );
+
+    let mut pool_data = get_pool(&mut context, &destination_pool).await;
+    pool_data.receipt_mint = Pubkey::new_unique();
+    set_pool(&mut context, &destination_pool, &pool_data).await;
+    let result = process(
+        &mut context,
+        &[migrate_stake_instruction(
+            &destination_pool,
+            &destination_user,
+            &pool,
+            &user,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::ReceiptPoolNotTransferable);
+}
+
+/// Basis points the Token-2022 stand-in keeps from every transfer
+const TRANSFER_FEE_BPS: u64 = 100;
+
+/// Stand-in for the Token-2022 program that only handles `TransferChecked`, withholding
+/// `TRANSFER_FEE_BPS` of the amount like a mint with the transfer fee extension
+fn token_2022_with_transfer_fee(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let (source, destination, authority) = (&accounts[0], &accounts[2], &accounts[3]);
+    let amount = match spl_token::instruction::TokenInstruction::unpack(input)? {
+        spl_token::instruction::TokenInstruction::TransferChecked { amount, .. } => amount,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let fee = amount * TRANSFER_FEE_BPS / 10_000;
+
+    let mut source_data = source.try_borrow_mut_data()?;
+    let mut source_account = TokenAccount::unpack(&source_data[..TokenAccount::LEN])?;
+    if source_account.owner != *authority.key || !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    source_account.amount = source_account
+        .amount
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    TokenAccount::pack(source_account, &mut source_data[..TokenAccount::LEN])?;
+
+    let mut destination_data = destination.try_borrow_mut_data()?;
+    let mut destination_account = TokenAccount::unpack(&destination_data[..TokenAccount::LEN])?;
+    destination_account.amount += amount - fee;
+    TokenAccount::pack(
+        destination_account,
+        &mut destination_data[..TokenAccount::LEN],
+    )
+}
+
+/// Moves an SPL Token account to Token-2022, appending the account type byte that follows
+/// the base state there
+async fn move_to_token_2022(context: &mut ProgramTestContext, address: &Pubkey) {
+    let mut account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    let account_type = if account.data.len() == Mint::LEN {
+        1u8
+    } else {
+        2u8
+    };
+    account.data.resize(TokenAccount::LEN, 0);
+    account.data.push(account_type);
+    account.owner = token_2022::id();
+    context.set_account(address, &account::AccountSharedData::from(account));
+}
+
+#[tokio::test]
+async fn test_stake_with_transfer_fee() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    program_test.add_program(
+        "token_2022",
+        token_2022::id(),
+        processor!(token_2022_with_transfer_fee),
+    );
+    let mut context = start(program_test).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let user = setup_user(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[create_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    for address in [pool.staking_mint, pool.staking_vault, user.staking_account] {
+        move_to_token_2022(&mut context, &address).await;
+    }
+
+    let mut instruction = stake_instruction(&pool, &user, STAKE_AMOUNT);
+    instruction.accounts[6].pubkey = token_2022::id();
+    process(&mut context, &[instruction], &[&user.wallet])
+        .await
+        .unwrap();
+
+    // Only the amount that reached the vault counts as staked
+    let amount_received = STAKE_AMOUNT - STAKE_AMOUNT * TRANSFER_FEE_BPS / 10_000;
+    assert_eq!(
+        { get_user(&mut context, &user).await.balance_your_staked },
+        amount_received
+    );
+    assert_eq!(
+        { get_pool(&mut context, &pool).await.total_your_staked },
+        amount_received
+    );
+    assert_eq!(
+        token_balance(&mut context, &pool.staking_vault).await,
+        amount_received
+    );
+}
+
+#[tokio::test]
+async fn test_mint_accounts_must_match_pool() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let mut instruction = final_unstake_instruction(&pool, &user);
+    instruction.accounts[6].pubkey = pool.rewards_mint;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+
+    let mut instruction = claim_rewards_instruction(&pool, &user, &pool.rewards_vault);
+    instruction.accounts[7].pubkey = pool.staking_mint;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+
+    // The staking and rewards mints passed in each other's place
+    let mut instruction = close_pool_instruction(&pool, &pool.owner.pubkey());
+    instruction.accounts.swap(8, 9);
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+}
+
+/// Writes the Metaplex metadata of `nft_mint`, up to its collection field
+fn set_nft_metadata(
+    context: &mut ProgramTestContext,
+    nft_mint: &Pubkey,
+    collection: &Pubkey,
+    verified: bool,
+) {
+    let mut data = vec![4u8]; // Key::MetadataV1
+    data.extend_from_slice(&Pubkey::new_unique().to_bytes()); // update_authority
+    data.extend_from_slice(&nft_mint.to_bytes());
+    data.extend_from_slice(&[0u8; 12]); // empty name, symbol and uri
+    data.extend_from_slice(&[0u8; 2]); // seller_fee_basis_points
+    data.push(0u8); // no creators
+    data.extend_from_slice(&[0u8, 1u8]); // primary_sale_happened, is_mutable
+    data.extend_from_slice(&[0u8, 0u8]); // no edition_nonce and token_standard
+    data.extend_from_slice(&[1u8, verified as u8]);
+    data.extend_from_slice(&collection.to_bytes());
+    context.set_account(
+        &get_metadata_address(nft_mint),
+        &account::AccountSharedData::from(account::Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: mpl_token_metadata::id(),
+            executable: false,
+            rent_epoch: 0,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn test_nft_staking() {
+    const NFT_WEIGHT: u64 = 25_000;
+
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    let instruction =
+        initialize_pool_instruction_with(&pool, REWARD_DURATION, FUND_AMOUNT, 0, false, true);
+    process(&mut context, &[instruction], &[&pool.owner])
+        .await
+        .unwrap();
+
+    let user = setup_user(&mut context, &pool).await;
+    let nft_mint = create_mint_with_decimals(&mut context, 0).await;
+    let user_nft_account =
+        create_token_account(&mut context, &nft_mint, &user.wallet.pubkey(), 1).await;
+    process(
+        &mut context,
+        &[create_user_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[set_nft_weight_instruction(&pool, &nft_mint, 0)],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AmountMustBeGreaterThanZero);
+    process(
+        &mut context,
+        &[set_nft_weight_instruction(&pool, &nft_mint, NFT_WEIGHT)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+
+    // Fungible staking is closed to NFT pools
+    let result = process(
+        &mut context,
+        &[stake_instruction(&pool, &user, 1)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolModeMismatched);
+
+    set_nft_metadata(&mut context, &nft_mint, &pool.staking_mint, false);
+    let result = process(
+        &mut context,
+        &[stake_nft_instruction(
+            &pool,
+            &user,
+            &nft_mint,
+            &user_nft_account,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::NftCollectionNotVerified);
+
+    set_nft_metadata(&mut context, &nft_mint, &pool.staking_mint, true);
+    process(
+        &mut context,
+        &[stake_nft_instruction(
+            &pool,
+            &user,
+            &nft_mint,
+            &user_nft_account,
+        )],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let nft_escrow =
+        get_nft_record_address(&pool.pool_storage, &nft_mint, NFT_ESCROW_SEED, &program_id);
+    assert_eq!(token_balance(&mut context, &nft_escrow).await, 1);
+    assert_eq!(token_balance(&mut context, &user_nft_account).await, 0);
+    assert_eq!(
+        { get_user(&mut context, &user).await.balance_your_staked },
+        NFT_WEIGHT
+    );
+    assert_eq!(
+        { get_pool(&mut context, &pool).await.total_your_staked },
+        NFT_WEIGHT
+    );
+
+    let pool_account = context
+        .banks_client
+        .get_account(pool.pool_storage)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut foreign_pool_account = pool_account.clone();
+    foreign_pool_account.owner = spl_token::id();
+    context.set_account(
+        &pool.pool_storage,
+        &account::AccountSharedData::from(foreign_pool_account),
+    );
+    let result = process(
+        &mut context,
+        &[unstake_nft_instruction(
+            &pool,
+            &user,
+            &nft_mint,
+            &user_nft_account,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AccountOwnerShouldBeProgram);
+    context.set_account(
+        &pool.pool_storage,
+        &account::AccountSharedData::from(pool_account),
+    );
+
+    process(
+        &mut context,
+        &[unstake_nft_instruction(
+            &pool,
+            &user,
+            &nft_mint,
+            &user_nft_account,
+        )],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(token_balance(&mut context, &user_nft_account).await, 1);
+    assert!(context
+        .banks_client
+        .get_account(nft_escrow)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        { get_user(&mut context, &user).await.balance_your_staked },
+        0
+    );
+    assert_eq!({ get_pool(&mut context, &pool).await.total_your_staked }, 0);
+}
+
+#[tokio::test]
+async fn test_referral_rewards() {
+    const REFERRAL_BPS: u16 = 1_000;
+
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    let payer = context.payer.pubkey();
+    process(
+        &mut context,
+        &[
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                &pool.rewards_mint,
+                &pool.owner_rewards_account,
+                &payer,
+                &[],
+                FUND_AMOUNT,
+            )
+            .unwrap(),
+            initialize_pool_instruction(&pool, FUND_AMOUNT),
+            configure_referrals_instruction(&pool, REFERRAL_BPS, FUND_AMOUNT),
+        ],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+
+    let referrer = setup_user(&mut context, &pool).await;
+    let referee = setup_user(&mut context, &pool).await;
+    let instruction =
+        create_user_instruction_with_referrer(&pool, &referee, Some(referrer.wallet.pubkey()));
+    process(
+        &mut context,
+        &[
+            instruction,
+            stake_instruction(&pool, &referee, STAKE_AMOUNT),
+        ],
+        &[&referee.wallet],
+    )
+    .await
+    .unwrap();
+
+    warp_seconds(&mut context, 100).await;
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &referee, STAKE_AMOUNT)],
+        &[&referee.wallet],
+    )
+    .await
+    .unwrap();
+    warp_seconds(&mut context, 3).await;
+    process(
+        &mut context,
+        &[
+            final_unstake_instruction(&pool, &referee),
+            claim_rewards_instruction(&pool, &referee, &pool.rewards_vault),
+        ],
+        &[&referee.wallet],
+    )
+    .await
+    .unwrap();
+
+    // The referrer's share is paid on top of the referee's rewards, rounded down at each settlement
+    let referee_rewards = token_balance(&mut context, &referee.rewards_account).await;
+    let referral_rewards = get_user(&mut context, &referee).await.referral_rewards_owed;
+    assert!(referral_rewards > 0);
+    assert!(referral_rewards <= referee_rewards * REFERRAL_BPS as u64 / 10_000);
+
+    // A referee leaving can not take the rewards owed to the referrer with them
+    let result = process(
+        &mut context,
+        &[close_user_instruction(&pool, &referee)],
+        &[&referee.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::ReferralRewardsNotClaimed);
+
+    let result = process(
+        &mut context,
+        &[claim_referral_rewards_instruction(
+            &pool,
+            &referee.wallet.pubkey(),
+            &referee,
+            &referee.rewards_account,
+        )],
+        &[&referee.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InvalidReferrer);
+
+    // CloseUser pays the referrer itself when given the payout accounts
+    let close_user_paying_referrer = |referrer_rewards_account| {
+        let mut instruction = close_user_instruction(&pool, &referee);
+        instruction.accounts.extend([
+            AccountMeta::new(pool.rewards_vault, false),
+            AccountMeta::new(referrer_rewards_account, false),
+            AccountMeta::new_readonly(pool.pool_signer, false),
+            AccountMeta::new_readonly(pool.rewards_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]);
+        instruction
+    };
+    let result = process(
+        &mut context,
+        &[close_user_paying_referrer(referee.rewards_account)],
+        &[&referee.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InvalidReferrer);
+
+    process(
+        &mut context,
+        &[close_user_paying_referrer(referrer.rewards_account)],
+        &[&referee.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &referrer.rewards_account).await,
+        referral_rewards
+    );
+    assert!(context
+        .banks_client
+        .get_account(referee.storage)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_configure_referrals_errors() {
+    let (mut context, pool, user) = setup_staked_user().await;
+
+    let result = process(
+        &mut context,
+        &[configure_referrals_instruction(&pool, 10_001, 0)],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::ReferralShareTooHigh);
+
+    let mut instruction = configure_referrals_instruction(&pool, 1_000, 0);
+    instruction.accounts[0].pubkey = user.wallet.pubkey();
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+
+    let mut instruction = configure_referrals_instruction(&pool, 1_000, 0);
+    instruction.accounts[2].pubkey = pool.staking_vault;
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::InvalidRewardsVault);
+
+    let mut instruction = configure_referrals_instruction(&pool, 1_000, 0);
+    instruction.accounts[4].pubkey = pool.staking_mint;
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+
+    // The budget can only be funded in the rewards mint
+    let mut instruction = configure_referrals_instruction(&pool, 1_000, 1);
+    instruction.accounts[3].pubkey = pool.owner_staking_account;
+    let result = process(&mut context, &[instruction], &[&pool.owner]).await;
+    assert_custom_error(result, CustomError::MintMismatched);
+
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert_eq!({ pool_data.referral_bps }, 0);
+    assert_eq!({ pool_data.referral_budget }, 0);
+}
+
+#[tokio::test]
+async fn test_reward_fee() {
+    const REWARD_FEE_BPS: u16 = 1_000;
+
+    let (mut context, pool, user) = setup_staked_user().await;
+    let fee_recipient =
+        create_token_account(&mut context, &pool.rewards_mint, &Pubkey::new_unique(), 0).await;
+    let owner = pool.owner.pubkey();
+
+    let result = process(
+        &mut context,
+        &[set_fee_instruction(
+            &pool,
+            &owner,
+            MAX_REWARD_FEE_BPS + 1,
+            &fee_recipient,
+        )],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::RewardFeeTooHigh);
+
+    // The cap itself is allowed
+    process(
+        &mut context,
+        &[set_fee_instruction(
+            &pool,
+            &owner,
+            MAX_REWARD_FEE_BPS,
+            &fee_recipient,
+        )],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        { get_pool(&mut context, &pool).await.reward_fee_bps },
+        MAX_REWARD_FEE_BPS
+    );
+
+    let result = process(
+        &mut context,
+        &[set_fee_instruction(
+            &pool,
+            &user.wallet.pubkey(),
+            REWARD_FEE_BPS,
+            &fee_recipient,
+        )],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+
+    let result = process(
+        &mut context,
+        &[set_fee_instruction(
+            &pool,
+            &owner,
+            REWARD_FEE_BPS,
+            &user.staking_account,
+        )],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InvalidFeeRecipient);
+
+    process(
+        &mut context,
+        &[set_fee_instruction(
+            &pool,
+            &owner,
+            REWARD_FEE_BPS,
+            &fee_recipient,
+        )],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+
+    warp_seconds(&mut context, 100).await;
+    let result = process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::InvalidFeeRecipient);
+
+    let mut instruction = claim_rewards_instruction(&pool, &user, &pool.rewards_vault);
+    instruction
+        .accounts
+        .push(AccountMeta::new(fee_recipient, false));
+    process(&mut context, &[instruction], &[&user.wallet])
+        .await
+        .unwrap();
+    let user_rewards = token_balance(&mut context, &user.rewards_account).await;
+    let fee = token_balance(&mut context, &fee_recipient).await;
+    assert!(fee > 0);
+    assert_eq!(fee, (user_rewards + fee) * REWARD_FEE_BPS as u64 / 10_000);
+}
+
+#[tokio::test]
+async fn test_vesting() {
+    const VESTING_DURATION: u64 = 1_000;
+
+    let (mut context, pool, user) = setup_staked_user().await;
+    let mut instruction = set_vesting_duration_instruction(&pool, VESTING_DURATION);
+    instruction.accounts[0].pubkey = user.wallet.pubkey();
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+    assert_eq!({ get_pool(&mut context, &pool).await.vesting_duration }, 0);
+
+    process(
+        &mut context,
+        &[set_vesting_duration_instruction(&pool, VESTING_DURATION)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+
+    warp_seconds(&mut context, 100).await;
+    process(
+        &mut context,
+        &[claim_rewards_instruction(&pool, &user, &pool.rewards_vault)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    // Claimed rewards stay in the vault until they vest
+    let user_data = get_user(&mut context, &user).await;
+    let vesting_total = user_data.vesting_total;
+    assert!(vesting_total > 0);
+    assert_eq!(
+        { user_data.vesting_end - user_data.vesting_start },
+        VESTING_DURATION as i64
+    );
+    assert_eq!(token_balance(&mut context, &user.rewards_account).await, 0);
+
+    warp_seconds(&mut context, VESTING_DURATION as i64 / 2).await;
+    process(
+        &mut context,
+        &[withdraw_vested_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let vested = token_balance(&mut context, &user.rewards_account).await;
+    assert!(vested > 0 && vested < vesting_total);
+
+    warp_seconds(&mut context, VESTING_DURATION as i64).await;
+    process(
+        &mut context,
+        &[withdraw_vested_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &user.rewards_account).await,
+        vesting_total
+    );
+    assert_eq!(
+        { get_user(&mut context, &user).await.vesting_withdrawn },
+        vesting_total
+    );
+}
+
+#[tokio::test]
+async fn test_pause_pool() {
+    let (mut context, pool, user) = setup_staked_user().await;
+    let other_user = setup_user(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[create_user_instruction(&pool, &other_user)],
+        &[&other_user.wallet],
+    )
+    .await
+    .unwrap();
+    let mut instruction = set_paused_instruction(&pool, true);
+    instruction.accounts[0].pubkey = user.wallet.pubkey();
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+
+    process(
+        &mut context,
+        &[set_paused_instruction(&pool, true)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    assert!(get_pool(&mut context, &pool).await.is_paused());
+    let result = process(
+        &mut context,
+        &[stake_instruction(&pool, &other_user, STAKE_AMOUNT)],
+        &[&other_user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::PoolPaused);
+
+    // Users can still leave a paused pool
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, 1)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    process(
+        &mut context,
+        &[set_paused_instruction(&pool, false)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    assert!(!get_pool(&mut context, &pool).await.is_paused());
+    process(
+        &mut context,
+        &[stake_instruction(&pool, &other_user, STAKE_AMOUNT)],
+        &[&other_user.wallet],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_fund_pool() {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    process(
+        &mut context,
+        &[initialize_pool_instruction(&pool, FUND_AMOUNT / 2)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let user = setup_user(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[
+            create_user_instruction(&pool, &user),
+            stake_instruction(&pool, &user, STAKE_AMOUNT),
+        ],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[fund_pool_instruction(&pool, 0)],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AmountMustBeGreaterThanZero);
+    let mut instruction = fund_pool_instruction(&pool, 1);
+    instruction.accounts[0].pubkey = user.wallet.pubkey();
+    instruction.accounts[3].pubkey = user.rewards_account;
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+
+    warp_seconds(&mut context, REWARD_DURATION as i64 / 2).await;
+    let pool_before = get_pool(&mut context, &pool).await;
+    process(
+        &mut context,
+        &[fund_pool_instruction(&pool, FUND_AMOUNT / 2)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let pool_after = get_pool(&mut context, &pool).await;
+
+    // The rewards left of the running period are spread again with the new ones
+    let now = pool_after.reward_duration_end - REWARD_DURATION;
+    let rewards_left = (pool_before.reward_duration_end - now) * pool_before.your_reward_rate;
+    assert_eq!(
+        { pool_after.your_reward_rate },
+        (FUND_AMOUNT / 2 + rewards_left) / REWARD_DURATION
+    );
+    assert_eq!({ pool_after.last_update_time }, now);
+    assert!(pool_after.rewards_owed > 0);
+    assert_eq!(
+        token_balance(&mut context, &pool.rewards_vault).await,
+        FUND_AMOUNT
+    );
+}
+
+/// Writes program-owned account data the current instructions no longer create
+async fn set_program_account(
+    context: &mut ProgramTestContext,
+    address: &Pubkey,
+    data: Vec<u8>,
+    program_id: &Pubkey,
+) {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    context.set_account(
+        address,
+        &account::AccountSharedData::from(account::Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: *program_id,
+            executable: false,
+            rent_epoch: 0,
+        }),
+    );
+}
+
+/// Starts a context with a V1 pool, its vaults and a V1 user that staked `STAKE_AMOUNT`.
+/// Returns the V1 pool address, the V1 user storage address and the V2 pool they migrate to,
+/// which keeps the V1 vaults.
+async fn setup_v1_pool() -> (ProgramTestContext, TestPool, TestUser, Pubkey, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let mut context = start(program_test(program_id)).await;
+    let pool = setup_pool(&mut context, program_id).await;
+    let user = setup_user(&mut context, &pool).await;
+
+    let pool_v1 = Pubkey::new_unique();
+    let (pool_v1_signer, pool_v1_nonce) =
+        Pubkey::find_program_address(&[&pool_v1.to_bytes()], &program_id);
+    let pool = TestPool {
+        staking_vault: create_token_account(
+            &mut context,
+            &pool.staking_mint,
+            &pool_v1_signer,
+            STAKE_AMOUNT,
+        )
+        .await,
+        rewards_vault: create_token_account(
+            &mut context,
+            &pool.rewards_mint,
+            &pool_v1_signer,
+            FUND_AMOUNT,
+        )
+        .await,
+        ..pool
+    };
+    let pool_v1_data = YourPoolV1 {
+        acc_type: AccTypesWithVersion::YourPoolDataV1 as u8,
+        owner_wallet: pool.owner.pubkey(),
+        your_staking_vault: pool.staking_vault,
+        your_reward_rate: 0,
+        your_epoch_duration: REWARD_DURATION,
+        user_stake_count: 1,
+        pda_nonce: pool_v1_nonce,
+        reward_duration_end: 0,
+    };
+    set_program_account(
+        &mut context,
+        &pool_v1,
+        pool_v1_data.try_to_vec().unwrap(),
+        &program_id,
+    )
+    .await;
+
+    let user_v1 = get_user_storage_address(&user.wallet.pubkey(), &pool_v1, &program_id);
+    let user_v1_data = UserV1 {
+        acc_type: AccTypesWithVersion::UserDataV1 as u8,
+        user_wallet: user.wallet.pubkey(),
+        your_pool: pool_v1,
+        balance_your_staked: STAKE_AMOUNT,
+        unstake_pending: 0,
+        unstake_pending_date: 0,
+        nonce: 0,
+        claim_timeout_date: 0,
+    };
+    set_program_account(
+        &mut context,
+        &user_v1,
+        user_v1_data.try_to_vec().unwrap(),
+        &program_id,
+    )
+    .await;
+    (context, pool, user, pool_v1, user_v1)
+}
+
+#[tokio::test]
+async fn test_migrate_pool_and_user() {
+    let (mut context, pool, user, pool_v1, user_v1) = setup_v1_pool().await;
+
+    let mut instruction = migrate_pool_instruction(&pool, &pool_v1);
+    instruction.accounts[0].pubkey = user.wallet.pubkey();
+    let result = process(&mut context, &[instruction], &[&user.wallet]).await;
+    assert_custom_error(result, CustomError::PoolOwnerMismatched);
+
+    // V1 accounts are closed by the migration, so copies owned by another program are rejected
+    let other_program = Pubkey::new_unique();
+    let foreign_pool_v1 = Pubkey::new_unique();
+    let pool_v1_data = context
+        .banks_client
+        .get_account(pool_v1)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    set_program_account(&mut context, &foreign_pool_v1, pool_v1_data, &other_program).await;
+    let result = process(
+        &mut context,
+        &[migrate_pool_instruction(&pool, &foreign_pool_v1)],
+        &[&pool.owner],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AccountOwnerShouldBeProgram);
+
+    let foreign_user_v1 = Pubkey::new_unique();
+    let user_v1_data = context
+        .banks_client
+        .get_account(user_v1)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    set_program_account(&mut context, &foreign_user_v1, user_v1_data, &other_program).await;
+    let result = process(
+        &mut context,
+        &[migrate_user_instruction(&pool, &user, &foreign_user_v1)],
+        &[&user.wallet],
+    )
+    .await;
+    assert_custom_error(result, CustomError::AccountOwnerShouldBeProgram);
+
+    process(
+        &mut context,
+        &[migrate_pool_instruction(&pool, &pool_v1)],
+        &[&pool.owner],
+    )
+    .await
+    .unwrap();
+    let pool_data = get_pool(&mut context, &pool).await;
+    assert_eq!(pool_data.migrated_from, pool_v1);
+    assert_eq!(pool_data.your_staking_vault, pool.staking_vault);
+    assert_eq!({ pool_data.total_your_staked }, STAKE_AMOUNT);
+    // Users are only counted once they migrate
+    assert_eq!({ pool_data.user_stake_count }, 0);
+    // The V1 reward period already ended, so nothing accrues after the migration
+    assert_eq!({ pool_data.last_update_time }, 0);
+    assert!(context
+        .banks_client
+        .get_account(pool_v1)
+        .await
+        .unwrap()
+        .is_none());
+
+    process(
+        &mut context,
+        &[migrate_user_instruction(&pool, &user, &user_v1)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    let user_data = get_user(&mut context, &user).await;
+    assert!(!user_data.is_v1());
+    assert_eq!(user_data.your_pool, pool.pool_storage);
+    assert_eq!({ user_data.balance_your_staked }, STAKE_AMOUNT);
+    assert_eq!({ get_pool(&mut context, &pool).await.user_stake_count }, 1);
+    assert!(context
+        .banks_client
+        .get_account(user_v1)
+        .await
+        .unwrap()
+        .is_none());
+
+    // The vaults moved to the V2 pool signer, so the stake unstakes from the V2 pool
+    process(
+        &mut context,
+        &[unstake_instruction(&pool, &user, STAKE_AMOUNT)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    warp_seconds(&mut context, 3).await;
+    process(
+        &mut context,
+        &[final_unstake_instruction(&pool, &user)],
+        &[&user.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &user.staking_account).await,
+        2 * STAKE_AMOUNT
+    );
+}